@@ -0,0 +1,524 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+use yx_protocol::{LawState, Severity};
+
+const USAGE: &str = "usage: yx <command> [args]
+
+  whoami
+  caps [--refresh]
+  allowed <command> <law-state>
+  audit [--ws WS] [--command NAME] [--trace TRACE_ID] [--limit N]
+  audit verify
+  law status
+  law snapshot
+  law history [--limit N]
+  law diff <from-seq> <to-seq>
+  law since <ts_ms|30m|8h|2d>
+  events [--ws WS] [--since T] [--until T] [--topic GLOB] [--severity S] [--trace ID]
+         [--seq-from N] [--seq-to N] [--before ID] [--limit N]
+  events stats [--ws WS]
+  events export --out FILE|- [--format ndjson|csv|otlp] [--redact KEY,...]
+         [--ws WS] [event filters]
+  search <query...> [--since T] [--until T] [--limit N]
+         query: terms, \"phrases\", topic:GLOB, severity:S, ws:WS
+  trace <trace-id>
+  providers list|status
+  providers health [--probe]
+  providers discover [--endpoint URL] [--model MODEL]
+  chat sessions
+  chat history <session-id>
+  chat send <session-id> <text...> [--law] [--events GLOB] [--events-limit N]
+         [--logs N] [--result TRACE_ID] [--trace TRACE_ID] [--budget BYTES] [--dry-run]
+  chat search <query...> [--session ID] [--role ROLE] [--limit N]
+  chat export <session-id> [--format md|json] [--out FILE]
+  shell [--cwd DIR] [--env K=V,...] [--stdin TEXT|-] [--timeout MS]
+        [--max-output BYTES] [--arm REASON] [--json] <cmd> [args...]
+  pty [--cwd DIR] [--record] [--record-input] [--title TEXT] [--arm REASON] [cmd] [args...]
+  recordings
+  recording <id> [--cast]
+  down preflight
+  down [--force] [--shutdown] [--confirm WS] [--arm REASON] [--timeout SECS]
+  usage [--by session|provider|model|ws|day] [--since T] [--until T] [--session ID]
+        [--ws WS | --all]
+
+The workspace is taken from YAI_WS, then yai.toml.";
+
+/// Flags that never take a value, so they do not eat the word after them.
+const SWITCHES: [&str; 11] = [
+    "all",
+    "cast",
+    "dry-run",
+    "force",
+    "json",
+    "law",
+    "probe",
+    "record",
+    "record-input",
+    "refresh",
+    "shutdown",
+];
+
+struct Args {
+    positional: Vec<String>,
+    flags: HashMap<String, Option<String>>,
+}
+
+impl Args {
+    fn parse(raw: impl Iterator<Item = String>) -> Args {
+        let mut positional: Vec<String> = Vec::new();
+        let mut flags = HashMap::new();
+        let mut raw = raw.peekable();
+        // after `--`, and after the remote command of `shell` and `pty`,
+        // everything is passed on as it is
+        let mut verbatim = false;
+        while let Some(arg) = raw.next() {
+            if verbatim {
+                positional.push(arg);
+            } else if arg == "--" {
+                verbatim = true;
+            } else if let Some(name) = arg.strip_prefix("--") {
+                let value = if SWITCHES.contains(&name) {
+                    None
+                } else {
+                    raw.next_if(|next| !next.starts_with("--"))
+                };
+                flags.insert(name.to_string(), value);
+            } else {
+                positional.push(arg);
+                verbatim =
+                    positional.len() == 2 && matches!(positional[0].as_str(), "shell" | "pty");
+            }
+        }
+        Args { positional, flags }
+    }
+
+    fn arg(&self, idx: usize, what: &str) -> Result<&str> {
+        self.positional
+            .get(idx)
+            .map(String::as_str)
+            .ok_or_else(|| anyhow!("missing <{what}>"))
+    }
+
+    fn flag(&self, name: &str) -> Option<&str> {
+        self.flags.get(name).and_then(|v| v.as_deref())
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.flags.contains_key(name)
+    }
+
+    fn number<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>> {
+        self.flag(name)
+            .map(|v| v.parse().map_err(|_| anyhow!("--{name} expects a number")))
+            .transpose()
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Absolute `ts_ms`, or a duration back from now such as `30m`, `8h`, `2d`.
+fn parse_since(raw: &str) -> Result<u64> {
+    if let Ok(ts) = raw.parse::<u64>() {
+        return Ok(ts);
+    }
+    let (num, unit) = raw.split_at(raw.len().saturating_sub(1));
+    let num: u64 = num
+        .parse()
+        .with_context(|| format!("invalid time: {raw}"))?;
+    let unit_ms = match unit {
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        _ => bail!("invalid time unit in {raw}, expected s, m, h or d"),
+    };
+    Ok(now_ms().saturating_sub(num * unit_ms))
+}
+
+fn print<T: Serialize>(value: &T) -> Result<()> {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&yx_client::redacted(value))?
+    );
+    Ok(())
+}
+
+fn event_query(args: &Args) -> Result<yx_client::EventQuery> {
+    Ok(yx_client::EventQuery {
+        ws: args.flag("ws").map(ToString::to_string),
+        since_ms: args.flag("since").map(parse_since).transpose()?,
+        until_ms: args.flag("until").map(parse_since).transpose()?,
+        topic: args.flag("topic").map(ToString::to_string),
+        severity: args.flag("severity").map(Severity::parse),
+        trace_id: args.flag("trace").map(ToString::to_string),
+        seq_from: args.number("seq-from")?,
+        seq_to: args.number("seq-to")?,
+        before_id: args.number("before")?,
+        limit: args.number("limit")?,
+    })
+}
+
+fn run(args: &Args) -> Result<()> {
+    let command = args.positional.first().map(String::as_str);
+    match command {
+        Some("whoami") => print(&yx_client::whoami()),
+        Some("caps") => print(&yx_client::capability_catalog(args.has("refresh"))),
+        Some("allowed") => {
+            let state: LawState = args
+                .arg(2, "law-state")?
+                .parse()
+                .map_err(|e| anyhow!("{e}"))?;
+            print(&yx_client::is_allowed(args.arg(1, "command")?, state))
+        }
+        Some("audit") if args.positional.get(1).map(String::as_str) == Some("verify") => {
+            print(&yx_client::audit_verify()?)
+        }
+        Some("audit") => {
+            let filter = yx_client::AuditQuery {
+                workspace: args.flag("ws").map(ToString::to_string),
+                command: args.flag("command").map(ToString::to_string),
+                trace_id: args.flag("trace").map(ToString::to_string),
+                limit: args.number("limit")?,
+                ..Default::default()
+            };
+            print(&yx_client::audit_query(&filter)?)
+        }
+        Some("law") => match args.positional.get(1).map(String::as_str) {
+            Some("status") => {
+                // the tracker is per process: read the state fresh from the daemon
+                yx_client::send_command("status", json!({}), false);
+                print(&yx_client::law_status())
+            }
+            Some("history") => {
+                let limit = args
+                    .number("limit")?
+                    .unwrap_or(yx_client::LAW_HISTORY_LIMIT);
+                print(&yx_client::law_history(limit)?)
+            }
+            Some("diff") => {
+                let from = args
+                    .arg(2, "from-seq")?
+                    .parse()
+                    .context("invalid from-seq")?;
+                let to = args.arg(3, "to-seq")?.parse().context("invalid to-seq")?;
+                print(&yx_client::law_diff(from, to)?)
+            }
+            Some("since") => {
+                let since = parse_since(args.arg(2, "since")?)?;
+                print(&yx_client::law_diff_since(since)?)
+            }
+            Some("snapshot") => print(&yx_client::send_command("law.snapshot", json!({}), false)),
+            _ => bail!("unknown law subcommand\n\n{USAGE}"),
+        },
+        Some("events") if args.positional.get(1).map(String::as_str) == Some("stats") => {
+            print(&yx_client::events_stats(args.flag("ws")))
+        }
+        Some("events") if args.positional.get(1).map(String::as_str) == Some("export") => {
+            let path = args.flag("out").context("missing --out FILE")?;
+            let request = yx_client::ExportRequest {
+                query: event_query(args)?,
+                format: match args.flag("format") {
+                    Some(raw) => raw.parse().map_err(|e| anyhow!("{e}"))?,
+                    None => yx_client::ExportFormat::default(),
+                },
+                path: path.to_string(),
+                redact: args
+                    .flag("redact")
+                    .map(|keys| keys.split(',').map(|k| k.trim().to_string()).collect())
+                    .unwrap_or_default(),
+            };
+            let summary = yx_client::events_export(&request)?;
+            if path == "-" {
+                return Ok(());
+            }
+            print(&summary)
+        }
+        Some("events") => print(&yx_client::events_query(&event_query(args)?)),
+        Some("search") => {
+            let request = yx_client::SearchRequest {
+                q: args.positional[1..].join(" "),
+                since_ms: args.flag("since").map(parse_since).transpose()?,
+                until_ms: args.flag("until").map(parse_since).transpose()?,
+                limit: args.number("limit")?,
+            };
+            print(&yx_client::search(&request))
+        }
+        Some("trace") => print(&yx_client::trace(args.arg(1, "trace-id")?)),
+        Some("providers") => match args.positional.get(1).map(String::as_str) {
+            Some("list") | None => print(&yx_client::providers_list()?),
+            Some("status") => print(&yx_client::providers_status()?),
+            Some("health") if args.has("probe") => print(&yx_client::providers_probe()),
+            Some("health") => print(&yx_client::providers_health(None)),
+            Some("discover") => print(&yx_client::providers_discover(
+                args.flag("endpoint"),
+                args.flag("model"),
+            )?),
+            _ => bail!("unknown providers subcommand\n\n{USAGE}"),
+        },
+        Some("chat") => match args.positional.get(1).map(String::as_str) {
+            Some("sessions") | None => print(&yx_client::chat_sessions()?),
+            Some("history") => print(&yx_client::chat_history(args.arg(2, "session-id")?)?),
+            Some("send") => {
+                let session_id = args.arg(2, "session-id")?;
+                let text = args.positional.get(3..).unwrap_or_default().join(" ");
+                if text.is_empty() {
+                    bail!("missing text\n\n{USAGE}");
+                }
+                let context = chat_context(args)?;
+                if args.has("dry-run") {
+                    return print(&yx_client::chat_context(&context)?);
+                }
+                print(&yx_client::chat_send(session_id, &text, Some(&context))?)
+            }
+            Some("search") => {
+                let request = yx_client::ChatSearchRequest {
+                    q: args.positional[2..].join(" "),
+                    session_id: args.flag("session").map(str::to_string),
+                    role: args.flag("role").map(|r| r.to_string().into()),
+                    limit: args.number("limit")?,
+                };
+                print(&yx_client::chat_search(&request))
+            }
+            Some("export") => {
+                let format = match args.flag("format") {
+                    Some(raw) => raw.parse().map_err(|e| anyhow!("{e}"))?,
+                    None => yx_client::ChatExportFormat::default(),
+                };
+                let doc = yx_client::chat_export(args.arg(2, "session-id")?, format)?;
+                match args.flag("out") {
+                    Some(path) if path != "-" => {
+                        std::fs::write(path, doc).with_context(|| format!("Failed to write {path}"))
+                    }
+                    _ => {
+                        print!("{doc}");
+                        Ok(())
+                    }
+                }
+            }
+            _ => bail!("unknown chat subcommand\n\n{USAGE}"),
+        },
+        Some("shell") => shell(args),
+        Some("pty") => pty(args),
+        Some("recordings") => print(&yx_client::recordings()),
+        Some("recording") if args.has("cast") => {
+            print!("{}", yx_client::recording_cast(args.arg(1, "id")?)?);
+            Ok(())
+        }
+        Some("recording") => print(&yx_client::recording(args.arg(1, "id")?)?),
+        Some("down") if args.positional.get(1).map(String::as_str) == Some("preflight") => {
+            print(&yx_client::down_preflight())
+        }
+        Some("down") => down(args),
+        Some("usage") => {
+            let query = yx_client::UsageQuery {
+                ws: args.flag("ws").map(str::to_string),
+                all_workspaces: args.has("all"),
+                since_ms: args.flag("since").map(parse_since).transpose()?,
+                until_ms: args.flag("until").map(parse_since).transpose()?,
+                session_id: args.flag("session").map(str::to_string),
+                group_by: match args.flag("by") {
+                    Some(raw) => raw.parse().map_err(|e| anyhow!("{e}"))?,
+                    None => yx_client::UsageGroupBy::default(),
+                },
+            };
+            print(&yx_client::usage_report(&query))
+        }
+        Some("help") | None => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Some(other) => bail!("unknown command: {other}\n\n{USAGE}"),
+    }
+}
+
+/// Streams a `shell.exec` to the terminal; `--json` prints each chunk and
+/// the exit as a JSON line instead.
+fn shell(args: &Args) -> Result<()> {
+    let mut request = yx_client::ShellExecRequest::new(args.arg(1, "cmd")?);
+    request.args = args.positional[2..].to_vec();
+    request.cwd = args.flag("cwd").map(str::to_string);
+    for pair in args.flag("env").into_iter().flat_map(|v| v.split(',')) {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| anyhow!("--env expects KEY=VALUE, got {pair}"))?;
+        request.env.insert(key.to_string(), value.to_string());
+    }
+    request.stdin = match args.flag("stdin") {
+        Some("-") => Some(std::io::read_to_string(std::io::stdin())?),
+        other => other.map(str::to_string),
+    };
+    request.timeout_ms = args.number("timeout")?;
+    request.max_output_bytes = args.number("max-output")?;
+    // arming only lives as long as the process, so it is taken per call and
+    // also covers shell policy rules that want the command named
+    if let Some(reason) = args.flag("arm") {
+        let ttl_secs = request.timeout().as_secs() + 60;
+        let program = request.cmd.split_whitespace().next().unwrap_or_default();
+        let program = program.rsplit('/').next().unwrap_or(program);
+        let scope = vec!["shell.exec".to_string(), format!("shell.exec:{program}")];
+        yx_client::arm(reason, scope, ttl_secs)?;
+    }
+    let json = args.has("json");
+    let exit = yx_client::shell_exec_stream(&request, |output| {
+        if json {
+            if let Ok(line) = serde_json::to_string(output) {
+                println!("{line}");
+            }
+            return;
+        }
+        if let yx_client::ShellOutput::Chunk(chunk) = output {
+            match chunk.stream {
+                yx_client::ShellStream::Stdout => print!("{}", chunk.data),
+                yx_client::ShellStream::Stderr => eprint!("{}", chunk.data),
+            }
+            let _ = std::io::Write::flush(&mut std::io::stdout());
+        }
+    })?;
+    if exit.truncated && !json {
+        eprintln!("yx: output truncated after {} bytes", request.max_output());
+    }
+    match (exit.exit_code, &exit.signal) {
+        _ if exit.timed_out => bail!("timed out after {} ms", request.timeout().as_millis()),
+        (Some(0), _) => Ok(()),
+        (Some(code), _) => bail!("exited with code {code}"),
+        (None, Some(signal)) => bail!("killed by signal {signal}"),
+        (None, None) => bail!("exited without a status"),
+    }
+}
+
+/// Opens a PTY in line mode: each line read from stdin is sent with its
+/// newline, and end of input closes the session.
+fn pty(args: &Args) -> Result<()> {
+    let request = yx_client::PtyOpenRequest {
+        cmd: args.positional.get(1).cloned(),
+        args: args.positional.get(2..).unwrap_or_default().to_vec(),
+        cwd: args.flag("cwd").map(str::to_string),
+        record: args.has("record"),
+        record_input: args.has("record-input"),
+        title: args.flag("title").map(str::to_string),
+        ..Default::default()
+    };
+    if let Some(reason) = args.flag("arm") {
+        yx_client::arm(reason, vec!["shell.pty.open".to_string()], 60)?;
+    }
+    let (exited, exit) = std::sync::mpsc::channel();
+    let session = yx_client::pty_open(&request, move |output| match output {
+        yx_client::PtyOutput::Data { data, .. } => {
+            print!("{data}");
+            let _ = std::io::Write::flush(&mut std::io::stdout());
+        }
+        yx_client::PtyOutput::Exit {
+            exit_code, signal, ..
+        } => {
+            let _ = exited.send((*exit_code, signal.clone()));
+        }
+    })?;
+    if let Some(id) = &session.recording {
+        eprintln!("yx: recording {id}");
+    }
+    let pty_id = session.pty_id.clone();
+    let closed = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let closing = std::sync::Arc::clone(&closed);
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            if let Err(e) = yx_client::pty_write(&pty_id, &format!("{line}\n")) {
+                eprintln!("yx: {}", e.message);
+                break;
+            }
+        }
+        closing.store(true, std::sync::atomic::Ordering::Relaxed);
+        let _ = yx_client::pty_close(&pty_id);
+    });
+    match exit.recv()? {
+        (Some(0), _) => Ok(()),
+        // end of input closed it
+        (None, _) if closed.load(std::sync::atomic::Ordering::Relaxed) => Ok(()),
+        (Some(code), _) => bail!("exited with code {code}"),
+        (None, Some(signal)) => bail!("ended by signal {signal}"),
+        (None, None) => bail!("exited without a status"),
+    }
+}
+
+/// Runs the guarded `down`, printing each phase to stderr and the outcome
+/// as JSON.
+fn down(args: &Args) -> Result<()> {
+    let request = yx_client::DownRequest {
+        force: args.has("force"),
+        shutdown: args.has("shutdown"),
+        confirm: args.flag("confirm").map(str::to_string),
+        timeout_secs: args.number("timeout")?,
+    };
+    if let Some(reason) = args.flag("arm") {
+        yx_client::arm(reason, vec!["down".to_string()], 120)?;
+    }
+    let outcome = yx_client::down(&request, |progress| {
+        eprintln!("yx: {}", progress.message);
+        if progress.phase == yx_client::DownPhase::Preflight {
+            for blocker in progress.detail["blockers"].as_array().into_iter().flatten() {
+                eprintln!("yx:   {}", blocker.as_str().unwrap_or_default());
+            }
+        }
+    })?;
+    print(&outcome)?;
+    if outcome.phase != yx_client::DownPhase::Stopped {
+        bail!("'{}' did not go away within the timeout", outcome.ws);
+    }
+    Ok(())
+}
+
+fn chat_context(args: &Args) -> Result<yx_client::ChatContextRequest> {
+    let mut items = Vec::new();
+    if args.has("law") {
+        items.push(yx_client::ContextItem::LawSnapshot);
+    }
+    if args.has("events") {
+        items.push(yx_client::ContextItem::Events {
+            query: yx_client::EventQuery {
+                topic: args.flag("events").map(str::to_string),
+                limit: args.number("events-limit")?,
+                ..Default::default()
+            },
+        });
+    }
+    if args.has("logs") {
+        items.push(yx_client::ContextItem::Logs {
+            lines: args.number("logs")?,
+        });
+    }
+    if let Some(trace_id) = args.flag("result") {
+        items.push(yx_client::ContextItem::CommandResult {
+            trace_id: trace_id.to_string(),
+        });
+    }
+    if let Some(trace_id) = args.flag("trace") {
+        items.push(yx_client::ContextItem::Trace {
+            trace_id: trace_id.to_string(),
+        });
+    }
+    Ok(yx_client::ChatContextRequest {
+        items,
+        budget_bytes: args.number("budget")?,
+    })
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse(env::args().skip(1));
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("yx: {error:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
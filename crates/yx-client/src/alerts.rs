@@ -0,0 +1,309 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use yx_protocol::{Event as ProtocolEvent, Severity};
+
+use crate::config::{AlertRule, AlertsConfig};
+use crate::event_store::glob_match;
+
+/// Resolved alerts kept in memory; active ones are never dropped.
+const RESOLVED_ALERT_LIMIT: usize = 500;
+
+type AlertListener = Box<dyn Fn(&Alert) + Send>;
+
+static ALERTS: Mutex<Option<AlertBook>> = Mutex::new(None);
+static LISTENERS: Mutex<Vec<AlertListener>> = Mutex::new(Vec::new());
+static ALERT_SEQ: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Firing,
+    Acknowledged,
+    Resolved,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    pub id: String,
+    pub rule_id: String,
+    pub ws: String,
+    /// Value of the rule's `group_by`, empty when it has none.
+    pub group: String,
+    pub status: AlertStatus,
+    pub severity: Severity,
+    pub message: String,
+    /// Matches folded into this alert since it was raised.
+    pub count: u64,
+    pub first_ts_ms: u64,
+    pub last_ts_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acked_at_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_at_ms: Option<u64>,
+    pub notify: bool,
+    /// The most recent matching event.
+    pub last_event: ProtocolEvent,
+}
+
+impl Alert {
+    pub fn is_active(&self) -> bool {
+        self.status != AlertStatus::Resolved
+    }
+}
+
+/// (ws, rule id, group)
+type AlertKey = (String, String, String);
+
+#[derive(Default)]
+struct AlertBook {
+    alerts: Vec<Alert>,
+    windows: HashMap<AlertKey, VecDeque<u64>>,
+    last_raised_ms: HashMap<AlertKey, u64>,
+}
+
+/// Minimal JSONPath: `$`, `.key` and `[index]` steps, e.g. `$.data.items[0].id`.
+pub(crate) fn json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut rest = path.trim().strip_prefix('$').unwrap_or(path.trim());
+    let mut current = value;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            let step = after[..end].trim_matches(|c| c == '\'' || c == '"');
+            current = match step.parse::<usize>() {
+                Ok(idx) => current.get(idx)?,
+                Err(_) => current.get(step)?,
+            };
+            rest = &after[end + 1..];
+        } else {
+            let after = rest.strip_prefix('.').unwrap_or(rest);
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            current = current.get(&after[..end])?;
+            rest = &after[end..];
+        }
+    }
+    Some(current)
+}
+
+fn rule_matches(rule: &AlertRule, event: &ProtocolEvent, doc: &Value) -> bool {
+    if rule
+        .topic
+        .as_deref()
+        .is_some_and(|p| !glob_match(p, &event.topic))
+    {
+        return false;
+    }
+    if rule
+        .min_severity
+        .as_ref()
+        .is_some_and(|min| event.severity.rank() < min.rank())
+    {
+        return false;
+    }
+    match &rule.path {
+        Some(path) => match json_path(doc, path) {
+            None | Some(Value::Null) => false,
+            Some(found) => rule.equals.as_ref().is_none_or(|want| found == want),
+        },
+        None => true,
+    }
+}
+
+fn group_of(rule: &AlertRule, doc: &Value) -> String {
+    let Some(path) = &rule.group_by else {
+        return String::new();
+    };
+    match json_path(doc, path) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn message_for(rule: &AlertRule, event: &ProtocolEvent, group: &str) -> String {
+    if let Some(message) = &rule.message {
+        return message.clone();
+    }
+    let subject = if group.is_empty() {
+        event.topic.clone()
+    } else {
+        format!("{} ({group})", event.topic)
+    };
+    if rule.threshold == 0 {
+        format!("{}: {subject}", rule.id)
+    } else {
+        format!(
+            "{}: more than {} {subject} in {}s",
+            rule.id, rule.threshold, rule.window_secs
+        )
+    }
+}
+
+impl AlertBook {
+    /// Applies one rule to one event; returns the alert to announce, if any.
+    fn apply(
+        &mut self,
+        ws: &str,
+        rule: &AlertRule,
+        event: &ProtocolEvent,
+        doc: &Value,
+        now: u64,
+    ) -> Option<Alert> {
+        let group = group_of(rule, doc);
+        let key = (ws.to_string(), rule.id.clone(), group.clone());
+
+        // dedup: an open alert absorbs further matches quietly, and is only
+        // announced again once the cooldown has passed
+        if let Some(open) = self
+            .alerts
+            .iter_mut()
+            .find(|a| a.is_active() && a.ws == ws && a.rule_id == rule.id && a.group == group)
+        {
+            open.count += 1;
+            open.last_ts_ms = now;
+            open.last_event = event.clone();
+            let raised = self.last_raised_ms.entry(key).or_insert(now);
+            if rule.cooldown_secs == 0 || now.saturating_sub(*raised) < rule.cooldown_secs * 1000 {
+                return None;
+            }
+            *raised = now;
+            return Some(open.clone());
+        }
+
+        let window = self.windows.entry(key.clone()).or_default();
+        window.push_back(now);
+        let horizon = now.saturating_sub(rule.window_secs * 1000);
+        while window.front().is_some_and(|ts| *ts < horizon) {
+            window.pop_front();
+        }
+        if window.len() as u64 <= u64::from(rule.threshold) {
+            return None;
+        }
+        if self
+            .last_raised_ms
+            .get(&key)
+            .is_some_and(|ts| now.saturating_sub(*ts) < rule.cooldown_secs * 1000)
+        {
+            return None;
+        }
+
+        let count = window.len() as u64;
+        window.clear();
+        self.last_raised_ms.insert(key, now);
+        let alert = Alert {
+            id: format!("alert-{now}-{}", ALERT_SEQ.fetch_add(1, Ordering::Relaxed)),
+            rule_id: rule.id.clone(),
+            ws: ws.to_string(),
+            message: message_for(rule, event, &group),
+            group,
+            status: AlertStatus::Firing,
+            severity: rule
+                .severity
+                .clone()
+                .unwrap_or_else(|| event.severity.clone()),
+            count,
+            first_ts_ms: now,
+            last_ts_ms: now,
+            acked_at_ms: None,
+            resolved_at_ms: None,
+            notify: rule.notify,
+            last_event: event.clone(),
+        };
+        self.alerts.push(alert.clone());
+        Some(alert)
+    }
+
+    fn prune(&mut self) {
+        let resolved = self.alerts.iter().filter(|a| !a.is_active()).count();
+        let mut excess = resolved.saturating_sub(RESOLVED_ALERT_LIMIT);
+        self.alerts.retain(|a| {
+            if excess > 0 && !a.is_active() {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+    }
+}
+
+fn notify(alert: &Alert) {
+    let listeners = LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+    for listener in listeners.iter() {
+        listener(alert);
+    }
+}
+
+pub(crate) fn subscribe(listener: AlertListener) {
+    let mut listeners = LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+    listeners.push(listener);
+}
+
+/// Runs every configured rule against `event`.
+pub(crate) fn observe(ws: &str, event: &ProtocolEvent, config: &AlertsConfig) {
+    if config.rules.is_empty() {
+        return;
+    }
+    let doc = serde_json::to_value(event).unwrap_or(Value::Null);
+    let now = crate::now_ms();
+    let raised: Vec<Alert> = {
+        let mut slot = ALERTS.lock().unwrap_or_else(|e| e.into_inner());
+        let book = slot.get_or_insert_with(AlertBook::default);
+        config
+            .rules
+            .iter()
+            .filter(|rule| rule_matches(rule, event, &doc))
+            .filter_map(|rule| book.apply(ws, rule, event, &doc, now))
+            .collect()
+    };
+    for alert in &raised {
+        notify(alert);
+    }
+}
+
+/// Newest first.
+pub(crate) fn list(include_resolved: bool) -> Vec<Alert> {
+    let slot = ALERTS.lock().unwrap_or_else(|e| e.into_inner());
+    slot.iter()
+        .flat_map(|book| book.alerts.iter().rev())
+        .filter(|a| include_resolved || a.is_active())
+        .cloned()
+        .collect()
+}
+
+fn transition(id: &str, status: AlertStatus) -> Result<Alert> {
+    let alert = {
+        let mut slot = ALERTS.lock().unwrap_or_else(|e| e.into_inner());
+        let book = slot.get_or_insert_with(AlertBook::default);
+        let alert = book
+            .alerts
+            .iter_mut()
+            .find(|a| a.id == id)
+            .ok_or_else(|| anyhow!("unknown alert: {id}"))?;
+        if !alert.is_active() {
+            return Err(anyhow!("alert {id} is already resolved"));
+        }
+        let now = crate::now_ms();
+        match status {
+            AlertStatus::Acknowledged => alert.acked_at_ms = Some(now),
+            AlertStatus::Resolved => alert.resolved_at_ms = Some(now),
+            AlertStatus::Firing => {}
+        }
+        alert.status = status;
+        let alert = alert.clone();
+        book.prune();
+        alert
+    };
+    notify(&alert);
+    Ok(alert)
+}
+
+pub(crate) fn acknowledge(id: &str) -> Result<Alert> {
+    transition(id, AlertStatus::Acknowledged)
+}
+
+pub(crate) fn resolve(id: &str) -> Result<Alert> {
+    transition(id, AlertStatus::Resolved)
+}
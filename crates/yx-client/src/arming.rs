@@ -0,0 +1,167 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+pub const MAX_ARMING_TTL_SECS: u64 = 30 * 60;
+const ARMED_COMMANDS: [&str; 7] = [
+    "down",
+    "shell.exec",
+    "shell.pty.open",
+    "providers.pair",
+    "providers.attach",
+    "providers.detach",
+    "providers.revoke",
+];
+
+static ARMING: Mutex<ArmingSlot> = Mutex::new(ArmingSlot {
+    session: None,
+    last_disarm: None,
+});
+
+struct ArmingSlot {
+    session: Option<ArmingSession>,
+    last_disarm: Option<Disarm>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArmingSession {
+    pub id: String,
+    pub ws: String,
+    pub reason: String,
+    /// Command names covered by the session; `"*"` covers every command.
+    pub scope: Vec<String>,
+    pub armed_at_ms: u64,
+    pub expires_at_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DisarmReason {
+    Manual,
+    Expired,
+    WorkspaceSwitch,
+    Lockdown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Disarm {
+    pub session_id: String,
+    pub ws: String,
+    pub reason: DisarmReason,
+    pub ts_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArmingState {
+    pub armed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<ArmingSession>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_disarm: Option<Disarm>,
+}
+
+impl ArmingSession {
+    pub fn covers(&self, name: &str) -> bool {
+        self.scope.iter().any(|s| s == "*" || s == name)
+    }
+}
+
+/// Commands the daemon only accepts from an armed operator.
+pub fn requires_arming(name: &str) -> bool {
+    ARMED_COMMANDS.contains(&name)
+}
+
+pub(crate) fn arm(
+    ws: &str,
+    reason: &str,
+    scope: Vec<String>,
+    ttl_secs: u64,
+) -> Result<ArmingSession> {
+    if reason.trim().is_empty() {
+        return Err(anyhow!("arming requires a reason"));
+    }
+    if scope.is_empty() {
+        return Err(anyhow!("arming requires a non-empty scope"));
+    }
+    if ttl_secs == 0 || ttl_secs > MAX_ARMING_TTL_SECS {
+        return Err(anyhow!(
+            "arming ttl must be between 1 and {MAX_ARMING_TTL_SECS} seconds"
+        ));
+    }
+    let now = crate::now_ms();
+    let session = ArmingSession {
+        id: format!("arm-{}", crate::next_trace_id()),
+        ws: ws.to_string(),
+        reason: reason.trim().to_string(),
+        scope,
+        armed_at_ms: now,
+        expires_at_ms: now + ttl_secs * 1000,
+    };
+    let mut slot = ARMING.lock().unwrap_or_else(|e| e.into_inner());
+    slot.session = Some(session.clone());
+    Ok(session)
+}
+
+pub(crate) fn disarm(reason: DisarmReason) -> Option<ArmingSession> {
+    let mut slot = ARMING.lock().unwrap_or_else(|e| e.into_inner());
+    take_session(&mut slot, reason)
+}
+
+/// `disarm`, but only a session armed for `ws`.
+pub(crate) fn disarm_ws(ws: &str, reason: DisarmReason) -> Option<ArmingSession> {
+    let mut slot = ARMING.lock().unwrap_or_else(|e| e.into_inner());
+    if slot.session.as_ref()?.ws != ws {
+        return None;
+    }
+    take_session(&mut slot, reason)
+}
+
+fn take_session(slot: &mut ArmingSlot, reason: DisarmReason) -> Option<ArmingSession> {
+    let session = slot.session.take()?;
+    slot.last_disarm = Some(Disarm {
+        session_id: session.id.clone(),
+        ws: session.ws.clone(),
+        reason,
+        ts_ms: crate::now_ms(),
+    });
+    Some(session)
+}
+
+/// Current session for `ws`, disarming it first if it expired or belongs to
+/// another workspace.
+pub(crate) fn active_session(ws: &str) -> Option<ArmingSession> {
+    let mut slot = ARMING.lock().unwrap_or_else(|e| e.into_inner());
+    let session = slot.session.as_ref()?;
+    if session.expires_at_ms <= crate::now_ms() {
+        take_session(&mut slot, DisarmReason::Expired);
+        return None;
+    }
+    if session.ws != ws {
+        take_session(&mut slot, DisarmReason::WorkspaceSwitch);
+        return None;
+    }
+    slot.session.clone()
+}
+
+pub(crate) fn state() -> ArmingState {
+    let mut slot = ARMING.lock().unwrap_or_else(|e| e.into_inner());
+    let now = crate::now_ms();
+    if slot
+        .session
+        .as_ref()
+        .is_some_and(|s| s.expires_at_ms <= now)
+    {
+        take_session(&mut slot, DisarmReason::Expired);
+    }
+    ArmingState {
+        armed: slot.session.is_some(),
+        remaining_ms: slot
+            .session
+            .as_ref()
+            .map(|s| s.expires_at_ms.saturating_sub(now)),
+        session: slot.session.clone(),
+        last_disarm: slot.last_disarm.clone(),
+    }
+}
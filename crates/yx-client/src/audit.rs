@@ -0,0 +1,238 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use yx_protocol::CommandResult;
+
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub ts_ms: u64,
+    pub trace_id: String,
+    pub workspace: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    pub command: String,
+    pub arming: bool,
+    /// The caller received the unredacted result.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unredacted: bool,
+    /// Redacted before they reach the journal.
+    pub args: Value,
+    pub result_code: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    pub workspace: Option<String>,
+    pub command: Option<String>,
+    pub trace_id: Option<String>,
+    pub since_ts_ms: Option<u64>,
+    pub until_ts_ms: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditVerification {
+    pub ok: bool,
+    pub entries: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_bad_seq: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+pub fn journal_path() -> PathBuf {
+    PathBuf::from(crate::yx_data_dir()).join("audit.jsonl")
+}
+
+/// Privileged commands land in the journal: anything armed, `shell.exec` and `down`.
+pub fn requires_audit(name: &str, arming: bool) -> bool {
+    arming || name == "shell.exec" || name == "down"
+}
+
+pub(crate) fn record(
+    workspace: &str,
+    role: Option<&str>,
+    name: &str,
+    args: &Value,
+    arming: bool,
+    unredacted: bool,
+    result: &CommandResult,
+) -> Result<AuditEntry> {
+    let result_code = match &result.error {
+        Some(err) => err.code.clone(),
+        None if result.ok => "ok".to_string(),
+        None => "error".to_string(),
+    };
+    append(AuditEntry {
+        seq: 0,
+        ts_ms: crate::now_ms(),
+        trace_id: result.id.clone(),
+        workspace: workspace.to_string(),
+        role: role.map(ToString::to_string),
+        command: name.to_string(),
+        arming,
+        unredacted,
+        args: args.clone(),
+        result_code,
+        prev_hash: String::new(),
+        hash: String::new(),
+    })
+}
+
+/// Chains `entry` onto the last one in the file. The desktop app and the CLI
+/// share the journal, so the head is read under an exclusive lock each time.
+fn append(mut entry: AuditEntry) -> Result<AuditEntry> {
+    let path = journal_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("open audit journal: {}", path.display()))?;
+    file.lock()
+        .with_context(|| format!("lock audit journal: {}", path.display()))?;
+    let (last_seq, last_hash) = chain_head(&file)?;
+    entry.seq = last_seq + 1;
+    entry.prev_hash = last_hash;
+    entry.hash = entry_hash(&entry)?;
+
+    let line = serde_json::to_string(&entry)?;
+    file.write_all(line.as_bytes())?;
+    file.write_all(b"\n")?;
+    file.flush()?;
+    Ok(entry)
+}
+
+/// (seq, hash) of the last readable entry; unreadable lines are left for
+/// `verify` to report.
+fn chain_head(file: &fs::File) -> Result<(u64, String)> {
+    let mut head = (0, GENESIS_HASH.to_string());
+    for line in BufReader::new(file).lines() {
+        if let Ok(entry) = serde_json::from_str::<AuditEntry>(&line?) {
+            head = (entry.seq, entry.hash);
+        }
+    }
+    Ok(head)
+}
+
+fn read_entries() -> Result<Vec<AuditEntry>> {
+    read_lines()?
+        .into_iter()
+        .map(|(line, parsed)| parsed.with_context(|| format!("invalid audit entry at line {line}")))
+        .collect()
+}
+
+fn entry_hash(entry: &AuditEntry) -> Result<String> {
+    let mut value = serde_json::to_value(entry)?;
+    if let Some(obj) = value.as_object_mut() {
+        obj.remove("hash");
+    }
+    // serde_json maps are sorted, so this serialization is canonical
+    let bytes = serde_json::to_vec(&value)?;
+    Ok(hex(&Sha256::digest(bytes)))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub fn query(filter: &AuditQuery) -> Result<Vec<AuditEntry>> {
+    let mut items: Vec<AuditEntry> = read_entries()?
+        .into_iter()
+        .filter(|e| {
+            filter
+                .workspace
+                .as_ref()
+                .is_none_or(|ws| &e.workspace == ws)
+        })
+        .filter(|e| filter.command.as_ref().is_none_or(|c| &e.command == c))
+        .filter(|e| filter.trace_id.as_ref().is_none_or(|t| &e.trace_id == t))
+        .filter(|e| filter.since_ts_ms.is_none_or(|ts| e.ts_ms >= ts))
+        .filter(|e| filter.until_ts_ms.is_none_or(|ts| e.ts_ms <= ts))
+        .collect();
+    if let Some(limit) = filter.limit {
+        let skip = items.len().saturating_sub(limit);
+        items.drain(..skip);
+    }
+    Ok(items)
+}
+
+/// Non-empty lines of the journal with their line number, parsed where they
+/// can be.
+fn read_lines() -> Result<Vec<(usize, Result<AuditEntry, serde_json::Error>)>> {
+    let path = journal_path();
+    let file = match fs::File::open(&path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("open {}", path.display())),
+    };
+    let mut out = Vec::new();
+    for (idx, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            out.push((idx + 1, serde_json::from_str(&line)));
+        }
+    }
+    Ok(out)
+}
+
+pub fn verify() -> Result<AuditVerification> {
+    let lines = read_lines()?;
+    let entries = lines.len() as u64;
+    let mut prev_hash = GENESIS_HASH.to_string();
+    let mut prev_seq = 0u64;
+    for (line, parsed) in &lines {
+        let entry = match parsed {
+            Ok(entry) => entry,
+            Err(e) => {
+                return Ok(AuditVerification {
+                    ok: false,
+                    entries,
+                    first_bad_seq: Some(prev_seq + 1),
+                    reason: Some(format!("unparsable entry at line {line}: {e}")),
+                });
+            }
+        };
+        let reason = if entry.seq != prev_seq + 1 {
+            Some(format!(
+                "expected seq {}, found {}",
+                prev_seq + 1,
+                entry.seq
+            ))
+        } else if entry.prev_hash != prev_hash {
+            Some("prev_hash does not match previous entry".to_string())
+        } else if entry_hash(entry)? != entry.hash {
+            Some("entry hash mismatch".to_string())
+        } else {
+            None
+        };
+        if reason.is_some() {
+            return Ok(AuditVerification {
+                ok: false,
+                entries,
+                first_bad_seq: Some(entry.seq),
+                reason,
+            });
+        }
+        prev_seq = entry.seq;
+        prev_hash = entry.hash.clone();
+    }
+    Ok(AuditVerification {
+        ok: true,
+        entries,
+        first_bad_seq: None,
+        reason: None,
+    })
+}
@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+use yx_protocol::{Event as ProtocolEvent, Severity};
+
+use crate::config::{OverflowPolicy, StreamConfig};
+
+pub const OVERFLOW_TOPIC: &str = "stream.overflow";
+
+/// Drop counters reported in the `data` of `stream.overflow` events.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OverflowStats {
+    pub policy: OverflowPolicy,
+    pub capacity: usize,
+    /// Events dropped since the previous report.
+    pub dropped: u64,
+    pub dropped_total: u64,
+    /// `dropped_total` split by severity.
+    pub by_severity: BTreeMap<String, u64>,
+}
+
+/// Bounded queue between the stream reader and the batch consumer.
+pub(crate) struct BatchQueue {
+    config: StreamConfig,
+    state: Mutex<QueueState>,
+}
+
+/// Queued events split by severity rank, each in arrival order, so the
+/// least severe are found without scanning the whole queue.
+struct QueueState {
+    queues: BTreeMap<u8, VecDeque<(u64, ProtocolEvent)>>,
+    len: usize,
+    /// Arrival number of the next event, to hand batches out in order.
+    next_seq: u64,
+    stats: OverflowStats,
+}
+
+impl QueueState {
+    fn count_drop(&mut self, severity: &Severity) {
+        self.stats.dropped += 1;
+        self.stats.dropped_total += 1;
+        *self
+            .stats
+            .by_severity
+            .entry(severity.as_str().to_string())
+            .or_default() += 1;
+    }
+
+    /// Rank of the queue holding the oldest event.
+    fn oldest(&self) -> Option<u8> {
+        self.queues
+            .iter()
+            .filter_map(|(rank, q)| q.front().map(|(seq, _)| (*seq, *rank)))
+            .min()
+            .map(|(_, rank)| rank)
+    }
+
+    /// Rank of the least severe queued events.
+    fn lowest(&self) -> Option<u8> {
+        self.queues
+            .iter()
+            .find(|(_, q)| !q.is_empty())
+            .map(|(rank, _)| *rank)
+    }
+
+    fn pop(&mut self, rank: u8) -> Option<ProtocolEvent> {
+        let (_, event) = self.queues.get_mut(&rank)?.pop_front()?;
+        self.len -= 1;
+        Some(event)
+    }
+}
+
+impl BatchQueue {
+    pub(crate) fn new(config: StreamConfig) -> BatchQueue {
+        let stats = OverflowStats {
+            policy: config.overflow,
+            capacity: config.queue_capacity,
+            ..Default::default()
+        };
+        BatchQueue {
+            config,
+            state: Mutex::new(QueueState {
+                queues: BTreeMap::new(),
+                len: 0,
+                next_seq: 0,
+                stats,
+            }),
+        }
+    }
+
+    pub(crate) fn push(&self, event: ProtocolEvent) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let rank = event.severity.rank();
+        if state.len >= self.config.queue_capacity.max(1) {
+            let shed = match self.config.overflow {
+                OverflowPolicy::DropOldest => state.oldest(),
+                OverflowPolicy::SampleBySeverity => {
+                    let lowest = state.lowest();
+                    // nothing queued is less severe than the newcomer
+                    if lowest.is_some_and(|lowest| rank < lowest) {
+                        state.count_drop(&event.severity);
+                        return;
+                    }
+                    lowest
+                }
+            };
+            if let Some(dropped) = shed.and_then(|rank| state.pop(rank)) {
+                state.count_drop(&dropped.severity);
+            }
+        }
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.len += 1;
+        state
+            .queues
+            .entry(rank)
+            .or_default()
+            .push_back((seq, event));
+    }
+
+    /// Up to `max_batch` queued events, oldest first, followed by a
+    /// `stream.overflow` event when anything was dropped since the last take.
+    pub(crate) fn take(&self) -> Vec<ProtocolEvent> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let count = state.len.min(self.config.max_batch.max(1));
+        let mut batch = Vec::with_capacity(count + 1);
+        while batch.len() < count {
+            let Some(event) = state.oldest().and_then(|rank| state.pop(rank)) else {
+                break;
+            };
+            batch.push(event);
+        }
+        if state.stats.dropped > 0 {
+            batch.push(overflow_event(&state.stats));
+            state.stats.dropped = 0;
+        }
+        batch
+    }
+
+    /// Delivers a batch every `window_ms` while there is anything to deliver.
+    pub(crate) fn run<F>(&self, on_batch: F)
+    where
+        F: Fn(Vec<ProtocolEvent>),
+    {
+        let window = Duration::from_millis(self.config.window_ms.max(1));
+        loop {
+            std::thread::sleep(window);
+            let batch = self.take();
+            if !batch.is_empty() {
+                on_batch(batch);
+            }
+        }
+    }
+}
+
+fn overflow_event(stats: &OverflowStats) -> ProtocolEvent {
+    ProtocolEvent {
+        topic: OVERFLOW_TOPIC.to_string(),
+        severity: Severity::Warn,
+        ts_ms: crate::now_ms(),
+        event_id: None,
+        ws: None,
+        seq: None,
+        msg: Some(format!(
+            "dropped {} events ({} total)",
+            stats.dropped, stats.dropped_total
+        )),
+        data: serde_json::to_value(stats).unwrap_or_default(),
+        compliance: None,
+        trace_id: None,
+    }
+}
@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use yx_protocol::{CapabilityCatalog, CommandSpec, LawState};
+
+use crate::arming;
+
+static CATALOGS: Mutex<Option<HashMap<String, CatalogSnapshot>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CatalogSource {
+    Daemon,
+    Builtin,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogSnapshot {
+    pub ws: String,
+    pub source: CatalogSource,
+    pub fetched_at_ms: u64,
+    /// Set when the law state changed since the catalog was fetched.
+    pub stale: bool,
+    pub catalog: CapabilityCatalog,
+}
+
+pub(crate) fn cached(ws: &str) -> Option<CatalogSnapshot> {
+    let slot = CATALOGS.lock().unwrap_or_else(|e| e.into_inner());
+    slot.as_ref()?.get(ws).cloned()
+}
+
+pub(crate) fn store(snapshot: CatalogSnapshot) -> CatalogSnapshot {
+    let mut slot = CATALOGS.lock().unwrap_or_else(|e| e.into_inner());
+    slot.get_or_insert_with(HashMap::new)
+        .insert(snapshot.ws.clone(), snapshot.clone());
+    snapshot
+}
+
+pub(crate) fn invalidate(ws: &str) {
+    let mut slot = CATALOGS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(snapshot) = slot.as_mut().and_then(|m| m.get_mut(ws)) {
+        snapshot.stale = true;
+    }
+}
+
+/// Builds a snapshot from a `capabilities` result, falling back to the builtin
+/// catalog when the daemon doesn't answer with one.
+pub(crate) fn snapshot_from(ws: &str, result: Option<&Value>) -> CatalogSnapshot {
+    let parsed = result
+        .map(|v| v.get("catalog").unwrap_or(v))
+        .and_then(|v| serde_json::from_value::<CapabilityCatalog>(v.clone()).ok())
+        .filter(|c| !c.commands.is_empty());
+    let (source, catalog) = match parsed {
+        Some(catalog) => (CatalogSource::Daemon, catalog),
+        None => (CatalogSource::Builtin, builtin_catalog()),
+    };
+    CatalogSnapshot {
+        ws: ws.to_string(),
+        source,
+        fetched_at_ms: crate::now_ms(),
+        stale: false,
+        catalog,
+    }
+}
+
+fn string_props(required: &[&str], optional: &[&str]) -> Value {
+    let mut props = serde_json::Map::new();
+    for name in required.iter().chain(optional) {
+        props.insert(name.to_string(), json!({ "type": "string" }));
+    }
+    json!({ "type": "object", "properties": props, "required": required })
+}
+
+fn spec(name: &str, capability: &str, args_schema: Value, states: &[LawState]) -> CommandSpec {
+    CommandSpec {
+        name: name.to_string(),
+        capability: Some(capability.to_string()),
+        args_schema,
+        arming_required: arming::requires_arming(name),
+        allowed_states: states.to_vec(),
+    }
+}
+
+/// What YX assumes when the daemon has no catalog: diagnostics everywhere,
+/// mutations only while running or degraded.
+pub fn builtin_catalog() -> CapabilityCatalog {
+    use LawState::*;
+    let all = &LawState::ALL[..];
+    let active = &[Running, Degraded][..];
+    let empty = || string_props(&[], &[]);
+    let commands = vec![
+        spec("status", "runtime.read", empty(), all),
+        spec("ping", "runtime.read", empty(), all),
+        spec(
+            "protocol.handshake",
+            "runtime.read",
+            string_props(&[], &["client"]),
+            all,
+        ),
+        spec("capabilities", "runtime.read", empty(), all),
+        spec("law.snapshot", "law.read", empty(), all),
+        spec("logs.tail", "logs.read", empty(), all),
+        spec("events.subscribe", "events.read", empty(), all),
+        spec("mind.graph.activation.tail", "mind.read", empty(), all),
+        spec("providers.list", "providers.read", empty(), all),
+        spec("providers.status", "providers.read", empty(), all),
+        spec("chat.sessions.list", "chat.read", empty(), all),
+        spec(
+            "chat.history",
+            "chat.read",
+            string_props(&[], &["session_id"]),
+            all,
+        ),
+        spec(
+            "chat.session.new",
+            "chat.write",
+            string_props(&[], &["title"]),
+            active,
+        ),
+        spec(
+            "chat.send",
+            "chat.write",
+            json!({
+                "type": "object",
+                "properties": {
+                    "session_id": { "type": "string" },
+                    "text": { "type": "string" },
+                    "stream": { "type": "boolean" }
+                },
+                "required": ["text"]
+            }),
+            active,
+        ),
+        spec(
+            "providers.discover",
+            "providers.manage",
+            string_props(&[], &["endpoint", "model"]),
+            active,
+        ),
+        spec(
+            "providers.pair",
+            "providers.manage",
+            string_props(&["id", "endpoint", "model"], &[]),
+            active,
+        ),
+        spec(
+            "providers.attach",
+            "providers.manage",
+            string_props(&["id"], &["model"]),
+            active,
+        ),
+        spec("providers.detach", "providers.manage", empty(), active),
+        spec(
+            "providers.revoke",
+            "providers.manage",
+            string_props(&["id"], &[]),
+            &[Running, Degraded, Lockdown],
+        ),
+        spec(
+            "shell.exec",
+            "shell.exec",
+            json!({
+                "type": "object",
+                "properties": {
+                    "cmd": { "type": "string" },
+                    "args": { "type": "array", "items": { "type": "string" } },
+                    "cwd": { "type": "string" },
+                    "env": { "type": "object", "additionalProperties": { "type": "string" } },
+                    "stdin": { "type": "string" },
+                    "timeout_ms": { "type": "integer", "minimum": 1 },
+                    "max_output_bytes": { "type": "integer", "minimum": 0 },
+                    "stream": { "type": "boolean" }
+                },
+                "required": ["cmd"]
+            }),
+            active,
+        ),
+        spec(
+            "shell.pty.open",
+            "shell.pty",
+            json!({
+                "type": "object",
+                "properties": {
+                    "cmd": { "type": "string" },
+                    "args": { "type": "array", "items": { "type": "string" } },
+                    "cwd": { "type": "string" },
+                    "env": { "type": "object", "additionalProperties": { "type": "string" } },
+                    "cols": { "type": "integer", "minimum": 1 },
+                    "rows": { "type": "integer", "minimum": 1 }
+                }
+            }),
+            active,
+        ),
+        spec(
+            "shell.pty.write",
+            "shell.pty",
+            string_props(&["pty_id", "data"], &[]),
+            active,
+        ),
+        // closing stays possible whatever the law says
+        spec(
+            "shell.pty.close",
+            "shell.pty",
+            string_props(&["pty_id"], &[]),
+            all,
+        ),
+        spec(
+            "down",
+            "runtime.control",
+            json!({
+                "type": "object",
+                "properties": {
+                    "force": { "type": "boolean" },
+                    "shutdown": { "type": "boolean" }
+                }
+            }),
+            &[Running, Degraded, Lockdown, Halted],
+        ),
+    ];
+    let mut capabilities: Vec<String> = commands
+        .iter()
+        .filter_map(|c| c.capability.clone())
+        .collect();
+    capabilities.sort();
+    capabilities.dedup();
+    CapabilityCatalog {
+        capabilities,
+        commands,
+    }
+}
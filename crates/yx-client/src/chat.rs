@@ -0,0 +1,550 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use yx_protocol::{ChatHistory, ChatMessage, ChatRole, ChatSession, ChatSessionList};
+
+use crate::redact;
+use crate::search::{self, Highlight};
+
+pub const DEFAULT_CHAT_SEARCH_LIMIT: usize = 50;
+
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatExportFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+impl std::str::FromStr for ChatExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Ok(ChatExportFormat::Markdown),
+            "json" => Ok(ChatExportFormat::Json),
+            other => Err(format!("unknown chat export format: {other}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatSearchRequest {
+    /// Terms and `"quoted phrases"`, all of which a message must contain.
+    pub q: String,
+    pub session_id: Option<String>,
+    pub role: Option<ChatRole>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatSearchHit {
+    pub session_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_title: Option<String>,
+    /// Position of the message in the session's history.
+    pub index: usize,
+    pub role: ChatRole,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ts_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    pub score: usize,
+    pub highlight: Highlight,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatSearchResults {
+    pub ws: String,
+    pub total: usize,
+    /// Newest first.
+    pub hits: Vec<ChatSearchHit>,
+}
+
+/// A message sent with `chat_send` and the reply, as recorded in the cache.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatExchange {
+    pub session_id: String,
+    pub trace_id: String,
+    pub sent: ChatMessage,
+    /// None when the daemon streams the reply as events instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply: Option<ChatMessage>,
+}
+
+/// Cached session listing, `chat/sessions.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SessionIndex {
+    fetched_ms: u64,
+    #[serde(default)]
+    selected: Option<String>,
+    #[serde(default)]
+    items: Vec<ChatSession>,
+}
+
+/// Cached history of one session, `chat/session-<id>.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedHistory {
+    fetched_ms: u64,
+    session: ChatSession,
+    #[serde(default)]
+    messages: Vec<ChatMessage>,
+}
+
+fn chat_dir(ws: &str) -> PathBuf {
+    PathBuf::from(crate::ws_data_dir(ws)).join("chat")
+}
+
+fn history_path(ws: &str, session_id: &str) -> PathBuf {
+    let name: String = session_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    chat_dir(ws).join(format!("session-{name}.json"))
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &PathBuf) -> Option<T> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+}
+
+fn write_json<T: Serialize>(path: &PathBuf, value: &T) -> Result<()> {
+    fs::create_dir_all(path.parent().context("chat cache path has no parent")?)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let value = redact::current().redacted(&serde_json::to_value(value)?);
+    let content = serde_json::to_string_pretty(&value)?;
+    fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn read_index(ws: &str) -> Option<SessionIndex> {
+    read_json(&chat_dir(ws).join("sessions.json"))
+}
+
+fn write_index(ws: &str, index: &SessionIndex) -> Result<()> {
+    write_json(&chat_dir(ws).join("sessions.json"), index)
+}
+
+fn read_history(ws: &str, session_id: &str) -> Option<CachedHistory> {
+    read_json(&history_path(ws, session_id))
+}
+
+/// Whether a daemon result came from mock mode, which keeps no chat state:
+/// listings and histories are then served from the cache.
+pub(crate) fn is_mock(value: &Value) -> bool {
+    value.get("mode").and_then(Value::as_str) == Some("mock")
+}
+
+/// Sessions in a `chat.sessions.list` result, and the selected one.
+pub(crate) fn sessions_from(value: &Value) -> ChatSessionList {
+    let items = value
+        .get("items")
+        .or_else(|| value.get("sessions"))
+        .unwrap_or(value);
+    let items: Vec<ChatSession> = items
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|v| match v {
+                    Value::String(id) => Some(ChatSession::new(id)),
+                    other => serde_json::from_value::<ChatSession>(other.clone()).ok(),
+                })
+                .filter(|s| !s.id.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let selected = value
+        .get("selected")
+        .or_else(|| value.get("active"))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    ChatSessionList {
+        items,
+        selected,
+        cached_ms: None,
+    }
+}
+
+/// Messages in a `chat.history` result.
+pub(crate) fn messages_from(value: &Value) -> Vec<ChatMessage> {
+    ["items", "messages", "history"]
+        .iter()
+        .find_map(|k| value.get(k))
+        .unwrap_or(value)
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|v| serde_json::from_value(v.clone()).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Session in a `chat.session.new` result.
+pub(crate) fn created_from(value: &Value) -> Option<ChatSession> {
+    let session = value
+        .get("session")
+        .or_else(|| value.get("item"))
+        .unwrap_or(value);
+    match session {
+        Value::String(id) => Some(ChatSession::new(id)),
+        other => serde_json::from_value::<ChatSession>(other.clone()).ok(),
+    }
+    .filter(|s| !s.id.is_empty())
+}
+
+/// Assistant reply in a `chat.send` result.
+pub(crate) fn reply_from(value: &Value) -> Option<ChatMessage> {
+    let message = value.get("message").or_else(|| value.get("item"))?;
+    serde_json::from_value(message.clone()).ok()
+}
+
+/// Session metadata a `chat.history` result may carry next to the messages.
+pub(crate) fn session_from(value: &Value, session_id: &str) -> ChatSession {
+    let mut session = value
+        .get("session")
+        .and_then(|s| serde_json::from_value::<ChatSession>(s.clone()).ok())
+        .filter(|s| s.id == session_id)
+        .unwrap_or_else(|| ChatSession::new(session_id));
+    if session.title.is_none() {
+        session.title = value
+            .get("title")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+    }
+    session
+}
+
+/// Fills in what the daemon left out of `session` from `known`.
+fn fill_session(session: &mut ChatSession, known: &ChatSession) {
+    session.title = session.title.take().or_else(|| known.title.clone());
+    session.created_ms = session.created_ms.or(known.created_ms);
+    session.updated_ms = session.updated_ms.or(known.updated_ms);
+    session.provider = session.provider.take().or_else(|| known.provider.clone());
+    session.model = session.model.take().or_else(|| known.model.clone());
+    session.message_count = session.message_count.or(known.message_count);
+}
+
+/// Derives timestamps, provider, model and count from the messages.
+fn summarize(session: &mut ChatSession, messages: &[ChatMessage]) {
+    let stamps = messages.iter().filter_map(|m| m.ts_ms);
+    session.created_ms = session.created_ms.or_else(|| stamps.clone().min());
+    session.updated_ms = stamps.max().max(session.updated_ms);
+    if let Some(last) = messages.iter().rev().find(|m| m.provider.is_some()) {
+        session.provider = last.provider.clone();
+    }
+    if let Some(last) = messages.iter().rev().find(|m| m.model.is_some()) {
+        session.model = last.model.clone();
+    }
+    session.message_count = Some(messages.len());
+}
+
+fn upsert_index(ws: &str, session: &ChatSession, now_ms: u64) -> Result<()> {
+    let mut index = read_index(ws).unwrap_or_default();
+    match index.items.iter_mut().find(|s| s.id == session.id) {
+        Some(known) => *known = session.clone(),
+        None => index.items.push(session.clone()),
+    }
+    if index.fetched_ms == 0 {
+        index.fetched_ms = now_ms;
+    }
+    write_index(ws, &index)
+}
+
+/// Caches a listing from the daemon, keeping metadata learned from histories.
+pub(crate) fn cache_sessions(ws: &str, list: &mut ChatSessionList, now_ms: u64) -> Result<()> {
+    let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let known = read_index(ws).unwrap_or_default();
+    for session in list.items.iter_mut() {
+        if let Some(k) = known.items.iter().find(|k| k.id == session.id) {
+            fill_session(session, k);
+        }
+    }
+    write_index(
+        ws,
+        &SessionIndex {
+            fetched_ms: now_ms,
+            selected: list.selected.clone(),
+            items: list.items.clone(),
+        },
+    )
+}
+
+/// Adds a session created through YX to the cached listing.
+pub(crate) fn cache_created(ws: &str, session: &ChatSession, now_ms: u64) -> Result<()> {
+    let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    upsert_index(ws, session, now_ms)
+}
+
+pub(crate) fn cached_sessions(ws: &str) -> Option<ChatSessionList> {
+    let mut index = read_index(ws)?;
+    index
+        .items
+        .sort_by_key(|s| std::cmp::Reverse(s.updated_ms.or(s.created_ms)));
+    Some(ChatSessionList {
+        items: index.items,
+        selected: index.selected,
+        cached_ms: Some(index.fetched_ms),
+    })
+}
+
+/// Caches a history from the daemon. The daemon's copy wins, but messages it
+/// returns without YX's metadata (trace ids, provider, model) get it back from
+/// the matching cached message.
+pub(crate) fn cache_history(
+    ws: &str,
+    mut session: ChatSession,
+    mut messages: Vec<ChatMessage>,
+    now_ms: u64,
+) -> Result<ChatHistory> {
+    let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let cached = read_history(ws, &session.id);
+    if let Some(cached) = &cached {
+        let mut local: Vec<Option<&ChatMessage>> = cached.messages.iter().map(Some).collect();
+        // newest first, so repeated messages pair up with their latest copy
+        for message in messages.iter_mut().rev() {
+            let found = local.iter_mut().rev().find(|slot| {
+                slot.is_some_and(|m| match (&m.id, &message.id) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => m.role == message.role && m.content == message.content,
+                })
+            });
+            let Some(known) = found.and_then(Option::take) else {
+                continue;
+            };
+            message.id = message.id.take().or_else(|| known.id.clone());
+            message.ts_ms = message.ts_ms.or(known.ts_ms);
+            message.provider = message.provider.take().or_else(|| known.provider.clone());
+            message.model = message.model.take().or_else(|| known.model.clone());
+            message.trace_id = message.trace_id.take().or_else(|| known.trace_id.clone());
+            if message.context.is_empty() {
+                message.context = known.context.clone();
+            }
+            message.usage = message.usage.take().or_else(|| known.usage.clone());
+        }
+    }
+    // the listing is the daemon's word, the cached history only what YX derived
+    if let Some(listed) =
+        read_index(ws).and_then(|i| i.items.into_iter().find(|s| s.id == session.id))
+    {
+        fill_session(&mut session, &listed);
+    }
+    if let Some(cached) = &cached {
+        fill_session(&mut session, &cached.session);
+    }
+    summarize(&mut session, &messages);
+    let entry = CachedHistory {
+        fetched_ms: now_ms,
+        session,
+        messages,
+    };
+    write_json(&history_path(ws, &entry.session.id), &entry)?;
+    upsert_index(ws, &entry.session, now_ms)?;
+    Ok(ChatHistory {
+        session: entry.session,
+        items: entry.messages,
+        cached_ms: None,
+    })
+}
+
+pub(crate) fn cached_history(ws: &str, session_id: &str) -> Option<ChatHistory> {
+    let cached = read_history(ws, session_id)?;
+    Some(ChatHistory {
+        session: cached.session,
+        items: cached.messages,
+        cached_ms: Some(cached.fetched_ms),
+    })
+}
+
+/// Appends a sent message and its reply to the cached history.
+pub(crate) fn record_exchange(
+    ws: &str,
+    session_id: &str,
+    sent: ChatMessage,
+    reply: Option<ChatMessage>,
+    now_ms: u64,
+) -> Result<()> {
+    let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entry = read_history(ws, session_id).unwrap_or_else(|| CachedHistory {
+        fetched_ms: now_ms,
+        session: read_index(ws)
+            .and_then(|index| index.items.into_iter().find(|s| s.id == session_id))
+            .unwrap_or_else(|| ChatSession::new(session_id)),
+        messages: Vec::new(),
+    });
+    entry.messages.push(sent);
+    entry.messages.extend(reply);
+    let mut session = entry.session.clone();
+    summarize(&mut session, &entry.messages);
+    entry.session = session;
+    write_json(&history_path(ws, session_id), &entry)?;
+    upsert_index(ws, &entry.session, now_ms)
+}
+
+/// Cached histories of `ws`.
+fn histories(ws: &str) -> Vec<CachedHistory> {
+    let Ok(entries) = fs::read_dir(chat_dir(ws)) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("session-"))
+        .filter_map(|e| read_json(&e.path()))
+        .collect()
+}
+
+/// Full-text search over every cached session of `ws`.
+pub(crate) fn search(ws: &str, request: &ChatSearchRequest) -> ChatSearchResults {
+    let query = search::parse_query(&request.q);
+    let mut hits = Vec::new();
+    for history in histories(ws) {
+        if request
+            .session_id
+            .as_deref()
+            .is_some_and(|id| id != history.session.id)
+        {
+            continue;
+        }
+        for (index, message) in history.messages.iter().enumerate() {
+            if request.role.as_ref().is_some_and(|r| *r != message.role)
+                || !search::text_matches(&message.content, &query)
+            {
+                continue;
+            }
+            let ranges = search::match_ranges(&message.content, &query);
+            hits.push(ChatSearchHit {
+                session_id: history.session.id.clone(),
+                session_title: history.session.title.clone(),
+                index,
+                role: message.role.clone(),
+                ts_ms: message.ts_ms,
+                trace_id: message.trace_id.clone(),
+                score: ranges.len(),
+                highlight: search::highlight("content", &message.content, &ranges),
+            });
+        }
+    }
+    hits.sort_by(|a, b| (b.ts_ms, &b.session_id, b.index).cmp(&(a.ts_ms, &a.session_id, a.index)));
+    let total = hits.len();
+    hits.truncate(request.limit.unwrap_or(DEFAULT_CHAT_SEARCH_LIMIT));
+    ChatSearchResults {
+        ws: ws.to_string(),
+        total,
+        hits,
+    }
+}
+
+/// `YYYY-MM-DD HH:MM:SS UTC` for epoch `ms`.
+fn utc(ms: u64) -> String {
+    let rem = ms / 1000 % 86_400;
+    format!(
+        "{} {:02}:{:02}:{:02} UTC",
+        crate::utc_date(ms),
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+fn markdown(ws: &str, history: &ChatHistory, exported_ms: u64) -> String {
+    let session = &history.session;
+    let mut out = format!("# {}\n\n", session.title.as_deref().unwrap_or(&session.id));
+    let mut meta = vec![
+        format!("- Session: `{}`", session.id),
+        format!("- Workspace: `{ws}`"),
+    ];
+    if let Some(provider) = &session.provider {
+        meta.push(format!("- Provider: `{provider}`"));
+    }
+    if let Some(model) = &session.model {
+        meta.push(format!("- Model: `{model}`"));
+    }
+    if let Some(ms) = session.created_ms {
+        meta.push(format!("- Created: {}", utc(ms)));
+    }
+    if let Some(ms) = session.updated_ms {
+        meta.push(format!("- Updated: {}", utc(ms)));
+    }
+    meta.push(format!("- Messages: {}", history.items.len()));
+    meta.push(format!("- Exported: {}", utc(exported_ms)));
+    out.push_str(&meta.join("\n"));
+    out.push('\n');
+    for message in &history.items {
+        let mut heading = vec![message.role.to_string()];
+        heading.extend(message.ts_ms.map(utc));
+        heading.extend(
+            [&message.provider, &message.model]
+                .into_iter()
+                .flatten()
+                .map(|s| format!("`{s}`")),
+        );
+        heading.extend(message.trace_id.as_ref().map(|t| format!("trace `{t}`")));
+        out.push_str(&format!(
+            "\n## {}\n\n{}\n",
+            heading.join(" · "),
+            message.content.trim_end()
+        ));
+        if !message.context.is_empty() {
+            out.push_str("\nContext:\n\n");
+            for attached in &message.context {
+                out.push_str(&format!(
+                    "- {}{} ({} bytes{})\n",
+                    attached.label,
+                    attached
+                        .id
+                        .as_ref()
+                        .map_or(String::new(), |id| format!(", `{id}`")),
+                    attached.bytes,
+                    if attached.truncated {
+                        ", truncated"
+                    } else {
+                        ""
+                    }
+                ));
+            }
+        }
+    }
+    out
+}
+
+/// `history` as a document in `format`.
+pub(crate) fn export(
+    ws: &str,
+    history: &ChatHistory,
+    format: ChatExportFormat,
+    exported_ms: u64,
+) -> Result<String> {
+    match format {
+        ChatExportFormat::Markdown => Ok(markdown(ws, history, exported_ms)),
+        ChatExportFormat::Json => {
+            let trace_ids: BTreeSet<&str> = history
+                .items
+                .iter()
+                .filter_map(|m| m.trace_id.as_deref())
+                .collect();
+            let doc = json!({
+                "ws": ws,
+                "exported_ms": exported_ms,
+                "session": history.session,
+                "trace_ids": trace_ids,
+                "messages": history.items,
+            });
+            Ok(serde_json::to_string_pretty(&doc)?)
+        }
+    }
+}
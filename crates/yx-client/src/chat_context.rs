@@ -0,0 +1,365 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use yx_protocol::{ChatContextRef, CommandError};
+
+use crate::event_store::{self, EventQuery, StoredEvent};
+use crate::{law, law_history, redact, trace};
+
+/// Bytes of JSON attached to one `chat.send` unless the request says otherwise.
+pub const DEFAULT_CONTEXT_BUDGET: usize = 16 * 1024;
+const DEFAULT_EVENT_WINDOW: usize = 50;
+const DEFAULT_LOG_LINES: usize = 100;
+/// Strings are clipped to this many chars before whole entries are dropped.
+const CLIP_CHARS: usize = 2_000;
+const TRUNCATED_MARK: &str = "…[truncated]";
+
+/// Something to attach to a chat message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ContextItem {
+    /// Current law state and the latest recorded snapshot.
+    LawSnapshot,
+    /// Stored events of the chat's workspace matching `query`, newest first;
+    /// `query.ws` is ignored.
+    Events {
+        #[serde(default)]
+        query: EventQuery,
+    },
+    /// The most recent lines from log topics, newest first.
+    Logs {
+        #[serde(default)]
+        lines: Option<usize>,
+    },
+    /// Request and result of a recorded command.
+    CommandResult { trace_id: String },
+    /// Request, response, events and log lines of a trace.
+    Trace { trace_id: String },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatContextRequest {
+    pub items: Vec<ContextItem>,
+    /// Total JSON bytes across all items; `DEFAULT_CONTEXT_BUDGET` if unset.
+    pub budget_bytes: Option<usize>,
+}
+
+/// One attached item: its reference, as kept in the history, and the data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextBlock {
+    #[serde(flatten)]
+    pub reference: ChatContextRef,
+    pub data: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatContext {
+    pub ws: String,
+    pub budget_bytes: usize,
+    pub used_bytes: usize,
+    pub blocks: Vec<ContextBlock>,
+}
+
+impl ChatContext {
+    pub fn references(&self) -> Vec<ChatContextRef> {
+        self.blocks.iter().map(|b| b.reference.clone()).collect()
+    }
+}
+
+fn invalid(message: String, details: Value) -> CommandError {
+    CommandError {
+        code: "invalid_context".to_string(),
+        message,
+        details: Some(details),
+        trace_id: Some(crate::next_trace_id()),
+    }
+}
+
+fn size(value: &Value) -> usize {
+    serde_json::to_string(value).map_or(0, |s| s.len())
+}
+
+fn clip_strings(value: &Value, max_chars: usize) -> Value {
+    match value {
+        Value::String(s) if s.chars().count() > max_chars => Value::String(format!(
+            "{}{TRUNCATED_MARK}",
+            s.chars().take(max_chars).collect::<String>()
+        )),
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|v| clip_strings(v, max_chars)).collect())
+        }
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), clip_strings(v, max_chars)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// `value` cut down to `limit` bytes of JSON. Lists keep their leading
+/// entries; anything else has its strings clipped, and as a last resort
+/// becomes a clipped JSON string. `None` when not even that fits.
+fn fit(value: Value, limit: usize) -> Option<(Value, bool)> {
+    if size(&value) <= limit {
+        return Some((value, false));
+    }
+    if let Value::Array(items) = value {
+        let mut kept = Vec::new();
+        let mut used = 2;
+        for item in items {
+            let item = clip_strings(&item, CLIP_CHARS);
+            let n = size(&item) + 1;
+            if used + n > limit {
+                // a clipped copy of the entry that no longer fits, if there is room
+                let room = limit.saturating_sub(used + 1);
+                if room >= 64 {
+                    kept.extend(fit(item, room).map(|(item, _)| item));
+                }
+                break;
+            }
+            used += n;
+            kept.push(item);
+        }
+        return Some((Value::Array(kept), true));
+    }
+    let mut max_chars = CLIP_CHARS;
+    while max_chars >= 64 {
+        let clipped = clip_strings(&value, max_chars);
+        if size(&clipped) <= limit {
+            return Some((clipped, true));
+        }
+        max_chars /= 2;
+    }
+    let text = value.to_string();
+    let mut end = limit
+        .saturating_sub(TRUNCATED_MARK.len() + 2)
+        .min(text.len());
+    loop {
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let clipped = Value::String(format!("{}{TRUNCATED_MARK}", &text[..end]));
+        // escaping makes the JSON longer than the text
+        let over = size(&clipped).saturating_sub(limit);
+        if over == 0 {
+            return Some((clipped, true));
+        }
+        if end == 0 {
+            return None;
+        }
+        end = end.saturating_sub(over);
+    }
+}
+
+fn event_entry(stored: &StoredEvent) -> Value {
+    let event = &stored.event;
+    json!({
+        "id": stored.id,
+        "ts_ms": event.ts_ms,
+        "topic": event.topic,
+        "severity": event.severity,
+        "msg": event.msg,
+        "data": event.data,
+        "trace_id": event.trace_id,
+    })
+}
+
+fn log_line(stored: &StoredEvent) -> String {
+    let event = &stored.event;
+    let text = event
+        .data
+        .get("line")
+        .and_then(Value::as_str)
+        .or(event.msg.as_deref())
+        .map_or_else(|| event.data.to_string(), str::to_string);
+    format!("{} {} {}: {text}", event.ts_ms, event.severity, event.topic)
+}
+
+fn describe_query(query: &EventQuery) -> String {
+    let mut filters = Vec::new();
+    if let Some(topic) = &query.topic {
+        filters.push(format!("topic={topic}"));
+    }
+    if let Some(severity) = &query.severity {
+        filters.push(format!("severity={severity}"));
+    }
+    if let Some(trace_id) = &query.trace_id {
+        filters.push(format!("trace={trace_id}"));
+    }
+    if let Some(since) = query.since_ms {
+        filters.push(format!("since={since}"));
+    }
+    if let Some(until) = query.until_ms {
+        filters.push(format!("until={until}"));
+    }
+    if filters.is_empty() {
+        "events".to_string()
+    } else {
+        format!("events {}", filters.join(" "))
+    }
+}
+
+/// The data for `item` before truncation: kind, label, source id, data.
+fn collect(
+    ws: &str,
+    item: &ContextItem,
+) -> Result<(&'static str, String, Option<String>, Value), CommandError> {
+    match item {
+        ContextItem::LawSnapshot => {
+            let snapshot = law_history::history(ws, 1)
+                .map_err(|e| invalid(format!("law history: {e:#}"), json!({ "workspace": ws })))?
+                .pop();
+            let status = law::status(ws);
+            if status.is_none() && snapshot.is_none() {
+                return Err(invalid(
+                    format!("no law snapshot recorded in workspace '{ws}'"),
+                    json!({ "workspace": ws, "kind": "law_snapshot" }),
+                ));
+            }
+            let state = status
+                .as_ref()
+                .map(|s| s.state)
+                .or(snapshot.as_ref().and_then(|s| s.state));
+            let id = snapshot.as_ref().map(|s| format!("seq {}", s.seq));
+            Ok((
+                "law_snapshot",
+                "law snapshot".to_string(),
+                id,
+                json!({
+                    "state": state,
+                    "since_ms": status.as_ref().map(|s| s.since_ms),
+                    "last_transition": status.and_then(|s| s.last_transition),
+                    "snapshot": snapshot,
+                }),
+            ))
+        }
+        ContextItem::Events { query } => {
+            let mut query = query.clone();
+            query.limit = Some(query.limit.unwrap_or(DEFAULT_EVENT_WINDOW));
+            // another workspace's events would bypass its role checks
+            query.ws = None;
+            let page = event_store::query(ws, &query);
+            let id = page.items.first().map(|e| format!("event {}", e.id));
+            let entries: Vec<Value> = page.items.iter().map(event_entry).collect();
+            Ok(("events", describe_query(&query), id, Value::Array(entries)))
+        }
+        ContextItem::Logs { lines } => {
+            let lines = lines.unwrap_or(DEFAULT_LOG_LINES);
+            let page = event_store::query(
+                ws,
+                &EventQuery {
+                    topic: Some("*log*".to_string()),
+                    limit: Some(lines),
+                    ..EventQuery::default()
+                },
+            );
+            let id = page.items.first().map(|e| format!("event {}", e.id));
+            let entries: Vec<Value> = page
+                .items
+                .iter()
+                .map(|e| Value::String(log_line(e)))
+                .collect();
+            Ok((
+                "logs",
+                format!("last {lines} log lines"),
+                id,
+                Value::Array(entries),
+            ))
+        }
+        ContextItem::CommandResult { trace_id } => {
+            let Some(record) = trace::command(ws, trace_id) else {
+                return Err(invalid(
+                    format!("no command recorded with trace '{trace_id}'"),
+                    json!({ "workspace": ws, "kind": "command_result", "trace_id": trace_id }),
+                ));
+            };
+            Ok((
+                "command_result",
+                format!("result of {}", record.name),
+                Some(trace_id.clone()),
+                json!({
+                    "name": record.name,
+                    "args": record.args,
+                    "requested_ms": record.requested_ms,
+                    "result": record.result,
+                }),
+            ))
+        }
+        ContextItem::Trace { trace_id } => {
+            let trace = trace::trace(ws, trace_id);
+            if trace.timeline.is_empty() {
+                return Err(invalid(
+                    format!("nothing recorded for trace '{trace_id}'"),
+                    json!({ "workspace": ws, "kind": "trace", "trace_id": trace_id }),
+                ));
+            }
+            let entries: Vec<Value> = trace
+                .timeline
+                .iter()
+                .map(|e| json!({ "ts_ms": e.ts_ms, "kind": e.kind, "summary": e.summary, "detail": e.detail }))
+                .collect();
+            Ok((
+                "trace",
+                format!("trace timeline ({} entries)", entries.len()),
+                Some(trace_id.clone()),
+                Value::Array(entries),
+            ))
+        }
+    }
+}
+
+/// Byte allowance per item: items smaller than an even share get their full
+/// size and leave the rest to the larger ones.
+fn allot(sizes: &[usize], budget: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| sizes[i]);
+    let mut out = vec![0; sizes.len()];
+    let mut left = budget;
+    for (n, &i) in order.iter().enumerate() {
+        let share = left / (sizes.len() - n);
+        out[i] = sizes[i].min(share);
+        left -= out[i];
+    }
+    out
+}
+
+/// Collects `request.items` in order, through the configured redaction, and
+/// fits them into the budget.
+pub(crate) fn build(ws: &str, request: &ChatContextRequest) -> Result<ChatContext, CommandError> {
+    let budget = request.budget_bytes.unwrap_or(DEFAULT_CONTEXT_BUDGET);
+    let redactor = redact::current();
+    let mut collected = Vec::new();
+    for item in &request.items {
+        let (kind, label, id, data) = collect(ws, item)?;
+        collected.push((kind, label, id, redactor.redacted(&data)));
+    }
+    let sizes: Vec<usize> = collected.iter().map(|(.., data)| size(data)).collect();
+    let mut blocks = Vec::new();
+    let mut used = 0;
+    for ((kind, label, id, data), limit) in collected.into_iter().zip(allot(&sizes, budget)) {
+        // a block too small for even the truncation mark is left out
+        let Some((data, truncated)) = fit(data, limit) else {
+            continue;
+        };
+        let bytes = size(&data);
+        used += bytes;
+        blocks.push(ContextBlock {
+            reference: ChatContextRef {
+                kind: kind.to_string(),
+                label,
+                id,
+                count: data.as_array().map_or(1, Vec::len),
+                bytes,
+                truncated,
+            },
+            data,
+        });
+    }
+    Ok(ChatContext {
+        ws: ws.to_string(),
+        budget_bytes: budget,
+        used_bytes: used,
+        blocks,
+    })
+}
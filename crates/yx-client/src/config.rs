@@ -0,0 +1,370 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use yx_protocol::{Role, Severity};
+
+/// YX-side settings, read from `yx.toml` next to the `yai.toml` candidates.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct YxConfig {
+    pub identity: IdentityConfig,
+    pub alerts: AlertsConfig,
+    pub events: EventStoreConfig,
+    pub stream: StreamConfig,
+    pub redaction: RedactionConfig,
+    pub provider_health: ProviderHealthConfig,
+    pub usage: UsageConfig,
+    pub shell_policy: ShellPolicyConfig,
+    pub pty: PtyConfig,
+    pub down: DownConfig,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdentityConfig {
+    /// Operator id used when `YX_OPERATOR` is not set.
+    pub active: Option<String>,
+    pub operators: Vec<OperatorConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperatorConfig {
+    pub id: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default = "default_role")]
+    pub role: Role,
+    /// Per-workspace overrides of `role`, e.g. `{ prod = "viewer" }`.
+    #[serde(default)]
+    pub workspace_roles: BTreeMap<String, Role>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertsConfig {
+    pub rules: Vec<AlertRule>,
+}
+
+/// Raises an alert when more than `threshold` matching events arrive within
+/// `window_secs`. Every condition that is set must hold for an event to match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub id: String,
+    /// Glob over the topic, as in event queries.
+    #[serde(default)]
+    pub topic: Option<String>,
+    #[serde(default)]
+    pub min_severity: Option<Severity>,
+    /// JSONPath into the event, e.g. `$.data.rule`; must resolve to a
+    /// non-null value, equal to `equals` when that is set.
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub equals: Option<Value>,
+    #[serde(default)]
+    pub threshold: u32,
+    #[serde(default = "default_alert_window_secs")]
+    pub window_secs: u64,
+    /// Minimum time between two alerts for the same rule and group; an open
+    /// alert that keeps matching is announced again after it.
+    #[serde(default)]
+    pub cooldown_secs: u64,
+    /// JSONPath whose value splits the rule into independent alerts.
+    #[serde(default)]
+    pub group_by: Option<String>,
+    /// Defaults to the severity of the triggering event.
+    #[serde(default)]
+    pub severity: Option<Severity>,
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Also raise a desktop notification.
+    #[serde(default)]
+    pub notify: bool,
+}
+
+/// Secret scrubbing applied to results, events and journals. Setting `keys` or
+/// `patterns` replaces the defaults rather than extending them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    pub enabled: bool,
+    /// Case-insensitive words of object keys whose values are hidden;
+    /// numbers and booleans are left as they are.
+    pub keys: Vec<String>,
+    /// Regexes replaced inside string values; with a capture group only the
+    /// first group is replaced.
+    pub patterns: Vec<String>,
+    /// Hide long random-looking tokens in string values.
+    pub entropy: bool,
+    pub entropy_min_len: usize,
+    /// Shannon entropy in bits per char; hex digests top out at 4.0.
+    pub entropy_min_bits: f64,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        RedactionConfig {
+            enabled: true,
+            keys: [
+                "token",
+                "secret",
+                "password",
+                "passwd",
+                "api_key",
+                "apikey",
+                "auth",
+                "authorization",
+                "credential",
+                "private_key",
+            ]
+            .map(String::from)
+            .to_vec(),
+            patterns: [
+                r"(?i)bearer\s+([A-Za-z0-9._~+/=-]+)",
+                r"://([^/\s:@]+:[^/\s@]+)@",
+                r"(?i)[?&](?:[a-z_]*token|key|api_?key|secret|password|sig|signature)=([^&#\s]+)",
+                r"\bsk-[A-Za-z0-9_-]{16,}",
+                r"\bAKIA[0-9A-Z]{16}\b",
+                r"\bgh[pousr]_[A-Za-z0-9]{36}\b",
+            ]
+            .map(String::from)
+            .to_vec(),
+            entropy: true,
+            entropy_min_len: 24,
+            entropy_min_bits: 4.2,
+        }
+    }
+}
+
+/// On-disk event store, one directory of rotating segments per workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventStoreConfig {
+    pub enabled: bool,
+    pub segment_bytes: u64,
+    /// Oldest segments are removed once a workspace store grows past this.
+    pub max_bytes: u64,
+}
+
+impl Default for EventStoreConfig {
+    fn default() -> Self {
+        EventStoreConfig {
+            enabled: true,
+            segment_bytes: 4 * 1024 * 1024,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// What the stream queue sheds once it holds `queue_capacity` events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    #[default]
+    DropOldest,
+    /// Sheds the oldest event of the lowest severity queued, so warnings
+    /// and errors outlive debug noise during a burst.
+    SampleBySeverity,
+}
+
+/// Batching between the daemon stream and the UI. Persistence and indexing
+/// happen before the queue, so shed events stay queryable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamConfig {
+    /// Events are coalesced and delivered at most once per window.
+    pub window_ms: u64,
+    pub max_batch: usize,
+    pub queue_capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            window_ms: 100,
+            max_batch: 500,
+            queue_capacity: 5_000,
+            overflow: OverflowPolicy::DropOldest,
+        }
+    }
+}
+
+/// Background checks of attached and paired providers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderHealthConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    pub timeout_ms: u64,
+    /// Reachable providers slower than this are degraded.
+    pub slow_ms: u64,
+}
+
+impl Default for ProviderHealthConfig {
+    fn default() -> Self {
+        ProviderHealthConfig {
+            enabled: true,
+            interval_secs: 30,
+            timeout_ms: 2_000,
+            slow_ms: 1_000,
+        }
+    }
+}
+
+/// Price tables for chat usage. Costs are worked out when reporting, so a
+/// price change applies to past usage too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageConfig {
+    pub currency: String,
+    /// First match wins; usage no rule matches is reported unpriced.
+    pub prices: Vec<PriceRule>,
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        UsageConfig {
+            currency: "USD".to_string(),
+            prices: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceRule {
+    /// Glob over the model name, `*` and `?` wildcards.
+    pub model: String,
+    /// Glob over the provider id; any provider when unset.
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Price per million prompt tokens.
+    #[serde(default)]
+    pub prompt_per_mtok: f64,
+    /// Price per million completion tokens.
+    #[serde(default)]
+    pub completion_per_mtok: f64,
+}
+
+/// Client-side checks on `shell.exec` before it is sent. A guard against
+/// mistakes, not a sandbox: the daemon still decides what runs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShellPolicyConfig {
+    /// Rules for every workspace.
+    #[serde(flatten)]
+    pub all: ShellPolicy,
+    /// Rules added for workspaces matching the glob key, e.g. `prod*`.
+    pub workspaces: BTreeMap<String, ShellPolicy>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShellPolicy {
+    /// When any are set, every command in the line must match one.
+    pub allow: Vec<ShellRule>,
+    pub deny: Vec<ShellRule>,
+    /// Matching commands need an arming session whose scope names
+    /// `shell.exec:<command>`.
+    pub arm: Vec<ShellRule>,
+    /// Working directories under these prefixes are refused.
+    pub forbidden_cwd: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShellRule {
+    /// Glob over the program, matched against its path and its file name.
+    pub cmd: String,
+    /// Regex searched in the arguments joined by spaces; any arguments if unset.
+    #[serde(default)]
+    pub args: Option<String>,
+    /// Shown in the denial.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Recording of PTY sessions to asciicast v2 files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PtyConfig {
+    /// Workspace globs whose sessions are always recorded, e.g. `prod*`.
+    pub record: Vec<String>,
+    /// Also record what the operator types. Passwords typed at a prompt
+    /// end up in the file, so this is off unless asked for.
+    pub record_input: bool,
+}
+
+/// Guards of the `down` workflow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownConfig {
+    /// Workspace globs where the workspace name has to be typed to confirm.
+    pub confirm: Vec<String>,
+    /// How long to wait for the socket to go away after `down` is accepted.
+    pub timeout_secs: u64,
+}
+
+impl Default for DownConfig {
+    fn default() -> Self {
+        DownConfig {
+            confirm: vec!["prod*".to_string()],
+            timeout_secs: 60,
+        }
+    }
+}
+
+fn default_alert_window_secs() -> u64 {
+    60
+}
+
+fn default_role() -> Role {
+    Role::Viewer
+}
+
+fn config_candidates() -> [String; 3] {
+    let home = crate::home_dir();
+    [
+        format!("{home}/.yai/yx.toml"),
+        format!("{home}/.config/yai/yx.toml"),
+        "yx.toml".to_string(),
+    ]
+}
+
+pub fn parse_config(content: &str) -> Result<YxConfig> {
+    let config: YxConfig = toml::from_str(content).context("invalid yx.toml")?;
+    for pattern in &config.redaction.patterns {
+        regex::Regex::new(pattern)
+            .with_context(|| format!("invalid redaction pattern: {pattern}"))?;
+    }
+    let policies =
+        std::iter::once(&config.shell_policy.all).chain(config.shell_policy.workspaces.values());
+    for policy in policies {
+        for rule in policy.allow.iter().chain(&policy.deny).chain(&policy.arm) {
+            if let Some(args) = &rule.args {
+                regex::Regex::new(args)
+                    .with_context(|| format!("invalid shell policy args pattern: {args}"))?;
+            }
+        }
+    }
+    for rule in &config.usage.prices {
+        if !(rule.prompt_per_mtok >= 0.0 && rule.completion_per_mtok >= 0.0) {
+            anyhow::bail!(
+                "invalid price for model {}: prices must be >= 0",
+                rule.model
+            );
+        }
+    }
+    Ok(config)
+}
+
+/// First readable candidate wins; a missing file yields the defaults.
+pub fn load_config() -> Result<YxConfig> {
+    for path in config_candidates() {
+        if let Ok(content) = fs::read_to_string(&path) {
+            return parse_config(&content).with_context(|| format!("load {path}"));
+        }
+    }
+    Ok(YxConfig::default())
+}
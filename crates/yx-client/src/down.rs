@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use yx_protocol::{LawState, Provider};
+
+use crate::config::DownConfig;
+use crate::event_store::glob_match;
+use crate::in_flight::InFlightCommand;
+use crate::pty::PtySession;
+
+const FOLLOW_POLL_MS: u64 = 250;
+const MOCK_PHASE_MS: u64 = 300;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownRequest {
+    /// Passed to the daemon; also lets `down` go ahead with open PTYs or
+    /// commands in flight.
+    pub force: bool,
+    /// Passed to the daemon: stop the runtime, not just the workspace.
+    pub shutdown: bool,
+    /// The workspace name as typed by the operator, for workspaces matching
+    /// `[down] confirm`.
+    pub confirm: Option<String>,
+    /// `[down] timeout_secs` if unset.
+    pub timeout_secs: Option<u64>,
+}
+
+/// What was in use on the workspace just before `down`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownPreflight {
+    pub ws: String,
+    pub socket_path: String,
+    /// The daemon answered `status`.
+    pub reachable: bool,
+    pub status: Option<Value>,
+    pub law_state: Option<LawState>,
+    pub attached_providers: Vec<Provider>,
+    /// PTYs this client has open in the workspace.
+    pub pty_sessions: Vec<PtySession>,
+    /// Commands this client is still waiting on.
+    pub in_flight: Vec<InFlightCommand>,
+    /// The workspace name has to be typed to confirm.
+    pub confirm_required: bool,
+    /// Why `down` needs `force`, empty when it does not.
+    pub blockers: Vec<String>,
+}
+
+impl DownPreflight {
+    pub(crate) fn blockers(
+        pty_sessions: &[PtySession],
+        in_flight: &[InFlightCommand],
+    ) -> Vec<String> {
+        let mut out = Vec::new();
+        if !pty_sessions.is_empty() {
+            out.push(format!("{} open PTY session(s)", pty_sessions.len()));
+        }
+        if !in_flight.is_empty() {
+            let names: Vec<&str> = in_flight.iter().map(|c| c.name.as_str()).collect();
+            out.push(format!(
+                "{} command(s) in flight: {}",
+                in_flight.len(),
+                names.join(", ")
+            ));
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownPhase {
+    Preflight,
+    /// Arming and confirmation checked.
+    Confirmed,
+    Sending,
+    /// The daemon accepted `down`.
+    Accepted,
+    /// The daemon still answers.
+    Stopping,
+    /// The socket is there but nothing answers on it.
+    Unresponsive,
+    /// The socket is gone.
+    Stopped,
+    TimedOut,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownProgress {
+    pub ws: String,
+    pub phase: DownPhase,
+    pub ts_ms: u64,
+    pub message: String,
+    /// Trace of the `down` command, once sent.
+    pub trace_id: Option<String>,
+    /// The preflight with the `preflight` phase, the error with `failed`.
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub detail: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownOutcome {
+    pub ws: String,
+    pub trace_id: String,
+    pub preflight: DownPreflight,
+    /// `stopped` or `timed_out`.
+    pub phase: DownPhase,
+    /// From acceptance to the last phase.
+    pub waited_ms: u64,
+}
+
+impl DownRequest {
+    pub fn timeout(&self, config: &DownConfig) -> Duration {
+        Duration::from_secs(self.timeout_secs.unwrap_or(config.timeout_secs))
+    }
+}
+
+pub(crate) fn confirm_required(config: &DownConfig, ws: &str) -> bool {
+    config.confirm.iter().any(|p| glob_match(p, ws))
+}
+
+pub(crate) fn message(phase: DownPhase, ws: &str) -> String {
+    match phase {
+        DownPhase::Preflight => format!("preflight of '{ws}' done"),
+        DownPhase::Confirmed => format!("down of '{ws}' confirmed"),
+        DownPhase::Sending => format!("sending down to '{ws}'"),
+        DownPhase::Accepted => format!("'{ws}' accepted down"),
+        DownPhase::Stopping => format!("'{ws}' is stopping"),
+        DownPhase::Unresponsive => format!("'{ws}' no longer answers, waiting for its socket"),
+        DownPhase::Stopped => format!("'{ws}' is down"),
+        DownPhase::TimedOut => format!("'{ws}' still has its socket"),
+        DownPhase::Failed => format!("down of '{ws}' failed"),
+    }
+}
+
+/// Watches the daemon after it accepted `down` until its socket is gone or
+/// `deadline`, reporting each change through `on_phase`. `alive` pings it.
+pub(crate) fn follow(
+    socket_path: &str,
+    deadline: Instant,
+    alive: impl Fn() -> bool,
+    mut on_phase: impl FnMut(DownPhase),
+) -> DownPhase {
+    let mut last = None;
+    loop {
+        let phase = if !Path::new(socket_path).exists() {
+            DownPhase::Stopped
+        } else if Instant::now() >= deadline {
+            DownPhase::TimedOut
+        } else if alive() {
+            DownPhase::Stopping
+        } else {
+            DownPhase::Unresponsive
+        };
+        if last != Some(phase) {
+            on_phase(phase);
+            last = Some(phase);
+        }
+        if matches!(phase, DownPhase::Stopped | DownPhase::TimedOut) {
+            return phase;
+        }
+        std::thread::sleep(Duration::from_millis(FOLLOW_POLL_MS));
+    }
+}
+
+/// Mock daemon: stops after a short pause.
+pub(crate) fn mock_follow(mut on_phase: impl FnMut(DownPhase)) -> DownPhase {
+    for phase in [DownPhase::Stopping, DownPhase::Stopped] {
+        std::thread::sleep(Duration::from_millis(MOCK_PHASE_MS));
+        on_phase(phase);
+    }
+    DownPhase::Stopped
+}
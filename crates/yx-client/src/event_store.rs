@@ -0,0 +1,317 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use yx_protocol::{Event as ProtocolEvent, Severity};
+
+use crate::config::EventStoreConfig;
+
+pub const DEFAULT_PAGE_SIZE: usize = 200;
+const SEGMENT_EXT: &str = "ndjson";
+/// Held while appending, since the CLI and the app write the same store.
+const LOCK_FILE: &str = "append.lock";
+
+static WRITERS: Mutex<Option<HashMap<String, SegmentWriter>>> = Mutex::new(None);
+
+/// An event as persisted: `id` is local to the workspace store and strictly
+/// increasing, unlike the daemon `seq`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredEvent {
+    pub id: u64,
+    pub ws: String,
+    pub received_ms: u64,
+    pub event: ProtocolEvent,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventQuery {
+    /// Defaults to the selected workspace.
+    pub ws: Option<String>,
+    pub since_ms: Option<u64>,
+    pub until_ms: Option<u64>,
+    /// Glob over the topic, `*` and `?` wildcards (`law.*`).
+    pub topic: Option<String>,
+    /// Exact level; aliases such as `warning` are accepted.
+    pub severity: Option<Severity>,
+    pub trace_id: Option<String>,
+    pub seq_from: Option<u64>,
+    pub seq_to: Option<u64>,
+    /// Only events stored before this id; pass `next_cursor` to page back.
+    pub before_id: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventPage {
+    pub ws: String,
+    /// Newest first.
+    pub items: Vec<StoredEvent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<u64>,
+}
+
+struct SegmentWriter {
+    dir: PathBuf,
+    lock: File,
+    index: u64,
+    file: File,
+    bytes: u64,
+    next_id: u64,
+}
+
+pub(crate) fn store_dir(ws: &str) -> PathBuf {
+    PathBuf::from(crate::ws_data_dir(ws)).join("events")
+}
+
+fn segment_path(dir: &Path, index: u64) -> PathBuf {
+    dir.join(format!("{index:08}.{SEGMENT_EXT}"))
+}
+
+/// Segment indexes in `dir`, oldest first.
+fn segments(dir: &Path) -> Vec<u64> {
+    let mut out: Vec<u64> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != SEGMENT_EXT {
+                return None;
+            }
+            path.file_stem()?.to_str()?.parse().ok()
+        })
+        .collect();
+    out.sort_unstable();
+    out
+}
+
+fn read_segment(dir: &Path, index: u64) -> Vec<StoredEvent> {
+    fs::read_to_string(segment_path(dir, index))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+fn open_segment(dir: &Path, index: u64) -> Result<File> {
+    let path = segment_path(dir, index);
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("open event segment: {}", path.display()))
+}
+
+impl SegmentWriter {
+    fn open(ws: &str) -> Result<SegmentWriter> {
+        let dir = store_dir(ws);
+        fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
+        let index = segments(&dir).last().copied().unwrap_or(1);
+        let next_id = segments(&dir)
+            .iter()
+            .rev()
+            .find_map(|idx| read_segment(&dir, *idx).last().map(|e| e.id + 1))
+            .unwrap_or(1);
+        let file = open_segment(&dir, index)?;
+        let bytes = file.metadata().map(|m| m.len()).unwrap_or(0);
+        let lock_path = dir.join(LOCK_FILE);
+        let lock = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&lock_path)
+            .with_context(|| format!("open {}", lock_path.display()))?;
+        Ok(SegmentWriter {
+            dir,
+            lock,
+            index,
+            file,
+            bytes,
+            next_id,
+        })
+    }
+
+    /// Catches up with appends and rotations made by other processes since
+    /// this writer last held the lock.
+    fn sync(&mut self) -> Result<()> {
+        let len = fs::metadata(segment_path(&self.dir, self.index)).map_or(0, |m| m.len());
+        if len == self.bytes && !segment_path(&self.dir, self.index + 1).exists() {
+            return Ok(());
+        }
+        let all = segments(&self.dir);
+        self.index = all.last().copied().unwrap_or(self.index);
+        let tail = all
+            .iter()
+            .rev()
+            .find_map(|idx| read_segment(&self.dir, *idx).last().map(|e| e.id + 1));
+        self.next_id = tail.unwrap_or(1).max(self.next_id);
+        self.file = open_segment(&self.dir, self.index)?;
+        self.bytes = self.file.metadata().map_or(0, |m| m.len());
+        Ok(())
+    }
+
+    fn write(&mut self, line: &str, config: &EventStoreConfig) -> Result<()> {
+        if self.bytes > 0 && self.bytes + line.len() as u64 > config.segment_bytes {
+            self.index += 1;
+            self.file = open_segment(&self.dir, self.index)?;
+            self.bytes = 0;
+            self.enforce_cap(config.max_bytes);
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.bytes += line.len() as u64;
+        Ok(())
+    }
+
+    /// Drops the oldest segments until the store fits in `max_bytes`; the
+    /// segment being written is always kept.
+    fn enforce_cap(&self, max_bytes: u64) {
+        let all = segments(&self.dir);
+        let sizes: Vec<(u64, u64)> = all
+            .iter()
+            .map(|idx| {
+                let len = fs::metadata(segment_path(&self.dir, *idx)).map_or(0, |m| m.len());
+                (*idx, len)
+            })
+            .collect();
+        let mut total: u64 = sizes.iter().map(|(_, len)| len).sum();
+        for (idx, len) in sizes {
+            if total <= max_bytes || idx == self.index {
+                break;
+            }
+            if fs::remove_file(segment_path(&self.dir, idx)).is_ok() {
+                total -= len;
+            }
+        }
+    }
+}
+
+pub(crate) fn append(
+    ws: &str,
+    event: &ProtocolEvent,
+    config: &EventStoreConfig,
+) -> Result<StoredEvent> {
+    let mut slot = WRITERS.lock().unwrap_or_else(|e| e.into_inner());
+    let writers = slot.get_or_insert_with(HashMap::new);
+    if !writers.contains_key(ws) {
+        writers.insert(ws.to_string(), SegmentWriter::open(ws)?);
+    }
+    let writer = writers.get_mut(ws).expect("writer inserted above");
+    writer.lock.lock().context("lock event store")?;
+    let appended = writer.sync().and_then(|()| {
+        let stored = StoredEvent {
+            id: writer.next_id,
+            ws: ws.to_string(),
+            received_ms: crate::now_ms(),
+            event: event.clone(),
+        };
+        let mut line = serde_json::to_string(&stored)?;
+        line.push('\n');
+        writer.write(&line, config)?;
+        writer.next_id += 1;
+        Ok(stored)
+    });
+    let _ = writer.lock.unlock();
+    appended
+}
+
+/// Minimal glob: `*` matches any run of characters, `?` exactly one.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            backtrack = Some((pi, ti));
+            pi += 1;
+        } else if let Some((star, matched)) = backtrack {
+            pi = star + 1;
+            ti = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+impl EventQuery {
+    pub fn matches(&self, stored: &StoredEvent) -> bool {
+        let event = &stored.event;
+        let seq = event.seq;
+        self.since_ms.is_none_or(|ts| event.ts_ms >= ts)
+            && self.until_ms.is_none_or(|ts| event.ts_ms <= ts)
+            && self
+                .topic
+                .as_deref()
+                .is_none_or(|p| glob_match(p, &event.topic))
+            && self.severity.as_ref().is_none_or(|s| event.severity == *s)
+            && self
+                .trace_id
+                .as_deref()
+                .is_none_or(|t| event.trace_id.as_deref() == Some(t))
+            && self
+                .seq_from
+                .is_none_or(|s| seq.is_some_and(|seq| seq >= s))
+            && self.seq_to.is_none_or(|s| seq.is_some_and(|seq| seq <= s))
+            && self.before_id.is_none_or(|id| stored.id < id)
+    }
+}
+
+/// Every stored event of `ws`, oldest first.
+pub(crate) fn scan(ws: &str) -> Vec<StoredEvent> {
+    let dir = store_dir(ws);
+    segments(&dir)
+        .into_iter()
+        .flat_map(|index| read_segment(&dir, index))
+        .collect()
+}
+
+/// Id of the oldest event `ws` still stores, reading only the head of the
+/// oldest segment.
+pub(crate) fn oldest_id(ws: &str) -> Option<u64> {
+    let dir = store_dir(ws);
+    segments(&dir).into_iter().find_map(|index| {
+        let file = File::open(segment_path(&dir, index)).ok()?;
+        BufReader::new(file)
+            .lines()
+            .map_while(std::io::Result::ok)
+            .find_map(|line| serde_json::from_str::<StoredEvent>(&line).ok())
+            .map(|stored| stored.id)
+    })
+}
+
+/// Walks `ws` segments newest to oldest and returns up to `limit` matches.
+pub(crate) fn query(ws: &str, query: &EventQuery) -> EventPage {
+    let dir = store_dir(ws);
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+    let mut items = Vec::new();
+    let mut more = false;
+    'segments: for index in segments(&dir).into_iter().rev() {
+        for stored in read_segment(&dir, index).into_iter().rev() {
+            if !query.matches(&stored) {
+                continue;
+            }
+            if items.len() == limit {
+                more = true;
+                break 'segments;
+            }
+            items.push(stored);
+        }
+    }
+    EventPage {
+        ws: ws.to_string(),
+        next_cursor: if more {
+            items.last().map(|e| e.id)
+        } else {
+            None
+        },
+        items,
+    }
+}
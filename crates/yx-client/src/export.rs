@@ -0,0 +1,302 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use yx_protocol::Severity;
+
+use crate::event_store::{self, EventQuery, StoredEvent};
+use crate::redact::{self, Redactor, REDACTED};
+
+/// Log records per OTLP request line.
+const OTLP_BATCH: usize = 1_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Ndjson,
+    Csv,
+    /// OpenTelemetry file format: one `ExportLogsServiceRequest` per line.
+    OtlpJson,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ndjson" | "jsonl" => Ok(ExportFormat::Ndjson),
+            "csv" => Ok(ExportFormat::Csv),
+            "otlp" | "otlp_json" | "otlp-json" => Ok(ExportFormat::OtlpJson),
+            other => Err(format!("unknown export format: {other}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportRequest {
+    /// Same filters as event queries; `limit` caps the export, which is
+    /// otherwise unbounded.
+    pub query: EventQuery,
+    pub format: ExportFormat,
+    /// Destination file, `-` for stdout. From the desktop app only a file
+    /// name, see `events_export_to_workspace`.
+    pub path: String,
+    /// Keys whose values are replaced at any depth, case-insensitively.
+    pub redact: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportSummary {
+    pub ws: String,
+    pub path: String,
+    pub format: ExportFormat,
+    pub count: usize,
+    pub bytes: u64,
+}
+
+fn redact(value: &mut Value, keys: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if keys.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+                    *v = Value::String(REDACTED.to_string());
+                } else {
+                    redact(v, keys);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| redact(v, keys)),
+        _ => {}
+    }
+}
+
+/// The stored event as JSON, through the configured redaction (events stored
+/// before it was set up) and then `keys`.
+fn export_value(stored: &StoredEvent, redactor: &Redactor, keys: &[String]) -> Value {
+    let mut event = stored.event.clone();
+    redactor.event(&mut event);
+    let mut value = serde_json::to_value(StoredEvent {
+        event,
+        ..stored.clone()
+    })
+    .unwrap_or(Value::Null);
+    if !keys.is_empty() {
+        if let Some(event) = value.get_mut("event") {
+            redact(event, keys);
+        }
+    }
+    value
+}
+
+fn csv_field(raw: &str) -> String {
+    if raw.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", raw.replace('"', "\"\""))
+    } else {
+        raw.to_string()
+    }
+}
+
+const CSV_COLUMNS: [&str; 11] = [
+    "id",
+    "ws",
+    "received_ms",
+    "ts_ms",
+    "topic",
+    "severity",
+    "event_id",
+    "seq",
+    "trace_id",
+    "msg",
+    "data",
+];
+
+fn csv_row(value: &Value) -> String {
+    let event = &value["event"];
+    let cell = |v: &Value| match v {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let cells = [
+        cell(&value["id"]),
+        cell(&value["ws"]),
+        cell(&value["received_ms"]),
+        cell(&event["ts_ms"]),
+        cell(&event["topic"]),
+        cell(&event["severity"]),
+        cell(&event["event_id"]),
+        cell(&event["seq"]),
+        cell(&event["trace_id"]),
+        cell(&event["msg"]),
+        cell(&event["data"]),
+    ];
+    cells
+        .iter()
+        .map(|c| csv_field(c))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn otlp_severity_number(severity: &Severity) -> u8 {
+    match severity {
+        Severity::Trace => 1,
+        Severity::Debug => 5,
+        Severity::Info => 9,
+        Severity::Notice => 10,
+        Severity::Warn => 13,
+        Severity::Error => 17,
+        Severity::Critical => 21,
+        Severity::Unknown(_) => 0,
+    }
+}
+
+/// OTLP `AnyValue` for a JSON value; 64-bit ints are strings in OTLP JSON.
+fn any_value(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({}),
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => json!({ "intValue": i.to_string() }),
+            None => json!({ "doubleValue": n.as_f64() }),
+        },
+        Value::String(s) => json!({ "stringValue": s }),
+        Value::Array(items) => {
+            json!({ "arrayValue": { "values": items.iter().map(any_value).collect::<Vec<_>>() } })
+        }
+        Value::Object(map) => json!({ "kvlistValue": { "values": attributes(map) } }),
+    }
+}
+
+fn attributes(map: &Map<String, Value>) -> Vec<Value> {
+    map.iter()
+        .filter(|(_, v)| !v.is_null())
+        .map(|(k, v)| json!({ "key": k, "value": any_value(v) }))
+        .collect()
+}
+
+fn otlp_record(stored: &StoredEvent, value: &Value) -> Value {
+    let event = &value["event"];
+    let mut attrs = Map::new();
+    attrs.insert("yai.topic".into(), event["topic"].clone());
+    for key in ["event_id", "seq", "trace_id", "data", "compliance"] {
+        attrs.insert(format!("yai.{key}"), event[key].clone());
+    }
+    attrs.insert("yx.id".into(), value["id"].clone());
+    let body = event["msg"]
+        .as_str()
+        .unwrap_or(&stored.event.topic)
+        .to_string();
+    json!({
+        "timeUnixNano": (u128::from(stored.event.ts_ms) * 1_000_000).to_string(),
+        "observedTimeUnixNano": (u128::from(stored.received_ms) * 1_000_000).to_string(),
+        "severityNumber": otlp_severity_number(&stored.event.severity),
+        "severityText": stored.event.severity.as_str(),
+        "body": { "stringValue": body },
+        "attributes": attributes(&attrs),
+    })
+}
+
+fn otlp_request(ws: &str, records: Vec<Value>) -> Value {
+    json!({
+        "resourceLogs": [{
+            "resource": { "attributes": [
+                { "key": "service.name", "value": { "stringValue": "yai" } },
+                { "key": "yai.ws", "value": { "stringValue": ws } },
+            ] },
+            "scopeLogs": [{
+                "scope": { "name": "yx", "version": env!("CARGO_PKG_VERSION") },
+                "logRecords": records,
+            }],
+        }],
+    })
+}
+
+/// Writes the matching events oldest first; returns how many were written.
+fn write_events(
+    out: &mut dyn Write,
+    ws: &str,
+    events: &[StoredEvent],
+    request: &ExportRequest,
+) -> Result<usize> {
+    let redactor = redact::current();
+    let values = events
+        .iter()
+        .map(|e| export_value(e, &redactor, &request.redact));
+    match request.format {
+        ExportFormat::Ndjson => {
+            for value in values {
+                writeln!(out, "{}", serde_json::to_string(&value)?)?;
+            }
+        }
+        ExportFormat::Csv => {
+            writeln!(out, "{}", CSV_COLUMNS.join(","))?;
+            for value in values {
+                writeln!(out, "{}", csv_row(&value))?;
+            }
+        }
+        ExportFormat::OtlpJson => {
+            let records: Vec<Value> = events
+                .iter()
+                .zip(values)
+                .map(|(stored, value)| otlp_record(stored, &value))
+                .collect();
+            for chunk in records.chunks(OTLP_BATCH) {
+                let line = otlp_request(ws, chunk.to_vec());
+                writeln!(out, "{}", serde_json::to_string(&line)?)?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(events.len())
+}
+
+/// `name` inside the exports directory of `ws`; a plain file name, so the
+/// desktop app cannot write anywhere else.
+pub(crate) fn exports_path(ws: &str, name: &str) -> Result<PathBuf> {
+    let plain =
+        |part: &str| !part.is_empty() && !part.contains(['/', '\\']) && !part.starts_with('.');
+    if !plain(ws) {
+        return Err(anyhow!("invalid workspace: {ws}"));
+    }
+    if !plain(name) || name == "-" {
+        return Err(anyhow!("invalid export file name: {name}"));
+    }
+    Ok(PathBuf::from(crate::ws_data_dir(ws))
+        .join("exports")
+        .join(name))
+}
+
+pub(crate) fn export(ws: &str, request: &ExportRequest) -> Result<ExportSummary> {
+    let mut events: Vec<StoredEvent> = event_store::scan(ws)
+        .into_iter()
+        .filter(|e| request.query.matches(e))
+        .collect();
+    if let Some(limit) = request.query.limit {
+        // keep the newest
+        events.drain(..events.len().saturating_sub(limit));
+    }
+
+    let (count, bytes) = if request.path == "-" {
+        let mut out = io::stdout().lock();
+        (write_events(&mut out, ws, &events, request)?, 0)
+    } else {
+        let path = Path::new(&request.path);
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+        }
+        let file = File::create(path).with_context(|| format!("create {}", path.display()))?;
+        let count = write_events(&mut BufWriter::new(file), ws, &events, request)?;
+        (count, fs::metadata(path).map_or(0, |m| m.len()))
+    };
+    Ok(ExportSummary {
+        ws: ws.to_string(),
+        path: request.path.clone(),
+        format: request.format,
+        count,
+        bytes,
+    })
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use yx_protocol::Role;
+
+use crate::config::YxConfig;
+
+const VIEWER_COMMANDS: [&str; 12] = [
+    "status",
+    "ping",
+    "capabilities",
+    "protocol.handshake",
+    "law.snapshot",
+    "logs.tail",
+    "events.subscribe",
+    "providers.list",
+    "providers.status",
+    "chat.sessions.list",
+    "chat.history",
+    "mind.graph.activation.tail",
+];
+const OPERATOR_COMMANDS: [&str; 11] = [
+    "chat.send",
+    "chat.session.new",
+    "providers.discover",
+    "providers.pair",
+    "providers.attach",
+    "providers.detach",
+    "providers.revoke",
+    "shell.exec",
+    "shell.pty.open",
+    "shell.pty.write",
+    "shell.pty.close",
+];
+const ADMIN_COMMANDS: [&str; 1] = ["down"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Identity {
+    pub operator_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    pub role: Role,
+    pub ws: String,
+    /// Whether the identity was found in `yx.toml`.
+    pub configured: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhoAmI {
+    pub identity: Identity,
+    pub can_arm: bool,
+    pub commands: Vec<String>,
+}
+
+/// Lowest role allowed to run `name`. Unknown commands need an admin.
+pub fn required_role(name: &str) -> Role {
+    if VIEWER_COMMANDS.contains(&name) {
+        Role::Viewer
+    } else if OPERATOR_COMMANDS.contains(&name) {
+        Role::Operator
+    } else {
+        Role::Admin
+    }
+}
+
+pub fn role_allows(role: Role, name: &str) -> bool {
+    role >= required_role(name)
+}
+
+pub fn commands_for(role: Role) -> Vec<String> {
+    let mut out: Vec<String> = VIEWER_COMMANDS.iter().map(|c| c.to_string()).collect();
+    if role >= Role::Operator {
+        out.extend(OPERATOR_COMMANDS.iter().map(|c| c.to_string()));
+    }
+    if role >= Role::Admin {
+        out.extend(ADMIN_COMMANDS.iter().map(|c| c.to_string()));
+    }
+    out
+}
+
+pub(crate) fn resolve(config: &YxConfig, ws: &str) -> Identity {
+    let operator_id = env::var("YX_OPERATOR")
+        .ok()
+        .filter(|id| !id.trim().is_empty())
+        .or_else(|| config.identity.active.clone())
+        .or_else(|| env::var("USER").ok())
+        .unwrap_or_else(|| "unknown".to_string());
+    match config
+        .identity
+        .operators
+        .iter()
+        .find(|op| op.id == operator_id)
+    {
+        Some(op) => Identity {
+            operator_id,
+            display_name: op.display_name.clone(),
+            role: op.workspace_roles.get(ws).copied().unwrap_or(op.role),
+            ws: ws.to_string(),
+            configured: true,
+        },
+        // without any configured operators YX keeps acting as a plain operator;
+        // once identities are configured, unknown ones only get to look
+        None => Identity {
+            operator_id,
+            display_name: None,
+            role: if config.identity.operators.is_empty() {
+                Role::Operator
+            } else {
+                Role::Viewer
+            },
+            ws: ws.to_string(),
+            configured: false,
+        },
+    }
+}
+
+/// Who runs YX while `yx.toml` fails to load: the operator list is unknown,
+/// so nobody gets more than Viewer.
+pub(crate) fn unconfigured(ws: &str) -> Identity {
+    Identity {
+        role: Role::Viewer,
+        ..resolve(&YxConfig::default(), ws)
+    }
+}
+
+pub(crate) fn whoami(identity: Identity) -> WhoAmI {
+    WhoAmI {
+        can_arm: identity.role >= Role::Operator,
+        commands: commands_for(identity.role),
+        identity,
+    }
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+static IN_FLIGHT: Mutex<Vec<InFlightCommand>> = Mutex::new(Vec::new());
+
+/// A command sent to the daemon that has not answered yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InFlightCommand {
+    pub trace_id: String,
+    pub ws: String,
+    pub name: String,
+    pub started_ms: u64,
+}
+
+/// Keeps its command listed until dropped.
+pub(crate) struct Tracked(String);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        IN_FLIGHT
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|c| c.trace_id != self.0);
+    }
+}
+
+pub(crate) fn track(ws: &str, trace_id: &str, name: &str) -> Tracked {
+    IN_FLIGHT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(InFlightCommand {
+            trace_id: trace_id.to_string(),
+            ws: ws.to_string(),
+            name: name.to_string(),
+            started_ms: crate::now_ms(),
+        });
+    Tracked(trace_id.to_string())
+}
+
+/// Commands of this process still waiting on `ws`, oldest first.
+pub(crate) fn list(ws: &str) -> Vec<InFlightCommand> {
+    IN_FLIGHT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|c| c.ws == ws)
+        .cloned()
+        .collect()
+}
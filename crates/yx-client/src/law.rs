@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use yx_protocol::{Event as ProtocolEvent, LawState, LawTransition};
+
+use crate::arming::{self, DisarmReason};
+use crate::catalog;
+
+type LawListener = Box<dyn Fn(&LawTransition) + Send>;
+
+static LAW: Mutex<Option<HashMap<String, LawStatus>>> = Mutex::new(None);
+static LISTENERS: Mutex<Vec<LawListener>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LawStatus {
+    pub ws: String,
+    pub state: LawState,
+    pub since_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_transition: Option<LawTransition>,
+}
+
+pub(crate) fn status(ws: &str) -> Option<LawStatus> {
+    let slot = LAW.lock().unwrap_or_else(|e| e.into_inner());
+    slot.as_ref()?.get(ws).cloned()
+}
+
+pub(crate) fn subscribe(listener: LawListener) {
+    let mut listeners = LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+    listeners.push(listener);
+}
+
+/// State carried by a `state.changed` or `law.*` event, if any.
+pub(crate) fn state_from_event(event: &ProtocolEvent) -> Option<LawState> {
+    if event.topic != "state.changed" && !event.topic.starts_with("law.") {
+        return None;
+    }
+    state_from_value(&event.data).or_else(|| event.compliance.as_ref().and_then(state_from_value))
+}
+
+/// Reads the law state out of a `status` or `law.snapshot` result.
+pub(crate) fn state_from_value(value: &Value) -> Option<LawState> {
+    ["law_state", "state", "to", "mode"]
+        .iter()
+        .filter_map(|key| value.get(key).and_then(Value::as_str))
+        .find_map(|s| s.parse().ok())
+}
+
+/// Records `state` for `ws`. Returns the transition when the state changed.
+pub(crate) fn observe(ws: &str, state: LawState, source: &str) -> Option<LawTransition> {
+    let transition = {
+        let mut slot = LAW.lock().unwrap_or_else(|e| e.into_inner());
+        let map = slot.get_or_insert_with(HashMap::new);
+        let from = map.get(ws).map(|s| s.state);
+        if from == Some(state) {
+            return None;
+        }
+        let ts_ms = crate::now_ms();
+        let transition = LawTransition {
+            ws: ws.to_string(),
+            from,
+            to: state,
+            ts_ms,
+            legal: from.is_none_or(|f| f.can_transition_to(state)),
+            source: source.to_string(),
+        };
+        map.insert(
+            ws.to_string(),
+            LawStatus {
+                ws: ws.to_string(),
+                state,
+                since_ms: ts_ms,
+                last_transition: Some(transition.clone()),
+            },
+        );
+        transition
+    };
+
+    if state.is_restricted() {
+        // a lockdown elsewhere leaves a session for another workspace alone
+        arming::disarm_ws(ws, DisarmReason::Lockdown);
+    }
+    catalog::invalidate(ws);
+    let listeners = LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+    for listener in listeners.iter() {
+        listener(&transition);
+    }
+    Some(transition)
+}
@@ -0,0 +1,269 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use yx_protocol::LawState;
+
+use crate::law;
+
+/// Snapshots kept per workspace; the file is compacted at twice this size.
+pub const LAW_HISTORY_LIMIT: usize = 128;
+
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LawSnapshotRecord {
+    pub seq: u64,
+    pub ws: String,
+    pub ts_ms: u64,
+    pub trace_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<LawState>,
+    /// Rules keyed by id (or name, or position when the daemon gives neither).
+    pub rules: BTreeMap<String, Value>,
+    /// Violations keyed by id, falling back to their JSON text.
+    pub violations: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleChange {
+    pub id: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateChange {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<LawState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<LawState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LawDiff {
+    pub ws: String,
+    pub from_seq: u64,
+    pub to_seq: u64,
+    pub from_ts_ms: u64,
+    pub to_ts_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<StateChange>,
+    pub rules_added: BTreeMap<String, Value>,
+    pub rules_removed: BTreeMap<String, Value>,
+    pub rules_changed: Vec<RuleChange>,
+    pub new_violations: Vec<Value>,
+    pub resolved_violations: Vec<Value>,
+}
+
+impl LawDiff {
+    pub fn is_empty(&self) -> bool {
+        self.state.is_none()
+            && self.rules_added.is_empty()
+            && self.rules_removed.is_empty()
+            && self.rules_changed.is_empty()
+            && self.new_violations.is_empty()
+            && self.resolved_violations.is_empty()
+    }
+}
+
+fn history_path(ws: &str) -> PathBuf {
+    PathBuf::from(crate::ws_data_dir(ws)).join("law_history.jsonl")
+}
+
+fn snapshot_body(result: &Value) -> &Value {
+    result
+        .get("law")
+        .or_else(|| result.get("snapshot"))
+        .unwrap_or(result)
+}
+
+fn entry_key(value: &Value, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .find_map(|k| value.get(k).and_then(Value::as_str))
+        .map(ToString::to_string)
+}
+
+fn rules_of(body: &Value) -> BTreeMap<String, Value> {
+    match body.get("rules") {
+        Some(Value::Object(map)) => map.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .enumerate()
+            .map(|(idx, rule)| {
+                let key = entry_key(rule, &["id", "rule_id", "name"])
+                    .unwrap_or_else(|| format!("#{idx}"));
+                (key, rule.clone())
+            })
+            .collect(),
+        _ => BTreeMap::new(),
+    }
+}
+
+fn violations_of(body: &Value) -> BTreeMap<String, Value> {
+    let Some(Value::Array(items)) = body.get("violations") else {
+        return BTreeMap::new();
+    };
+    items
+        .iter()
+        .map(|v| {
+            let key =
+                entry_key(v, &["id", "violation_id", "event_id"]).unwrap_or_else(|| v.to_string());
+            (key, v.clone())
+        })
+        .collect()
+}
+
+fn read_history(ws: &str) -> Result<Vec<LawSnapshotRecord>> {
+    let path = history_path(ws);
+    let content = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+    };
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Stores a `law.snapshot` result. Snapshots identical to the previous one are
+/// skipped; returns the record when one was written.
+pub(crate) fn record(
+    ws: &str,
+    trace_id: &str,
+    result: &Value,
+) -> Result<Option<LawSnapshotRecord>> {
+    let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let body = snapshot_body(result);
+    let mut history = read_history(ws)?;
+    let last = history.last();
+    let record = LawSnapshotRecord {
+        seq: last.map_or(1, |r| r.seq + 1),
+        ws: ws.to_string(),
+        ts_ms: crate::now_ms(),
+        trace_id: trace_id.to_string(),
+        state: law::state_from_value(body).or_else(|| law::state_from_value(result)),
+        rules: rules_of(body),
+        violations: violations_of(body),
+    };
+    if last.is_some_and(|r| {
+        r.state == record.state && r.rules == record.rules && r.violations == record.violations
+    }) {
+        return Ok(None);
+    }
+
+    let path = history_path(ws);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    }
+    history.push(record.clone());
+    if history.len() > LAW_HISTORY_LIMIT * 2 {
+        history.drain(..history.len() - LAW_HISTORY_LIMIT);
+        let mut out = String::new();
+        for r in &history {
+            out.push_str(&serde_json::to_string(r)?);
+            out.push('\n');
+        }
+        fs::write(&path, out).with_context(|| format!("write {}", path.display()))?;
+    } else {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("open {}", path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(&record)?)?;
+    }
+    Ok(Some(record))
+}
+
+/// Most recent `limit` snapshots of `ws`, oldest first.
+pub(crate) fn history(ws: &str, limit: usize) -> Result<Vec<LawSnapshotRecord>> {
+    let mut items = read_history(ws)?;
+    let skip = items.len().saturating_sub(limit.min(LAW_HISTORY_LIMIT));
+    items.drain(..skip);
+    Ok(items)
+}
+
+pub fn diff_records(from: &LawSnapshotRecord, to: &LawSnapshotRecord) -> LawDiff {
+    let mut rules_added = BTreeMap::new();
+    let mut rules_changed = Vec::new();
+    for (id, rule) in &to.rules {
+        match from.rules.get(id) {
+            None => {
+                rules_added.insert(id.clone(), rule.clone());
+            }
+            Some(before) if before != rule => rules_changed.push(RuleChange {
+                id: id.clone(),
+                before: before.clone(),
+                after: rule.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    let rules_removed = from
+        .rules
+        .iter()
+        .filter(|(id, _)| !to.rules.contains_key(*id))
+        .map(|(id, rule)| (id.clone(), rule.clone()))
+        .collect();
+    let new_violations = to
+        .violations
+        .iter()
+        .filter(|(id, _)| !from.violations.contains_key(*id))
+        .map(|(_, v)| v.clone())
+        .collect();
+    let resolved_violations = from
+        .violations
+        .iter()
+        .filter(|(id, _)| !to.violations.contains_key(*id))
+        .map(|(_, v)| v.clone())
+        .collect();
+    LawDiff {
+        ws: to.ws.clone(),
+        from_seq: from.seq,
+        to_seq: to.seq,
+        from_ts_ms: from.ts_ms,
+        to_ts_ms: to.ts_ms,
+        state: (from.state != to.state).then_some(StateChange {
+            from: from.state,
+            to: to.state,
+        }),
+        rules_added,
+        rules_removed,
+        rules_changed,
+        new_violations,
+        resolved_violations,
+    }
+}
+
+pub(crate) fn diff(ws: &str, from_seq: u64, to_seq: u64) -> Result<LawDiff> {
+    let history = read_history(ws)?;
+    let find = |seq: u64| {
+        history
+            .iter()
+            .find(|r| r.seq == seq)
+            .ok_or_else(|| anyhow!("no law snapshot #{seq} for workspace '{ws}'"))
+    };
+    Ok(diff_records(find(from_seq)?, find(to_seq)?))
+}
+
+/// Diff from the last snapshot taken at or before `since_ts_ms` (or the oldest
+/// one kept) to the latest. `None` with fewer than two snapshots.
+pub(crate) fn diff_since(ws: &str, since_ts_ms: u64) -> Result<Option<LawDiff>> {
+    let history = read_history(ws)?;
+    let Some(latest) = history.last() else {
+        return Ok(None);
+    };
+    let base = history
+        .iter()
+        .rev()
+        .find(|r| r.ts_ms <= since_ts_ms)
+        .or_else(|| history.first())
+        .filter(|r| r.seq != latest.seq);
+    Ok(base.map(|base| diff_records(base, latest)))
+}
@@ -10,7 +10,85 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{atomic::AtomicBool, Arc};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
-use yx_protocol::{CommandError, CommandRequest, CommandResult, Event as ProtocolEvent};
+use yx_protocol::{
+    ChatHistory, ChatMessage, ChatRole, ChatSession, ChatSessionList, ChatUsage, CommandError,
+    CommandRequest, CommandResult, Event as ProtocolEvent, LawState, LawTransition, Provider,
+    ProviderList, ProviderState, ProviderStatus, ProviderTransition, Role,
+};
+
+mod alerts;
+mod arming;
+mod audit;
+mod batch;
+mod catalog;
+mod chat;
+mod chat_context;
+mod config;
+mod down;
+mod event_store;
+mod export;
+mod identity;
+mod in_flight;
+mod journal;
+mod law;
+mod law_history;
+mod pairing;
+mod provider_health;
+mod providers;
+mod pty;
+mod redact;
+mod search;
+mod shell;
+mod shell_policy;
+mod stats;
+mod trace;
+mod usage;
+
+pub use alerts::{Alert, AlertStatus};
+pub use arming::{
+    requires_arming, ArmingSession, ArmingState, Disarm, DisarmReason, MAX_ARMING_TTL_SECS,
+};
+pub use audit::{AuditEntry, AuditQuery, AuditVerification};
+pub use batch::{OverflowStats, OVERFLOW_TOPIC};
+pub use catalog::{builtin_catalog, CatalogSnapshot, CatalogSource};
+pub use chat::{
+    ChatExchange, ChatExportFormat, ChatSearchHit, ChatSearchRequest, ChatSearchResults,
+    DEFAULT_CHAT_SEARCH_LIMIT,
+};
+pub use chat_context::{
+    ChatContext, ChatContextRequest, ContextBlock, ContextItem, DEFAULT_CONTEXT_BUDGET,
+};
+pub use config::{
+    load_config, parse_config, AlertRule, AlertsConfig, DownConfig, EventStoreConfig,
+    IdentityConfig, OperatorConfig, OverflowPolicy, PriceRule, ProviderHealthConfig, PtyConfig,
+    RedactionConfig, ShellPolicy, ShellPolicyConfig, ShellRule, StreamConfig, UsageConfig,
+    YxConfig,
+};
+pub use down::{DownOutcome, DownPhase, DownPreflight, DownProgress, DownRequest};
+pub use event_store::{EventPage, EventQuery, StoredEvent, DEFAULT_PAGE_SIZE};
+pub use export::{ExportFormat, ExportRequest, ExportSummary};
+pub use identity::{commands_for, required_role, role_allows, Identity, WhoAmI};
+pub use in_flight::InFlightCommand;
+pub use law::LawStatus;
+pub use pairing::{PairingError, PairingSession, PairingStep, PairingVerification};
+pub use provider_health::{HealthChange, HealthStatus, Probe, ProviderHealth, HEALTH_TOPIC};
+pub use pty::{
+    CastEvent, PtyOpenRequest, PtyOutput, PtySession, Recording, RecordingSummary,
+    DEFAULT_PTY_COLS, DEFAULT_PTY_ROWS,
+};
+pub use redact::REDACTED;
+pub use search::{Highlight, SearchHit, SearchRequest, SearchResults, DEFAULT_SEARCH_LIMIT};
+pub use shell::{
+    ShellChunk, ShellExecRequest, ShellExit, ShellOutput, ShellStream, DEFAULT_MAX_OUTPUT_BYTES,
+    DEFAULT_SHELL_TIMEOUT_MS,
+};
+pub use law_history::{
+    diff_records as law_diff_records, LawDiff, LawSnapshotRecord, RuleChange, StateChange,
+    LAW_HISTORY_LIMIT,
+};
+pub use stats::{EventStats, LagBucket, LagHistogram, RateWindows, TopicStats};
+pub use trace::{CommandRecord, Trace, TraceEntry, TraceEntryKind};
+pub use usage::{UsageGroupBy, UsageQuery, UsageRecord, UsageReport, UsageRow};
 
 const DEFAULT_PING_TIMEOUT_MS: u64 = 1200;
 const RPC_PROTOCOL_VERSION: u8 = 1;
@@ -74,6 +152,22 @@ fn now_ms() -> u64 {
         .as_millis() as u64
 }
 
+/// `YYYY-MM-DD` of epoch `ms` in UTC.
+fn utc_date(ms: u64) -> String {
+    let days = (ms / 1000 / 86_400) as i64;
+    // civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
 fn next_trace_id() -> String {
     format!(
         "yx-{}-{}",
@@ -90,6 +184,14 @@ fn run_root() -> String {
     format!("{}/.yai/run", home_dir())
 }
 
+fn yx_data_dir() -> String {
+    format!("{}/.yai/yx", home_dir())
+}
+
+fn ws_data_dir(ws: &str) -> String {
+    format!("{}/{}", yx_data_dir(), ws)
+}
+
 fn socket_path_for_ws(ws: &str) -> String {
     format!("{}/{}/control.sock", run_root(), ws)
 }
@@ -157,7 +259,8 @@ fn ping_socket(sock: &str, ws: &str, timeout: Duration) -> Result<u64> {
     let started = Instant::now();
 
     // ping minimale, niente handshake a raffica
-    let _ = send_command_real(sock, ws, "ping", json!({}), false, &trace_id, timeout)?;
+    let identity = current_identity(ws);
+    let _ = send_command_real(sock, &identity, "ping", json!({}), None, &trace_id, timeout)?;
 
     Ok(started.elapsed().as_millis() as u64)
 }
@@ -175,10 +278,10 @@ fn resolve_ws_preference() -> String {
     let candidates = list_known_workspaces();
     for ws in candidates {
         let sock = socket_path_for_ws(&ws);
-        if fs::metadata(&sock).is_ok() {
-            if ping_socket(&sock, &ws, Duration::from_millis(DEFAULT_PING_TIMEOUT_MS)).is_ok() {
-                return ws;
-            }
+        if fs::metadata(&sock).is_ok()
+            && ping_socket(&sock, &ws, Duration::from_millis(DEFAULT_PING_TIMEOUT_MS)).is_ok()
+        {
+            return ws;
         }
     }
     "dev".to_string()
@@ -262,9 +365,1369 @@ pub fn ping_selected() -> PingState {
     }
 }
 
+/// Sends `name` to the selected workspace. Commands that require arming, or
+/// that the caller asks to arm, only go out under an active arming session
+/// whose scope covers them. The returned result is redacted.
 pub fn send_command(name: &str, args: Value, arming: bool) -> CommandResult {
+    let mut result = run_command(name, args, arming, false);
+    redact::current().result(&mut result);
+    result
+}
+
+/// `send_command` without redaction of the returned result, for armed
+/// admins only. Journals still store the redacted form and the call is
+/// always audited.
+pub fn send_command_unredacted(name: &str, args: Value) -> CommandResult {
+    run_command(name, args, true, true)
+}
+
+/// `value` as it may leave the client, scrubbed per `[redaction]`. Results
+/// of the client API are raw; frontends pass them through this before
+/// showing or printing them.
+pub fn redacted<T: Serialize>(value: &T) -> Value {
+    let value = serde_json::to_value(value).unwrap_or_default();
+    redact::current().redacted(&value)
+}
+
+fn run_command(name: &str, args: Value, arming: bool, unredacted: bool) -> CommandResult {
+    let state = connection_state();
+    let identity = current_identity(&state.selected_ws);
+    let needs_arming = arming || arming::requires_arming(name);
+    let session = if needs_arming {
+        arming::active_session(&state.selected_ws)
+    } else {
+        None
+    };
+    let requested_ms = now_ms();
+    let redactor = redact::current();
+    let logged_args = redactor.redacted(&args);
+    let denied = if let Some(denied) = workflow_only(name, &state.selected_ws) {
+        Some(denied)
+    } else if unredacted && identity.role != Role::Admin {
+        Some(rejected(
+            name,
+            "unredacted_denied",
+            "unredacted results require an armed admin".to_string(),
+            json!({ "workspace": state.selected_ws, "role": identity.role }),
+        ))
+    } else {
+        gate(
+            &state,
+            &identity,
+            name,
+            &args,
+            needs_arming,
+            session.as_ref(),
+        )
+    };
+    let result = match denied {
+        Some(denied) => denied,
+        None => {
+            let result = dispatch_command(&state, &identity, name, args, session.as_ref());
+            if result.ok && (name == "status" || name == "law.snapshot") {
+                if let Some(law_state) = result.result.as_ref().and_then(law::state_from_value) {
+                    law::observe(&state.selected_ws, law_state, name);
+                }
+            }
+            if result.ok && name == "law.snapshot" {
+                if let Some(snapshot) = &result.result {
+                    let snapshot = redactor.redacted(snapshot);
+                    let _ = law_history::record(&state.selected_ws, &result.id, &snapshot);
+                }
+            }
+            result
+        }
+    };
+    let mut logged = result.clone();
+    redactor.result(&mut logged);
+    journal_command(
+        &state.selected_ws,
+        &identity,
+        name,
+        logged_args,
+        needs_arming,
+        session.is_some(),
+        unredacted,
+        requested_ms,
+        &logged,
+    );
+    result
+}
+
+/// Commands that only their own workflow may send: `down` after its
+/// preflight and confirmation, PTY open and input so the PTY is registered
+/// and recorded.
+fn workflow_only(name: &str, ws: &str) -> Option<CommandResult> {
+    let (code, message) = match name {
+        "down" => (
+            "use_down_workflow",
+            "down goes through the down workflow, which runs the preflight and confirmation",
+        ),
+        "shell.pty.open" | "shell.pty.write" => (
+            "use_pty_session",
+            "PTYs are opened and written through pty_open and pty_write, which record them",
+        ),
+        _ => return None,
+    };
+    Some(rejected(
+        name,
+        code,
+        message.to_string(),
+        json!({ "workspace": ws }),
+    ))
+}
+
+/// Records a finished command in the trace journal and, when privileged,
+/// the audit journal. `args` and `result` are already redacted.
+#[allow(clippy::too_many_arguments)]
+fn journal_command(
+    ws: &str,
+    identity: &Identity,
+    name: &str,
+    args: Value,
+    needs_arming: bool,
+    has_session: bool,
+    unredacted: bool,
+    requested_ms: u64,
+    result: &CommandResult,
+) {
+    let armed = result.ok && has_session;
+    if audit::requires_audit(name, needs_arming) {
+        let _ = audit::record(
+            ws,
+            Some(identity.role.as_str()),
+            name,
+            &args,
+            armed,
+            unredacted,
+            result,
+        );
+    }
+    let _ = trace::record_command(&CommandRecord {
+        trace_id: result.id.clone(),
+        ws: ws.to_string(),
+        name: name.to_string(),
+        args,
+        arming: armed,
+        requested_ms,
+        result: result.clone(),
+    });
+}
+
+/// A command that holds the connection open, once it has passed `gate`:
+/// what it needs to talk to the daemon and to journal how it went.
+struct Admitted {
+    name: &'static str,
+    state: ConnectionState,
+    identity: Identity,
+    session: Option<ArmingSession>,
+    requested_ms: u64,
+    redactor: Arc<redact::Redactor>,
+    logged_args: Value,
+}
+
+impl Admitted {
+    /// Runs the checks of `send_command` on `name`; a refusal is journalled
+    /// and returned as the error.
+    fn check(name: &'static str, args: &Value) -> Result<Admitted, CommandError> {
+        let state = connection_state();
+        let identity = current_identity(&state.selected_ws);
+        let session = arming::active_session(&state.selected_ws);
+        let needs_arming = arming::requires_arming(name);
+        let redactor = redact::current();
+        let admitted = Admitted {
+            name,
+            requested_ms: now_ms(),
+            logged_args: redactor.redacted(args),
+            redactor,
+            state,
+            identity,
+            session,
+        };
+        let session = admitted.session.as_ref().filter(|_| needs_arming);
+        let denied = gate(
+            &admitted.state,
+            &admitted.identity,
+            name,
+            args,
+            needs_arming,
+            session,
+        );
+        match denied {
+            Some(denied) => {
+                admitted.journal(&denied);
+                Err(denied.error.unwrap_or_else(|| CommandError {
+                    code: "command_failed".to_string(),
+                    message: format!("Command failed: {name}"),
+                    details: None,
+                    trace_id: Some(denied.id),
+                }))
+            }
+            None => Ok(admitted),
+        }
+    }
+
+    fn ws(&self) -> &str {
+        &self.state.selected_ws
+    }
+
+    fn journal(&self, result: &CommandResult) {
+        let needs_arming = arming::requires_arming(self.name);
+        journal_command(
+            self.ws(),
+            &self.identity,
+            self.name,
+            self.logged_args.clone(),
+            needs_arming,
+            needs_arming && self.session.is_some(),
+            false,
+            self.requested_ms,
+            result,
+        );
+    }
+
+    /// Journals a refusal by a check of the caller's own and returns it.
+    fn refuse(&self, code: &str, message: String, details: Value) -> CommandError {
+        let result = rejected(self.name, code, message, details);
+        self.journal(&result);
+        result.error.expect("rejected always carries an error")
+    }
+
+    /// Journals `result`, already redacted, as the outcome of `trace_id`.
+    fn succeeded(&self, trace_id: &str, result: Value) {
+        self.journal(&CommandResult {
+            id: trace_id.to_string(),
+            ts_ms: now_ms(),
+            name: self.name.to_string(),
+            ok: true,
+            result: Some(result),
+            error: None,
+        });
+    }
+
+    /// Journals a failure to talk to the daemon and returns it as an error.
+    fn failed(&self, trace_id: &str, error: anyhow::Error) -> CommandError {
+        let code = if self.state.connected {
+            "command_failed"
+        } else {
+            "sock_unavailable"
+        };
+        let error = CommandError {
+            code: code.to_string(),
+            message: self.redactor.text(&format!("{error:#}")),
+            details: Some(json!({
+                "workspace": self.ws(),
+                "socket_path": socket_path_for_ws(self.ws()),
+            })),
+            trace_id: Some(trace_id.to_string()),
+        };
+        self.journal(&CommandResult {
+            id: trace_id.to_string(),
+            ts_ms: now_ms(),
+            name: self.name.to_string(),
+            ok: false,
+            result: None,
+            error: Some(error.clone()),
+        });
+        error
+    }
+
+    /// Sends the command as `send_command` would and journals the outcome;
+    /// the returned result is not redacted.
+    fn dispatch(&self, args: Value) -> CommandResult {
+        let session = self
+            .session
+            .as_ref()
+            .filter(|_| arming::requires_arming(self.name));
+        let result = dispatch_command(&self.state, &self.identity, self.name, args, session);
+        let mut logged = result.clone();
+        self.redactor.result(&mut logged);
+        self.journal(&logged);
+        result
+    }
+
+    #[cfg(unix)]
+    fn open(
+        &self,
+        args: Value,
+        trace_id: &str,
+        timeout: Duration,
+    ) -> Result<BufReader<UnixStream>> {
+        open_request(
+            &socket_path_for_ws(self.ws()),
+            &self.identity,
+            self.name,
+            args,
+            self.session.as_ref(),
+            trace_id,
+            timeout,
+        )
+    }
+}
+
+/// Runs `request` as a streaming `shell.exec`, handing interleaved stdout
+/// and stderr chunks, then the exit, to `on_output` as they arrive. Goes
+/// through the same role, law and arming checks as `send_command`; a
+/// timeout or a non-zero exit is an outcome, not an error.
+pub fn shell_exec_stream(
+    request: &ShellExecRequest,
+    mut on_output: impl FnMut(&ShellOutput),
+) -> Result<ShellExit, CommandError> {
+    let args = request.args_value();
+    let admitted = Admitted::check("shell.exec", &args)?;
     let trace_id = next_trace_id();
+    let started = Instant::now();
+    let deadline = started + request.timeout();
+    let mut sink = shell::Sink::new(&trace_id, request, &admitted.redactor, &mut on_output);
+    let _tracked = in_flight::track(admitted.ws(), &trace_id, "shell.exec");
+    let outcome = if admitted.state.resolved_mode == Mode::Mock {
+        Ok(shell::mock_stream(request, deadline, &mut sink))
+    } else {
+        stream_shell(&admitted, args, &trace_id, deadline, &mut sink)
+    };
+    match outcome {
+        Ok((exit_code, signal, timed_out)) => {
+            let (exit, output) = sink.finish(exit_code, signal, timed_out, started);
+            admitted.succeeded(&trace_id, output);
+            Ok(exit)
+        }
+        Err(error) => Err(admitted.failed(&trace_id, error)),
+    }
+}
+
+#[cfg(unix)]
+fn stream_shell(
+    admitted: &Admitted,
+    args: Value,
+    trace_id: &str,
+    deadline: Instant,
+    sink: &mut shell::Sink,
+) -> Result<(Option<i32>, Option<String>, bool)> {
+    let left = deadline.saturating_duration_since(Instant::now());
+    let mut reader = admitted.open(args, trace_id, left.min(Duration::from_secs(3)))?;
+    shell::read_stream(&mut reader, deadline, sink)
+}
+
+#[cfg(not(unix))]
+fn stream_shell(
+    _admitted: &Admitted,
+    _args: Value,
+    _trace_id: &str,
+    _deadline: Instant,
+    _sink: &mut shell::Sink,
+) -> Result<(Option<i32>, Option<String>, bool)> {
+    Err(anyhow!("real mode requires unix sockets"))
+}
+
+/// Opens a PTY through the same checks as `send_command`, handing its
+/// output, then the exit, to `on_output` from a reader thread. Recorded to
+/// an asciicast v2 file named after the trace id when `request.record` is
+/// set or `[pty] record` matches the workspace.
+pub fn pty_open(
+    request: &PtyOpenRequest,
+    on_output: impl FnMut(&PtyOutput) + Send + 'static,
+) -> Result<PtySession, CommandError> {
+    let args = request.args_value();
+    let admitted = Admitted::check("shell.pty.open", &args)?;
+    // forced recording must not lapse because yx.toml does not load
+    let config = match load_config() {
+        Ok(config) => config.pty,
+        Err(error) => {
+            return Err(admitted.refuse(
+                "policy_denied",
+                format!("pty recording policy unavailable, yx.toml does not load: {error:#}"),
+                json!({
+                    "workspace": admitted.ws(),
+                    "command": "shell.pty.open",
+                    "config_error": format!("{error:#}"),
+                }),
+            ));
+        }
+    };
+    let trace_id = next_trace_id();
+    let (record, record_input) = request.recording(&config, admitted.ws());
+    let mock = admitted.state.resolved_mode == Mode::Mock;
+    #[cfg(unix)]
+    let mut reader = None;
+    let pty_id = if mock {
+        format!("mock-pty-{}", TRACE_COUNTER.fetch_add(1, Ordering::Relaxed))
+    } else {
+        #[cfg(unix)]
+        {
+            let opened = admitted
+                .open(args, &trace_id, Duration::from_secs(5))
+                .and_then(|mut r| pty::read_opened(&mut r).map(|id| (id, r)));
+            let (pty_id, opened) = opened.map_err(|e| admitted.failed(&trace_id, e))?;
+            reader = Some(opened);
+            pty_id
+        }
+        #[cfg(not(unix))]
+        return Err(admitted.failed(&trace_id, anyhow!("real mode requires unix sockets")));
+    };
+    let session = PtySession {
+        pty_id,
+        trace_id: trace_id.clone(),
+        ws: admitted.ws().to_string(),
+        operator_id: admitted.identity.operator_id.clone(),
+        cmd: request.cmd.clone(),
+        cols: request.cols(),
+        rows: request.rows(),
+        opened_ms: now_ms(),
+        recording: record.then(|| trace_id.clone()),
+        record_input,
+    };
+    let recorder = if record {
+        let created = pty::Recorder::create(
+            &session,
+            request.title.as_deref(),
+            admitted.redactor.clone(),
+        );
+        match created {
+            Ok(recorder) => Some(recorder),
+            Err(error) => {
+                // an unrecorded session must not stay open where recording is asked for
+                if !mock {
+                    let _ = run_command(
+                        "shell.pty.close",
+                        json!({ "pty_id": session.pty_id }),
+                        false,
+                        false,
+                    );
+                }
+                return Err(admitted.failed(&trace_id, error));
+            }
+        }
+    } else {
+        None
+    };
+    admitted.succeeded(
+        &trace_id,
+        json!({ "pty_id": session.pty_id, "recording": session.recording }),
+    );
+    let pty = pty::register(session.clone(), recorder, Box::new(on_output));
+    if mock {
+        pty::mock_open(&pty);
+    }
+    #[cfg(unix)]
+    if let Some(mut reader) = reader {
+        reader.get_ref().set_read_timeout(None).ok();
+        std::thread::spawn(move || pty::follow(&mut reader, &pty));
+    }
+    Ok(session)
+}
+
+/// Sends typed `data` to an open PTY, recording it when the session records
+/// input. Checked like any command but not journalled per keystroke.
+pub fn pty_write(pty_id: &str, data: &str) -> Result<(), CommandError> {
+    let pty = open_pty(pty_id)?;
+    let args = json!({ "pty_id": pty_id, "data": data });
     let state = connection_state();
+    let identity = current_identity(&state.selected_ws);
+    if let Some(denied) = gate(&state, &identity, "shell.pty.write", &args, false, None) {
+        return Err(denied
+            .error
+            .unwrap_or_else(|| pty_error(pty_id, "command_failed", "denied")));
+    }
+    pty.input(data);
+    if state.resolved_mode == Mode::Mock {
+        pty::mock_write(&pty, data);
+        return Ok(());
+    }
+    write_pty(&state.selected_ws, &identity, args)
+        .map_err(|e| pty_error(pty_id, "command_failed", &format!("{e:#}")))
+}
+
+/// Closes an open PTY; its exit reaches the `pty_open` callback.
+pub fn pty_close(pty_id: &str) -> Result<(), CommandError> {
+    let pty = open_pty(pty_id)?;
+    let result = run_command("shell.pty.close", json!({ "pty_id": pty_id }), false, false);
+    if !result.ok {
+        return Err(result
+            .error
+            .unwrap_or_else(|| pty_error(pty_id, "command_failed", "close failed")));
+    }
+    pty.exit(None, Some("SIGHUP".to_string()));
+    Ok(())
+}
+
+/// PTYs this process has open in the selected workspace.
+pub fn pty_sessions() -> Vec<PtySession> {
+    pty::sessions(&resolve_ws_preference())
+}
+
+/// Recordings of the selected workspace, newest first.
+pub fn recordings() -> Vec<RecordingSummary> {
+    pty::recordings(&resolve_ws_preference())
+}
+
+/// Recording `id` with its header and timed events, for playback.
+pub fn recording(id: &str) -> Result<Recording> {
+    pty::recording(&resolve_ws_preference(), id)
+}
+
+/// Recording `id` as the asciicast v2 file, for standard players.
+pub fn recording_cast(id: &str) -> Result<String> {
+    pty::cast(&resolve_ws_preference(), id)
+}
+
+fn pty_error(pty_id: &str, code: &str, message: &str) -> CommandError {
+    CommandError {
+        code: code.to_string(),
+        message: message.to_string(),
+        details: Some(json!({ "pty_id": pty_id })),
+        trace_id: None,
+    }
+}
+
+fn open_pty(pty_id: &str) -> Result<Arc<pty::Pty>, CommandError> {
+    pty::get(pty_id)
+        .filter(|pty| pty.session.ws == resolve_ws_preference())
+        .ok_or_else(|| pty_error(pty_id, "pty_not_found", &format!("no open pty {pty_id}")))
+}
+
+#[cfg(unix)]
+fn write_pty(ws: &str, identity: &Identity, args: Value) -> Result<()> {
+    let mut reader = open_request(
+        &socket_path_for_ws(ws),
+        identity,
+        "shell.pty.write",
+        args,
+        None,
+        &next_trace_id(),
+        Duration::from_secs(3),
+    )?;
+    pty::read_ack(&mut reader)
+}
+
+#[cfg(not(unix))]
+fn write_pty(_ws: &str, _identity: &Identity, _args: Value) -> Result<()> {
+    Err(anyhow!("real mode requires unix sockets"))
+}
+
+/// What `down` would interrupt on the selected workspace: the daemon's
+/// status, attached providers, open PTYs and commands still in flight.
+pub fn down_preflight() -> DownPreflight {
+    let ws = resolve_ws_preference();
+    let config = load_config().unwrap_or_default().down;
+    let status = run_command("status", json!({}), false, false);
+    let reachable = status.ok;
+    let status = status.result.filter(|_| reachable);
+    let attached_providers = if reachable {
+        providers_list()
+            .map(|list| {
+                list.items
+                    .into_iter()
+                    .filter(|p| p.state == ProviderState::Attached)
+                    .collect()
+            })
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let pty_sessions = pty::sessions(&ws);
+    let in_flight = in_flight::list(&ws);
+    DownPreflight {
+        socket_path: socket_path_for_ws(&ws),
+        reachable,
+        law_state: status.as_ref().and_then(law::state_from_value),
+        status,
+        attached_providers,
+        blockers: DownPreflight::blockers(&pty_sessions, &in_flight),
+        pty_sessions,
+        in_flight,
+        confirm_required: down::confirm_required(&config, &ws),
+        ws,
+    }
+}
+
+/// Takes the selected workspace down: a preflight, then arming and, where
+/// `[down] confirm` matches, the workspace name typed back, then `down`
+/// itself, following the daemon until its socket is gone. Every phase is
+/// reported to `on_progress`. Open PTYs or commands in flight need `force`.
+pub fn down(
+    request: &DownRequest,
+    mut on_progress: impl FnMut(&DownProgress),
+) -> Result<DownOutcome, CommandError> {
+    let preflight = down_preflight();
+    let ws = preflight.ws.clone();
+    let mut report = |phase: DownPhase, trace_id: Option<&str>, detail: Value| {
+        on_progress(&DownProgress {
+            ws: ws.clone(),
+            phase,
+            ts_ms: now_ms(),
+            message: down::message(phase, &ws),
+            trace_id: trace_id.map(str::to_string),
+            detail,
+        });
+    };
+    let detail = serde_json::to_value(&preflight).unwrap_or_default();
+    report(DownPhase::Preflight, None, detail);
+    run_down(request, preflight, &mut report).inspect_err(|error| {
+        let detail = serde_json::to_value(error).unwrap_or_default();
+        report(DownPhase::Failed, error.trace_id.as_deref(), detail);
+    })
+}
+
+fn run_down(
+    request: &DownRequest,
+    preflight: DownPreflight,
+    report: &mut impl FnMut(DownPhase, Option<&str>, Value),
+) -> Result<DownOutcome, CommandError> {
+    let ws = preflight.ws.clone();
+    let args = json!({ "force": request.force, "shutdown": request.shutdown });
+    let admitted = Admitted::check("down", &args)?;
+    if !request.force && !preflight.blockers.is_empty() {
+        return Err(admitted.refuse(
+            "down_blocked",
+            format!(
+                "down of '{ws}' would interrupt {}; force it to go ahead",
+                preflight.blockers.join(" and ")
+            ),
+            json!({ "workspace": ws, "blockers": preflight.blockers }),
+        ));
+    }
+    if preflight.confirm_required && request.confirm.as_deref().map(str::trim) != Some(&ws) {
+        return Err(admitted.refuse(
+            "confirmation_required",
+            format!("type the workspace name '{ws}' to confirm down"),
+            json!({ "workspace": ws }),
+        ));
+    }
+    report(DownPhase::Confirmed, None, Value::Null);
+    report(DownPhase::Sending, None, args.clone());
+    let result = admitted.dispatch(args);
+    let trace_id = result.id.clone();
+    if !result.ok {
+        return Err(result.error.unwrap_or_else(|| CommandError {
+            code: "command_failed".to_string(),
+            message: "Command failed: down".to_string(),
+            details: None,
+            trace_id: Some(trace_id),
+        }));
+    }
+    report(
+        DownPhase::Accepted,
+        Some(&trace_id),
+        result.result.unwrap_or_default(),
+    );
+    let started = Instant::now();
+    let on_phase = |phase| report(phase, Some(&trace_id), Value::Null);
+    let phase = if admitted.state.resolved_mode == Mode::Mock {
+        down::mock_follow(on_phase)
+    } else {
+        let config = load_config().unwrap_or_default().down;
+        let socket_path = socket_path_for_ws(&ws);
+        let alive = || ping_socket(&socket_path, &ws, Duration::from_millis(500)).is_ok();
+        down::follow(
+            &socket_path,
+            started + request.timeout(&config),
+            alive,
+            on_phase,
+        )
+    };
+    if phase == DownPhase::Stopped {
+        pty::hang_up(&ws);
+    }
+    Ok(DownOutcome {
+        ws,
+        trace_id,
+        preflight,
+        phase,
+        waited_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Client-side checks a command has to pass before it leaves YX: role, law
+/// state, the shell policy for `shell.exec`, then arming.
+fn gate(
+    state: &ConnectionState,
+    identity: &Identity,
+    name: &str,
+    args: &Value,
+    needs_arming: bool,
+    session: Option<&ArmingSession>,
+) -> Option<CommandResult> {
+    let ws = &state.selected_ws;
+    if !identity::role_allows(identity.role, name) {
+        return Some(rejected(
+            name,
+            "role_denied",
+            format!(
+                "role '{}' cannot run '{name}' (requires '{}')",
+                identity.role.as_str(),
+                identity::required_role(name).as_str()
+            ),
+            json!({
+                "workspace": ws,
+                "command": name,
+                "operator_id": identity.operator_id,
+                "role": identity.role,
+            }),
+        ));
+    }
+    if let Some(law) = law::status(ws).filter(|l| l.state.is_restricted()) {
+        // armed commands go by the built-in rules alone, so a daemon catalog
+        // can't open them up while restricted; the rest use the cached one, as
+        // fetching a catalog goes through send_command
+        let catalog = if arming::requires_arming(name) {
+            builtin_catalog()
+        } else {
+            catalog::cached(ws)
+                .map(|s| s.catalog)
+                .unwrap_or_else(builtin_catalog)
+        };
+        if !catalog.is_allowed(name, law.state) {
+            return Some(rejected(
+                name,
+                "lockdown",
+                format!(
+                    "'{name}' is not allowed while workspace '{ws}' is {}",
+                    law.state.as_str()
+                ),
+                json!({
+                    "workspace": ws,
+                    "command": name,
+                    "law_state": law.state,
+                    "since_ms": law.since_ms,
+                }),
+            ));
+        }
+    }
+    if name == "shell.exec" {
+        // without a readable policy nothing is allowed
+        let policy = match load_config() {
+            Ok(config) => config.shell_policy,
+            Err(error) => {
+                return Some(rejected(
+                    name,
+                    "policy_denied",
+                    format!("shell policy unavailable, yx.toml does not load: {error:#}"),
+                    json!({
+                        "workspace": ws,
+                        "command": name,
+                        "config_error": format!("{error:#}"),
+                    }),
+                ));
+            }
+        };
+        if let Some(denial) = shell_policy::check(&policy, ws, args, session) {
+            return Some(rejected(
+                name,
+                "policy_denied",
+                denial.message(),
+                denial.details(ws),
+            ));
+        }
+    }
+    if needs_arming && !session.is_some_and(|s| s.covers(name)) {
+        return Some(rejected(
+            name,
+            "arming_required",
+            format!("'{name}' requires an active arming session for workspace '{ws}'"),
+            json!({
+                "workspace": ws,
+                "command": name,
+                "armed": session.is_some(),
+                "scope": session.map(|s| s.scope.clone()),
+            }),
+        ));
+    }
+    None
+}
+
+/// Identity YX acts as on `ws`, from `YX_OPERATOR` and `yx.toml`.
+pub fn current_identity(ws: &str) -> Identity {
+    match load_config() {
+        Ok(config) => identity::resolve(&config, ws),
+        Err(_) => identity::unconfigured(ws),
+    }
+}
+
+pub fn whoami() -> WhoAmI {
+    identity::whoami(current_identity(&resolve_ws_preference()))
+}
+
+pub fn audit_query(filter: &AuditQuery) -> Result<Vec<AuditEntry>> {
+    audit::query(filter)
+}
+
+pub fn audit_verify() -> Result<AuditVerification> {
+    audit::verify()
+}
+
+/// Arms the selected workspace for `ttl_secs` seconds, replacing any session
+/// already active.
+pub fn arm(reason: &str, scope: Vec<String>, ttl_secs: u64) -> Result<ArmingSession> {
+    let ws = resolve_ws_preference();
+    let identity = current_identity(&ws);
+    if identity.role < Role::Operator {
+        return Err(anyhow!(
+            "role '{}' cannot arm workspace '{ws}'",
+            identity.role.as_str()
+        ));
+    }
+    arming::arm(&ws, reason, scope, ttl_secs)
+}
+
+pub fn disarm(reason: DisarmReason) -> Option<ArmingSession> {
+    arming::disarm(reason)
+}
+
+pub fn arming_state() -> ArmingState {
+    arming::state()
+}
+
+/// Capability catalog of the selected workspace. Cached per workspace and
+/// re-fetched once a law-state change marks it stale, or when `refresh` is set.
+pub fn capability_catalog(refresh: bool) -> CatalogSnapshot {
+    let ws = resolve_ws_preference();
+    if !refresh {
+        if let Some(snapshot) = catalog::cached(&ws).filter(|s| !s.stale) {
+            return snapshot;
+        }
+    }
+    let result = run_command("capabilities", json!({}), false, false);
+    catalog::store(catalog::snapshot_from(&ws, result.result.as_ref()))
+}
+
+pub fn is_allowed(command: &str, state: LawState) -> bool {
+    capability_catalog(false).catalog.is_allowed(command, state)
+}
+
+/// Last law state seen for the selected workspace, if any.
+pub fn law_status() -> Option<LawStatus> {
+    law::status(&resolve_ws_preference())
+}
+
+/// Most recent law snapshots of the selected workspace, oldest first.
+pub fn law_history(limit: usize) -> Result<Vec<LawSnapshotRecord>> {
+    law_history::history(&resolve_ws_preference(), limit)
+}
+
+pub fn law_diff(from_seq: u64, to_seq: u64) -> Result<LawDiff> {
+    law_history::diff(&resolve_ws_preference(), from_seq, to_seq)
+}
+
+/// What changed in the selected workspace since `since_ts_ms`.
+pub fn law_diff_since(since_ts_ms: u64) -> Result<Option<LawDiff>> {
+    law_history::diff_since(&resolve_ws_preference(), since_ts_ms)
+}
+
+/// Pages back through the persisted events of `query.ws` (default: the
+/// selected workspace), newest first.
+pub fn events_query(query: &EventQuery) -> EventPage {
+    let ws = query.ws.clone().unwrap_or_else(resolve_ws_preference);
+    event_store::query(&ws, query)
+}
+
+/// Full-text search over persisted events and log lines. `ws:` in the query
+/// picks the workspace, otherwise the selected one is searched.
+pub fn search(request: &SearchRequest) -> SearchResults {
+    search::search(&resolve_ws_preference(), request)
+}
+
+/// Writes the stored events matching `request.query` to `request.path`.
+pub fn events_export(request: &ExportRequest) -> Result<ExportSummary> {
+    let ws = request
+        .query
+        .ws
+        .clone()
+        .unwrap_or_else(resolve_ws_preference);
+    export::export(&ws, request)
+}
+
+/// `events_export` for the desktop app: `request.path` is only a file name,
+/// written to `exports` in the workspace's YX data directory.
+pub fn events_export_to_workspace(request: &ExportRequest) -> Result<ExportSummary> {
+    let ws = request
+        .query
+        .ws
+        .clone()
+        .unwrap_or_else(resolve_ws_preference);
+    let path = export::exports_path(&ws, &request.path)?;
+    let request = ExportRequest {
+        path: path.to_string_lossy().into_owned(),
+        ..request.clone()
+    };
+    export::export(&ws, &request)
+}
+
+/// Rolling 1m/5m/1h rates per topic and severity, event lag and silent
+/// topics for `ws`, or the selected workspace.
+pub fn events_stats(ws: Option<&str>) -> EventStats {
+    let ws = ws.map_or_else(resolve_ws_preference, str::to_string);
+    stats::stats(&ws)
+}
+
+/// Timeline of `trace_id` in the selected workspace: the command request and
+/// response plus every stored event or log line correlated with it.
+pub fn trace(trace_id: &str) -> Trace {
+    trace::trace(&resolve_ws_preference(), trace_id)
+}
+
+/// Calls `listener` on every law-state transition, in any workspace.
+pub fn on_law_transition<F>(listener: F)
+where
+    F: Fn(&LawTransition) + Send + 'static,
+{
+    law::subscribe(Box::new(listener));
+}
+
+/// Alerts raised by the `[[alerts.rules]]` of yx.toml, newest first.
+pub fn alerts(include_resolved: bool) -> Vec<Alert> {
+    alerts::list(include_resolved)
+}
+
+pub fn alert_ack(id: &str) -> Result<Alert> {
+    alerts::acknowledge(id)
+}
+
+pub fn alert_resolve(id: &str) -> Result<Alert> {
+    alerts::resolve(id)
+}
+
+/// Calls `listener` when an alert is raised, absorbs another match, or is
+/// acknowledged or resolved.
+pub fn on_alert<F>(listener: F)
+where
+    F: Fn(&Alert) + Send + 'static,
+{
+    alerts::subscribe(Box::new(listener));
+}
+
+/// Runs a command; a failed result becomes its error.
+fn provider_call(name: &str, args: Value) -> Result<CommandResult, CommandError> {
+    let result = run_command(name, args, false, false);
+    if result.ok {
+        return Ok(result);
+    }
+    Err(result.error.unwrap_or_else(|| CommandError {
+        code: "command_failed".to_string(),
+        message: format!("Command failed: {name}"),
+        details: None,
+        trace_id: Some(result.id),
+    }))
+}
+
+/// Providers of the selected workspace; refreshes what YX knows about them.
+pub fn providers_list() -> Result<ProviderList, CommandError> {
+    let ws = resolve_ws_preference();
+    let result = provider_call("providers.list", json!({}))?;
+    let items = providers::items_from(result.result.as_ref().unwrap_or(&Value::Null));
+    providers::replace(&ws, &items);
+    Ok(ProviderList { items })
+}
+
+pub fn providers_status() -> Result<ProviderStatus, CommandError> {
+    let ws = resolve_ws_preference();
+    let result = provider_call("providers.status", json!({}))?;
+    let active = result
+        .result
+        .as_ref()
+        .and_then(|v| providers::active_from(&ws, v));
+    providers::set_active(&ws, active.as_ref());
+    Ok(ProviderStatus { active })
+}
+
+pub fn providers_discover(
+    endpoint: Option<&str>,
+    model: Option<&str>,
+) -> Result<ProviderList, CommandError> {
+    let ws = resolve_ws_preference();
+    let result = provider_call(
+        "providers.discover",
+        json!({ "endpoint": endpoint, "model": model }),
+    )?;
+    let items = providers::items_from(result.result.as_ref().unwrap_or(&Value::Null));
+    providers::merge(&ws, &items);
+    Ok(ProviderList { items })
+}
+
+/// Validates a lifecycle command against the provider's last known state,
+/// refreshing the listing once if the provider is unknown, then sends it.
+fn provider_transition(
+    name: &str,
+    id: &str,
+    args: Value,
+) -> Result<ProviderTransition, CommandError> {
+    let ws = resolve_ws_preference();
+    if providers::get(&ws, id).is_none() {
+        let _ = providers_list();
+    }
+    let from = providers::check(&ws, name, id)?;
+    let to = providers::target(name).unwrap_or(ProviderState::Error);
+    let result = provider_call(name, args)?;
+    let provider = result.result.as_ref().and_then(|v| {
+        v.get("provider")
+            .and_then(|p| serde_json::from_value::<Provider>(p.clone()).ok())
+    });
+    providers::applied(&ws, id, to, provider.as_ref());
+    Ok(ProviderTransition {
+        command: name.to_string(),
+        id: id.to_string(),
+        from,
+        to,
+        provider,
+        trace_id: result.id,
+    })
+}
+
+pub fn providers_pair(
+    id: &str,
+    endpoint: &str,
+    model: &str,
+) -> Result<ProviderTransition, CommandError> {
+    provider_transition(
+        "providers.pair",
+        id,
+        json!({ "id": id, "endpoint": endpoint, "model": model }),
+    )
+}
+
+/// Attaches a paired or detached provider, detaching the active one.
+pub fn providers_attach(id: &str, model: Option<&str>) -> Result<ProviderTransition, CommandError> {
+    provider_transition("providers.attach", id, json!({ "id": id, "model": model }))
+}
+
+/// Detaches the active provider.
+pub fn providers_detach() -> Result<ProviderTransition, CommandError> {
+    let ws = resolve_ws_preference();
+    let active = match providers::attached(&ws) {
+        Some(active) => Some(active),
+        None => providers_status()?.active,
+    };
+    let Some(active) = active else {
+        return Err(CommandError {
+            code: "invalid_transition".to_string(),
+            message: format!("no provider is attached in workspace '{ws}'"),
+            details: Some(json!({ "workspace": ws, "command": "providers.detach" })),
+            trace_id: Some(next_trace_id()),
+        });
+    };
+    provider_transition("providers.detach", &active.id, json!({}))
+}
+
+pub fn providers_revoke(id: &str) -> Result<ProviderTransition, CommandError> {
+    provider_transition("providers.revoke", id, json!({ "id": id }))
+}
+
+/// `cached` in place of `error` when the daemon is unreachable.
+fn chat_offline<T>(error: CommandError, cached: Option<T>) -> Result<T, CommandError> {
+    match cached {
+        Some(cached) if error.code == "sock_unavailable" => Ok(cached),
+        _ => Err(error),
+    }
+}
+
+/// Chat sessions of the selected workspace. Listings are cached locally and
+/// served from there, with `cached_ms` set, when the daemon is unreachable.
+pub fn chat_sessions() -> Result<ChatSessionList, CommandError> {
+    let ws = resolve_ws_preference();
+    let result = match provider_call("chat.sessions.list", json!({})) {
+        Ok(result) => result,
+        Err(e) => return chat_offline(e, chat::cached_sessions(&ws)),
+    };
+    let value = result.result.unwrap_or(Value::Null);
+    if chat::is_mock(&value) {
+        return Ok(chat::cached_sessions(&ws).unwrap_or_default());
+    }
+    let mut list = chat::sessions_from(&value);
+    // the cache only serves offline reads; the daemon's answer stands without it
+    let _ = chat::cache_sessions(&ws, &mut list, now_ms());
+    Ok(list)
+}
+
+/// History of `session_id`, cached like `chat_sessions`. Messages keep the
+/// trace id, provider and model of the `chat_send` that produced them.
+pub fn chat_history(session_id: &str) -> Result<ChatHistory, CommandError> {
+    let ws = resolve_ws_preference();
+    let result = match provider_call("chat.history", json!({ "session_id": session_id })) {
+        Ok(result) => result,
+        Err(e) => return chat_offline(e, chat::cached_history(&ws, session_id)),
+    };
+    let value = result.result.unwrap_or(Value::Null);
+    if chat::is_mock(&value) {
+        return Ok(
+            chat::cached_history(&ws, session_id).unwrap_or_else(|| ChatHistory {
+                session: ChatSession::new(session_id),
+                items: Vec::new(),
+                cached_ms: None,
+            }),
+        );
+    }
+    let session = chat::session_from(&value, session_id);
+    let messages = chat::messages_from(&value);
+    let fetched = ChatHistory {
+        session: session.clone(),
+        items: messages.clone(),
+        cached_ms: None,
+    };
+    Ok(chat::cache_history(&ws, session, messages, now_ms()).unwrap_or(fetched))
+}
+
+pub fn chat_session_new(title: Option<&str>) -> Result<ChatSession, CommandError> {
+    let ws = resolve_ws_preference();
+    let result = provider_call("chat.session.new", json!({ "title": title }))?;
+    let value = result.result.unwrap_or(Value::Null);
+    // mock mode keeps no sessions; make one up so the cache can hold it
+    let created = if chat::is_mock(&value) {
+        Some(ChatSession::new(&format!("mock-{}", result.id)))
+    } else {
+        chat::created_from(&value)
+    };
+    let mut session = match created {
+        Some(session) => session,
+        None => {
+            return Err(CommandError {
+                code: "invalid_response".to_string(),
+                message: "chat.session.new returned no session id".to_string(),
+                details: Some(value),
+                trace_id: Some(result.id),
+            })
+        }
+    };
+    session.title = session.title.or_else(|| title.map(str::to_string));
+    session.created_ms = session.created_ms.or(Some(result.ts_ms));
+    let _ = chat::cache_created(&ws, &session, now_ms());
+    Ok(session)
+}
+
+/// Builds the context `request` would attach to a chat message, to preview
+/// it; `chat_send` builds it again when sending.
+pub fn chat_context(request: &ChatContextRequest) -> Result<ChatContext, CommandError> {
+    chat_context::build(&resolve_ws_preference(), request)
+}
+
+/// Sends `text` to `session_id` and records it and the reply in the cache,
+/// tagged with the command's trace id and the attached provider and model.
+/// `context` items go along as structured `context` blocks within their byte
+/// budget; the history keeps references to them, not the data.
+pub fn chat_send(
+    session_id: &str,
+    text: &str,
+    context: Option<&ChatContextRequest>,
+) -> Result<ChatExchange, CommandError> {
+    let ws = resolve_ws_preference();
+    let context = context
+        .filter(|c| !c.items.is_empty())
+        .map(|c| chat_context::build(&ws, c))
+        .transpose()?;
+    let started = Instant::now();
+    let result = provider_call(
+        "chat.send",
+        json!({
+            "session_id": session_id,
+            "text": text,
+            "context": context.as_ref().map(|c| &c.blocks),
+        }),
+    )?;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let attached = providers::attached(&ws);
+    let tag = |mut message: ChatMessage, ts_ms: u64| {
+        message.ts_ms = message.ts_ms.or(Some(ts_ms));
+        message.trace_id = message.trace_id.or_else(|| Some(result.id.clone()));
+        message.provider = message
+            .provider
+            .or_else(|| attached.as_ref().map(|p| p.id.clone()));
+        message.model = message
+            .model
+            .or_else(|| attached.as_ref().and_then(|p| p.model.clone()));
+        message
+    };
+    let mut sent = tag(ChatMessage::new(ChatRole::User, text), result.ts_ms);
+    sent.context = context.map(|c| c.references()).unwrap_or_default();
+    let mut reply = result
+        .result
+        .as_ref()
+        .and_then(chat::reply_from)
+        .map(|reply| tag(reply, now_ms()));
+    let reported = result
+        .result
+        .as_ref()
+        .and_then(|r| r.get("usage"))
+        .and_then(|u| serde_json::from_value::<ChatUsage>(u.clone()).ok())
+        .or_else(|| reply.as_ref().and_then(|r| r.usage.clone()));
+    let usage = usage::usage_of(reported, latency_ms);
+    let (provider, model) = match &reply {
+        Some(reply) => (reply.provider.clone(), reply.model.clone()),
+        None => (sent.provider.clone(), sent.model.clone()),
+    };
+    // a failed journal write does not fail the exchange that was made
+    let _ = usage::record(UsageRecord {
+        ts_ms: now_ms(),
+        ws: ws.clone(),
+        session_id: session_id.to_string(),
+        trace_id: result.id.clone(),
+        provider,
+        model,
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        latency_ms,
+    });
+    if let Some(reply) = reply.as_mut() {
+        reply.usage = Some(usage);
+    }
+    let _ = chat::record_exchange(&ws, session_id, sent.clone(), reply.clone(), now_ms());
+    Ok(ChatExchange {
+        session_id: session_id.to_string(),
+        trace_id: result.id,
+        sent,
+        reply,
+    })
+}
+
+/// Tokens, latency and cost of `chat.send` calls grouped as `query` asks,
+/// priced with the `[usage]` tables of the current config.
+pub fn usage_report(query: &UsageQuery) -> UsageReport {
+    let config = load_config().unwrap_or_default().usage;
+    usage::report(&resolve_ws_preference(), query, &config)
+}
+
+/// Full-text search over the cached chat histories of the selected
+/// workspace, newest first.
+pub fn chat_search(request: &ChatSearchRequest) -> ChatSearchResults {
+    chat::search(&resolve_ws_preference(), request)
+}
+
+/// `session_id` as a Markdown or JSON document with its metadata: provider,
+/// model, timestamps and the trace id of every message. Fetches the history
+/// first, falling back to the cache when the daemon is unreachable.
+pub fn chat_export(session_id: &str, format: ChatExportFormat) -> Result<String> {
+    let history = chat_history(session_id).map_err(|e| anyhow!(e))?;
+    chat::export(&resolve_ws_preference(), &history, format, now_ms())
+}
+
+/// Starts a guided pairing in the selected workspace and runs discovery.
+/// Sessions are persisted after each step, so they survive restarts; see
+/// `pairing_sessions`.
+pub fn pairing_start(
+    endpoint_hint: Option<&str>,
+    model_hint: Option<&str>,
+) -> Result<PairingSession, PairingError> {
+    pairing::start(&resolve_ws_preference(), endpoint_hint, model_hint)
+}
+
+/// Pairing sessions of the selected workspace, newest first.
+pub fn pairing_sessions(include_closed: bool) -> Vec<PairingSession> {
+    pairing::list(&resolve_ws_preference(), include_closed)
+}
+
+pub fn pairing_session(id: &str) -> Result<PairingSession, PairingError> {
+    pairing::get(&resolve_ws_preference(), id)
+}
+
+pub fn pairing_rediscover(id: &str) -> Result<PairingSession, PairingError> {
+    pairing::rediscover(&resolve_ws_preference(), id)
+}
+
+pub fn pairing_pick(
+    id: &str,
+    candidate: &str,
+    model: Option<&str>,
+) -> Result<PairingSession, PairingError> {
+    pairing::pick(&resolve_ws_preference(), id, candidate, model)
+}
+
+pub fn pairing_pair(id: &str) -> Result<PairingSession, PairingError> {
+    pairing::pair(&resolve_ws_preference(), id)
+}
+
+pub fn pairing_verify(id: &str) -> Result<PairingSession, PairingError> {
+    pairing::verify(&resolve_ws_preference(), id)
+}
+
+pub fn pairing_attach(id: &str) -> Result<PairingSession, PairingError> {
+    pairing::attach(&resolve_ws_preference(), id)
+}
+
+pub fn pairing_finish(id: &str) -> Result<PairingSession, PairingError> {
+    pairing::finish(&resolve_ws_preference(), id)
+}
+
+pub fn pairing_cancel(id: &str) -> Result<PairingSession, PairingError> {
+    pairing::cancel(&resolve_ws_preference(), id)
+}
+
+/// One health round for the selected workspace: `providers.status`, then a
+/// probe of every attached or paired provider. Status changes are raised as
+/// `provider.health_changed` events.
+pub fn providers_probe() -> Vec<ProviderHealth> {
+    let ws = resolve_ws_preference();
+    let config = load_config().unwrap_or_default();
+    if providers::is_empty(&ws) {
+        let _ = providers_list();
+    }
+    let started = Instant::now();
+    let status = providers_status();
+    let status = provider_health::StatusCheck {
+        latency_ms: started.elapsed().as_millis() as u64,
+        error: status.as_ref().err().map(|e| e.message.clone()),
+        active: status.ok().and_then(|s| s.active),
+    };
+    let round = providers::monitored(&ws)
+        .iter()
+        .filter_map(|p| provider_health::probe(p, &status, &config.provider_health))
+        .collect();
+    let redactor = redact::for_config(&config.redaction);
+    for mut ev in provider_health::record(&ws, round) {
+        redactor.event(&mut ev);
+        observe_event(&ws, &ev, &config);
+        provider_health::notify(&ev);
+    }
+    provider_health::health(&ws)
+}
+
+/// Availability, latency and recent failures per provider of `ws`, or the
+/// selected workspace, from the probes recorded so far.
+pub fn providers_health(ws: Option<&str>) -> Vec<ProviderHealth> {
+    let ws = ws.map_or_else(resolve_ws_preference, str::to_string);
+    provider_health::health(&ws)
+}
+
+/// Calls `listener` with every `provider.health_changed` event.
+pub fn on_provider_health<F>(listener: F)
+where
+    F: Fn(&ProtocolEvent) + Send + 'static,
+{
+    provider_health::subscribe(Box::new(listener));
+}
+
+/// Runs `providers_probe` every `[provider_health] interval_secs`.
+pub fn start_provider_health_monitor() {
+    std::thread::spawn(|| loop {
+        let config = load_config().unwrap_or_default().provider_health;
+        if config.enabled {
+            providers_probe();
+        }
+        std::thread::sleep(Duration::from_secs(config.interval_secs.max(1)));
+    });
+}
+
+fn rejected(name: &str, code: &str, message: String, details: Value) -> CommandResult {
+    let trace_id = next_trace_id();
+    CommandResult {
+        id: trace_id.clone(),
+        ts_ms: now_ms(),
+        name: name.to_string(),
+        ok: false,
+        result: None,
+        error: Some(CommandError {
+            code: code.to_string(),
+            message,
+            details: Some(details),
+            trace_id: Some(trace_id),
+        }),
+    }
+}
+
+fn dispatch_command(
+    state: &ConnectionState,
+    identity: &Identity,
+    name: &str,
+    args: Value,
+    arming: Option<&ArmingSession>,
+) -> CommandResult {
+    let trace_id = next_trace_id();
     let ts_ms = now_ms();
 
     if state.resolved_mode == Mode::Mock {
@@ -278,9 +1741,10 @@ pub fn send_command(name: &str, args: Value, arming: bool) -> CommandResult {
         };
     }
 
+    let _tracked = in_flight::track(&state.selected_ws, &trace_id, name);
     match send_command_real(
         &socket_path_for_ws(&state.selected_ws),
-        &state.selected_ws,
+        identity,
         name,
         args,
         arming,
@@ -361,23 +1825,27 @@ fn mock_response(name: &str, args: Value) -> Value {
     }
 }
 
+/// Connects to the control socket and writes the request envelope; the
+/// answer is read from the returned reader.
 #[cfg(unix)]
-fn send_command_real(
+fn open_request(
     sock: &str,
-    ws_id: &str,
+    identity: &Identity,
     name: &str,
     args: Value,
-    arming: bool,
+    arming: Option<&ArmingSession>,
     trace_id: &str,
     timeout: Duration,
-) -> Result<Value> {
+) -> Result<BufReader<UnixStream>> {
     let request = CommandRequest {
         protocol_version: "v1".to_string(),
         trace_id: trace_id.to_string(),
         ts_ms: now_ms(),
         name: name.to_string(),
         args: args.clone(),
-        arming,
+        arming: arming.is_some(),
+        role: Some(identity.role),
+        operator_id: Some(identity.operator_id.clone()),
     };
 
     let req = map_request(&request.name, request.args.clone())?;
@@ -386,17 +1854,43 @@ fn send_command_real(
     stream.set_read_timeout(Some(timeout)).ok();
     stream.set_write_timeout(Some(timeout)).ok();
 
-    let envelope = if arming {
-        json!({ "v": RPC_PROTOCOL_VERSION, "request": req, "ws_id": ws_id, "arming": true, "role": "operator" })
-    } else {
-        json!({ "v": RPC_PROTOCOL_VERSION, "request": req, "ws_id": ws_id, "arming": false })
-    };
+    let mut envelope = json!({
+        "v": RPC_PROTOCOL_VERSION,
+        "request": req,
+        "trace_id": request.trace_id,
+        "ws_id": identity.ws,
+        "arming": request.arming,
+        "role": request.role,
+        "operator": {
+            "id": request.operator_id,
+            "display_name": identity.display_name,
+        },
+    });
+    if let Some(session) = arming {
+        envelope["arming_session"] = json!({
+            "id": session.id,
+            "reason": session.reason,
+            "expires_at_ms": session.expires_at_ms,
+        });
+    }
     let line = serde_json::to_string(&envelope)?;
     stream.write_all(line.as_bytes())?;
     stream.write_all(b"\n")?;
     stream.flush()?;
+    Ok(BufReader::new(stream))
+}
 
-    let mut reader = BufReader::new(stream);
+#[cfg(unix)]
+fn send_command_real(
+    sock: &str,
+    identity: &Identity,
+    name: &str,
+    args: Value,
+    arming: Option<&ArmingSession>,
+    trace_id: &str,
+    timeout: Duration,
+) -> Result<Value> {
+    let mut reader = open_request(sock, identity, name, args, arming, trace_id, timeout)?;
     let mut seen_lines = 0u8;
     while seen_lines < 8 {
         let mut resp = String::new();
@@ -436,9 +1930,10 @@ fn send_command_real(
 #[cfg(not(unix))]
 fn send_command_real(
     _sock: &str,
+    _identity: &Identity,
     _name: &str,
     _args: Value,
-    _arming: bool,
+    _arming: Option<&ArmingSession>,
     _trace_id: &str,
     _timeout: Duration,
 ) -> Result<Value> {
@@ -456,6 +1951,10 @@ fn map_request(name: &str, args: Value) -> Result<Value> {
 
         "ping" => json!({ "Ping": {} }),
 
+        "capabilities" => json!({ "Capabilities": {} }),
+
+        "law.snapshot" => json!({ "LawSnapshot": {} }),
+
         "providers.discover" => {
             let endpoint = args
                 .get("endpoint")
@@ -473,21 +1972,15 @@ fn map_request(name: &str, args: Value) -> Result<Value> {
         "providers.detach" => json!({ "ProvidersDetach": {} }),
 
         "providers.pair" => {
-            let id = args
-                .get("id")
-                .and_then(Value::as_str)
-                .unwrap_or_default()
-                .to_string();
-            let endpoint = args
-                .get("endpoint")
-                .and_then(Value::as_str)
-                .unwrap_or_default()
-                .to_string();
-            let model = args
-                .get("model")
-                .and_then(Value::as_str)
-                .unwrap_or_default()
-                .to_string();
+            let field = |key: &str| {
+                args.get(key)
+                    .and_then(Value::as_str)
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow!("providers.pair requires a non-empty '{key}'"))
+            };
+            let (id, endpoint, model) = (field("id")?, field("endpoint")?, field("model")?);
             json!({ "ProvidersPair": { "id": id, "endpoint": endpoint, "model": model } })
         }
 
@@ -541,7 +2034,12 @@ fn map_request(name: &str, args: Value) -> Result<Value> {
                 .unwrap_or_default()
                 .to_string();
             let stream = args.get("stream").and_then(Value::as_bool).unwrap_or(true);
-            json!({ "ChatSend": { "session_id": session_id, "text": text, "stream": stream } })
+            let mut send = json!({ "session_id": session_id, "text": text, "stream": stream });
+            // only sent when attached, for daemons that predate it
+            if let Some(context) = args.get("context").filter(|c| !c.is_null()) {
+                send["context"] = context.clone();
+            }
+            json!({ "ChatSend": send })
         }
 
         "shell.exec" => {
@@ -563,7 +2061,45 @@ fn map_request(name: &str, args: Value) -> Result<Value> {
                 .get("cwd")
                 .and_then(Value::as_str)
                 .map(|s| s.to_string());
-            json!({ "ShellExec": { "cmd": cmd, "args": cmd_args, "cwd": cwd } })
+            let mut exec = json!({ "cmd": cmd, "args": cmd_args, "cwd": cwd });
+            // only sent when given, for daemons that predate streaming exec
+            for key in ["env", "stdin", "timeout_ms", "max_output_bytes", "stream"] {
+                if let Some(value) = args.get(key).filter(|v| !v.is_null()) {
+                    exec[key] = value.clone();
+                }
+            }
+            json!({ "ShellExec": exec })
+        }
+
+        "shell.pty.open" => {
+            let mut open = json!({
+                "cmd": args.get("cmd").cloned().unwrap_or(Value::Null),
+                "args": args.get("args").cloned().unwrap_or_else(|| json!([])),
+                "cwd": args.get("cwd").cloned().unwrap_or(Value::Null),
+            });
+            for key in ["env", "cols", "rows"] {
+                if let Some(value) = args.get(key).filter(|v| !v.is_null()) {
+                    open[key] = value.clone();
+                }
+            }
+            json!({ "ShellPtyOpen": open })
+        }
+
+        "shell.pty.write" => {
+            let pty_id = args
+                .get("pty_id")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let data = args.get("data").and_then(Value::as_str).unwrap_or_default();
+            json!({ "ShellPtyWrite": { "pty_id": pty_id, "data": data } })
+        }
+
+        "shell.pty.close" => {
+            let pty_id = args
+                .get("pty_id")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            json!({ "ShellPtyClose": { "pty_id": pty_id } })
         }
 
         "down" => {
@@ -581,6 +2117,32 @@ fn map_request(name: &str, args: Value) -> Result<Value> {
     Ok(v)
 }
 
+/// Text of a wire field; numbers and booleans are taken as written.
+#[cfg(unix)]
+fn wire_str(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Counter or timestamp of a wire field, also when sent as a float or a
+/// string.
+#[cfg(unix)]
+fn wire_u64(value: Option<&Value>) -> Option<u64> {
+    match value? {
+        Value::Number(n) => n
+            .as_u64()
+            .or_else(|| n.as_f64().filter(|f| *f >= 0.0).map(|f| f as u64)),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Reads an event off the stream field by field: a field of the wrong type
+/// is dropped or falls back, never the whole event.
 #[cfg(unix)]
 fn map_event(value: &Value) -> Option<ProtocolEvent> {
     let kind = value.get("type").and_then(Value::as_str)?;
@@ -588,35 +2150,44 @@ fn map_event(value: &Value) -> Option<ProtocolEvent> {
         return None;
     }
     let event = value.get("event")?;
-    let topic = event
-        .get("type")
-        .and_then(Value::as_str)
-        .unwrap_or("unknown");
-    let severity = event.get("level").and_then(Value::as_str).unwrap_or("info");
-    let ts_ms = event
-        .get("ts")
-        .and_then(Value::as_u64)
-        .unwrap_or_else(now_ms);
-    let payload = json!({
-        "event_id": event.get("event_id"),
-        "ws": event.get("ws"),
-        "seq": event.get("seq"),
-        "msg": event.get("msg"),
-        "data": event.get("data"),
-        "compliance": event.get("compliance"),
-    });
+    let field = |name: &str| event.get(name).filter(|v| !v.is_null());
     Some(ProtocolEvent {
-        topic: topic.to_string(),
-        severity: severity.to_string(),
-        ts_ms,
-        payload,
-        trace_id: event
-            .get("event_id")
-            .and_then(Value::as_str)
-            .map(|s| s.to_string()),
+        topic: wire_str(field("type"))
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| "unknown".to_string()),
+        severity: wire_str(field("level").or_else(|| field("severity")))
+            .map(|s| yx_protocol::Severity::parse(&s))
+            .unwrap_or_default(),
+        ts_ms: wire_u64(field("ts")).unwrap_or_else(now_ms),
+        event_id: wire_str(field("event_id")),
+        ws: wire_str(field("ws")),
+        seq: wire_u64(field("seq")),
+        msg: wire_str(field("msg")),
+        data: field("data").cloned().unwrap_or_default(),
+        compliance: field("compliance").cloned(),
+        // the trace of the command that caused the event, not its own id
+        trace_id: wire_str(field("trace_id"))
+            .or_else(|| wire_str(field("causation_id")))
+            .or_else(|| wire_str(field("correlation_id"))),
     })
 }
 
+/// Feeds an event, from the daemon or raised by YX, to the law tracker,
+/// alert rules, rate stats and the event store.
+fn observe_event(ws: &str, ev: &ProtocolEvent, config: &YxConfig) {
+    if let Some(law_state) = law::state_from_event(ev) {
+        law::observe(ws, law_state, &ev.topic);
+    }
+    alerts::observe(ws, ev, &config.alerts);
+    stats::record(ws, ev);
+    if config.events.enabled {
+        if let Ok(stored) = event_store::append(ws, ev, &config.events) {
+            search::index_event(&stored);
+            trace::index_event(&stored);
+        }
+    }
+}
+
 #[cfg(unix)]
 pub fn start_event_stream<F>(on_event: F) -> Result<()>
 where
@@ -642,7 +2213,16 @@ where
                 std::thread::sleep(Duration::from_millis(1200));
                 continue;
             }
-            let envelope = json!({ "v": RPC_PROTOCOL_VERSION, "request": req.unwrap(), "arming": false });
+            let config = load_config().unwrap_or_default();
+            let identity = identity::resolve(&config, &selected_ws);
+            let redactor = redact::for_config(&config.redaction);
+            let envelope = json!({
+                "v": RPC_PROTOCOL_VERSION,
+                "request": req.unwrap(),
+                "arming": false,
+                "role": identity.role,
+                "operator": { "id": identity.operator_id, "display_name": identity.display_name },
+            });
             if let Ok(line) = serde_json::to_string(&envelope) {
                 let _ = stream.write_all(line.as_bytes());
                 let _ = stream.write_all(b"\n");
@@ -665,7 +2245,9 @@ where
                     Ok(v) => v,
                     Err(_) => continue,
                 };
-                if let Some(ev) = map_event(&parsed) {
+                if let Some(mut ev) = map_event(&parsed) {
+                    redactor.event(&mut ev);
+                    observe_event(&selected_ws, &ev, &config);
                     on_event(ev);
                 }
             }
@@ -682,3 +2264,21 @@ where
 {
     Err(anyhow!("real mode requires unix sockets"))
 }
+
+/// Like `start_event_stream`, but coalesces events into time-windowed batches
+/// through a bounded queue set up by `[stream]` in yx.toml. Drops are
+/// reported in the batch as a `stream.overflow` event. Provider health
+/// events join the same queue.
+pub fn start_batched_event_stream<F>(on_batch: F) -> Result<()>
+where
+    F: Fn(Vec<ProtocolEvent>) + Send + 'static,
+{
+    let config = load_config().unwrap_or_default().stream;
+    let queue = Arc::new(batch::BatchQueue::new(config));
+    let producer = Arc::clone(&queue);
+    start_event_stream(move |event| producer.push(event))?;
+    let health = Arc::clone(&queue);
+    provider_health::subscribe(Box::new(move |event| health.push(event.clone())));
+    std::thread::spawn(move || queue.run(on_batch));
+    Ok(())
+}
@@ -0,0 +1,477 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use yx_protocol::{CommandError, Provider, ProviderState};
+
+use crate::config::load_config;
+use crate::{provider_health, providers, redact};
+
+/// Finished and cancelled sessions kept per workspace.
+const CLOSED_SESSION_LIMIT: usize = 20;
+
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+static SESSION_SEQ: AtomicU64 = AtomicU64::new(1);
+
+/// The step a pairing session is waiting for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PairingStep {
+    Discover,
+    Pick,
+    Pair,
+    Verify,
+    /// Verified; attaching is optional, `finish` closes without it.
+    Attach,
+    Done,
+    Cancelled,
+}
+
+impl PairingStep {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PairingStep::Discover => "discover",
+            PairingStep::Pick => "pick",
+            PairingStep::Pair => "pair",
+            PairingStep::Verify => "verify",
+            PairingStep::Attach => "attach",
+            PairingStep::Done => "done",
+            PairingStep::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(self, PairingStep::Done | PairingStep::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum PairingError {
+    UnknownSession {
+        session: String,
+    },
+    /// The session is at another step; `expected` is where it stands.
+    OutOfOrder {
+        expected: PairingStep,
+        attempted: PairingStep,
+    },
+    NoCandidates,
+    UnknownCandidate {
+        candidate: String,
+    },
+    /// A required field could not be filled from the pick.
+    MissingField {
+        field: String,
+    },
+    UnknownModel {
+        model: String,
+        models: Vec<String>,
+    },
+    VerifyFailed {
+        message: String,
+    },
+    /// The daemon, or YX's own checks, refused a step's command.
+    Command {
+        step: PairingStep,
+        error: CommandError,
+    },
+    Storage {
+        message: String,
+    },
+}
+
+impl std::fmt::Display for PairingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PairingError::UnknownSession { session } => {
+                write!(f, "unknown pairing session: {session}")
+            }
+            PairingError::OutOfOrder {
+                expected,
+                attempted,
+            } => write!(
+                f,
+                "'{}' is out of order: the session is at '{}'",
+                attempted.as_str(),
+                expected.as_str()
+            ),
+            PairingError::NoCandidates => f.write_str("discovery found no candidates"),
+            PairingError::UnknownCandidate { candidate } => {
+                write!(f, "unknown candidate: {candidate}")
+            }
+            PairingError::MissingField { field } => write!(f, "missing {field}"),
+            PairingError::UnknownModel { model, models } => write!(
+                f,
+                "model '{model}' is not offered (available: {})",
+                models.join(", ")
+            ),
+            PairingError::VerifyFailed { message } => write!(f, "verification failed: {message}"),
+            PairingError::Command { step, error } => {
+                write!(f, "{} failed: {error}", step.as_str())
+            }
+            PairingError::Storage { message } => write!(f, "pairing storage: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for PairingError {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairingVerification {
+    pub ts_ms: u64,
+    /// `http` for a test request, `tcp`/`unix` for a bare connect.
+    pub method: String,
+    pub latency_ms: u64,
+    /// What answered, e.g. the HTTP status line.
+    pub detail: String,
+}
+
+/// A resumable pairing run, persisted per workspace after every step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairingSession {
+    pub id: String,
+    pub ws: String,
+    pub step: PairingStep,
+    pub created_ms: u64,
+    pub updated_ms: u64,
+    /// Hints passed to `providers.discover`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint_hint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_hint: Option<String>,
+    #[serde(default)]
+    pub candidates: Vec<Provider>,
+    /// The picked candidate, with `model` set to the chosen model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub picked: Option<Provider>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<PairingVerification>,
+    #[serde(default)]
+    pub attached: bool,
+    /// Error of the last failed step; the step can be retried.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<PairingError>,
+}
+
+fn sessions_path(ws: &str) -> PathBuf {
+    PathBuf::from(crate::ws_data_dir(ws)).join("pairing.json")
+}
+
+fn read_sessions(ws: &str) -> BTreeMap<String, PairingSession> {
+    fs::read_to_string(sessions_path(ws))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn storage_error(error: impl std::fmt::Display) -> PairingError {
+    PairingError::Storage {
+        message: error.to_string(),
+    }
+}
+
+fn save(session: &PairingSession) -> Result<(), PairingError> {
+    let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut sessions = read_sessions(&session.ws);
+    sessions.insert(session.id.clone(), session.clone());
+    let closed: Vec<(u64, String)> = sessions
+        .values()
+        .filter(|s| s.step.is_closed())
+        .map(|s| (s.updated_ms, s.id.clone()))
+        .collect();
+    if closed.len() > CLOSED_SESSION_LIMIT {
+        let mut closed = closed;
+        closed.sort();
+        for (_, id) in &closed[..closed.len() - CLOSED_SESSION_LIMIT] {
+            sessions.remove(id);
+        }
+    }
+    let path = sessions_path(&session.ws);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(storage_error)?;
+    }
+    let sessions = serde_json::to_value(&sessions).map_err(storage_error)?;
+    let content = serde_json::to_string_pretty(&redact::current().redacted(&sessions))
+        .map_err(storage_error)?;
+    fs::write(&path, content).map_err(storage_error)
+}
+
+pub(crate) fn get(ws: &str, id: &str) -> Result<PairingSession, PairingError> {
+    read_sessions(ws)
+        .remove(id)
+        .ok_or_else(|| PairingError::UnknownSession {
+            session: id.to_string(),
+        })
+}
+
+/// Newest first.
+pub(crate) fn list(ws: &str, include_closed: bool) -> Vec<PairingSession> {
+    let mut sessions: Vec<PairingSession> = read_sessions(ws)
+        .into_values()
+        .filter(|s| include_closed || !s.step.is_closed())
+        .collect();
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_ms));
+    sessions
+}
+
+/// Loads session `id` and checks it is at one of `allowed`.
+fn at_step(
+    ws: &str,
+    id: &str,
+    attempted: PairingStep,
+    allowed: &[PairingStep],
+) -> Result<PairingSession, PairingError> {
+    let session = get(ws, id)?;
+    if !allowed.contains(&session.step) {
+        return Err(PairingError::OutOfOrder {
+            expected: session.step,
+            attempted,
+        });
+    }
+    Ok(session)
+}
+
+/// Runs `step` on `session`: on success the session moves on and is saved;
+/// on failure the error is recorded on it so the step can be retried.
+fn run_step(
+    mut session: PairingSession,
+    step: impl FnOnce(&mut PairingSession) -> Result<(), PairingError>,
+) -> Result<PairingSession, PairingError> {
+    let outcome = step(&mut session);
+    session.updated_ms = crate::now_ms();
+    session.last_error = outcome.as_ref().err().cloned();
+    save(&session)?;
+    outcome.map(|()| session)
+}
+
+fn discover_step(session: &mut PairingSession) -> Result<(), PairingError> {
+    let list = crate::providers_discover(
+        session.endpoint_hint.as_deref(),
+        session.model_hint.as_deref(),
+    )
+    .map_err(|error| PairingError::Command {
+        step: PairingStep::Discover,
+        error,
+    })?;
+    let candidates: Vec<Provider> = list
+        .items
+        .into_iter()
+        .filter(|p| p.endpoint.is_some())
+        .collect();
+    if candidates.is_empty() {
+        return Err(PairingError::NoCandidates);
+    }
+    session.candidates = candidates;
+    session.picked = None;
+    session.step = PairingStep::Pick;
+    Ok(())
+}
+
+/// Creates a session and runs discovery; a failed discovery leaves the
+/// session at `discover` for `rediscover`.
+pub(crate) fn start(
+    ws: &str,
+    endpoint_hint: Option<&str>,
+    model_hint: Option<&str>,
+) -> Result<PairingSession, PairingError> {
+    let now = crate::now_ms();
+    let hint = |raw: Option<&str>| {
+        raw.map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    };
+    let session = PairingSession {
+        id: format!("pair-{now}-{}", SESSION_SEQ.fetch_add(1, Ordering::Relaxed)),
+        ws: ws.to_string(),
+        step: PairingStep::Discover,
+        created_ms: now,
+        updated_ms: now,
+        endpoint_hint: hint(endpoint_hint),
+        model_hint: hint(model_hint),
+        candidates: Vec::new(),
+        picked: None,
+        verification: None,
+        attached: false,
+        last_error: None,
+    };
+    save(&session)?;
+    run_step(session, discover_step)
+}
+
+/// Discovers again, e.g. after starting the model server; allowed until the
+/// pick has been paired.
+pub(crate) fn rediscover(ws: &str, id: &str) -> Result<PairingSession, PairingError> {
+    let session = at_step(
+        ws,
+        id,
+        PairingStep::Discover,
+        &[PairingStep::Discover, PairingStep::Pick, PairingStep::Pair],
+    )?;
+    run_step(session, discover_step)
+}
+
+/// Picks candidate `candidate` and the model to pair it with. Without
+/// `model`, the candidate's own model, or its only offered one, is used.
+pub(crate) fn pick(
+    ws: &str,
+    id: &str,
+    candidate: &str,
+    model: Option<&str>,
+) -> Result<PairingSession, PairingError> {
+    let session = at_step(
+        ws,
+        id,
+        PairingStep::Pick,
+        &[PairingStep::Pick, PairingStep::Pair],
+    )?;
+    run_step(session, |session| {
+        let mut picked = session
+            .candidates
+            .iter()
+            .find(|c| c.id == candidate)
+            .cloned()
+            .ok_or_else(|| PairingError::UnknownCandidate {
+                candidate: candidate.to_string(),
+            })?;
+        if picked.endpoint.as_deref().is_none_or(str::is_empty) {
+            return Err(PairingError::MissingField {
+                field: "endpoint".to_string(),
+            });
+        }
+        let model = model
+            .map(str::trim)
+            .filter(|m| !m.is_empty())
+            .map(str::to_string)
+            .or_else(|| picked.model.clone())
+            .or_else(|| match picked.models.as_slice() {
+                [only] => Some(only.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| PairingError::MissingField {
+                field: "model".to_string(),
+            })?;
+        if !picked.models.is_empty() && !picked.models.contains(&model) {
+            return Err(PairingError::UnknownModel {
+                model,
+                models: picked.models.clone(),
+            });
+        }
+        picked.model = Some(model);
+        session.picked = Some(picked);
+        session.step = PairingStep::Pair;
+        Ok(())
+    })
+}
+
+fn picked(session: &PairingSession) -> Result<(String, String, String), PairingError> {
+    let picked = session
+        .picked
+        .as_ref()
+        .ok_or_else(|| PairingError::MissingField {
+            field: "candidate".to_string(),
+        })?;
+    let field = |value: &Option<String>, name: &str| {
+        value.clone().ok_or_else(|| PairingError::MissingField {
+            field: name.to_string(),
+        })
+    };
+    Ok((
+        picked.id.clone(),
+        field(&picked.endpoint, "endpoint")?,
+        field(&picked.model, "model")?,
+    ))
+}
+
+/// Pairs the pick. A provider already paired, e.g. by an earlier attempt
+/// whose result was lost, counts as done.
+pub(crate) fn pair(ws: &str, id: &str) -> Result<PairingSession, PairingError> {
+    let session = at_step(ws, id, PairingStep::Pair, &[PairingStep::Pair])?;
+    run_step(session, |session| {
+        let (provider_id, endpoint, model) = picked(session)?;
+        if providers::get(ws, &provider_id).is_none() {
+            let _ = crate::providers_list();
+        }
+        let already = providers::get(ws, &provider_id).is_some_and(|p| {
+            matches!(
+                p.state,
+                ProviderState::Paired | ProviderState::Attached | ProviderState::Detached
+            )
+        });
+        if !already {
+            crate::providers_pair(&provider_id, &endpoint, &model).map_err(|error| {
+                PairingError::Command {
+                    step: PairingStep::Pair,
+                    error,
+                }
+            })?;
+        }
+        session.step = PairingStep::Verify;
+        Ok(())
+    })
+}
+
+/// Sends a test request to the paired endpoint.
+pub(crate) fn verify(ws: &str, id: &str) -> Result<PairingSession, PairingError> {
+    let session = at_step(ws, id, PairingStep::Verify, &[PairingStep::Verify])?;
+    let timeout =
+        Duration::from_millis(load_config().unwrap_or_default().provider_health.timeout_ms);
+    run_step(session, |session| {
+        let (_, endpoint, _) = picked(session)?;
+        let (method, outcome) = provider_health::test_request(&endpoint, timeout);
+        let (latency_ms, detail) =
+            outcome.map_err(|message| PairingError::VerifyFailed { message })?;
+        session.verification = Some(PairingVerification {
+            ts_ms: crate::now_ms(),
+            method: method.to_string(),
+            latency_ms,
+            detail,
+        });
+        session.step = PairingStep::Attach;
+        Ok(())
+    })
+}
+
+/// Attaches the verified provider with the picked model and closes the
+/// session.
+pub(crate) fn attach(ws: &str, id: &str) -> Result<PairingSession, PairingError> {
+    let session = at_step(ws, id, PairingStep::Attach, &[PairingStep::Attach])?;
+    run_step(session, |session| {
+        let (provider_id, _, model) = picked(session)?;
+        crate::providers_attach(&provider_id, Some(&model)).map_err(|error| {
+            PairingError::Command {
+                step: PairingStep::Attach,
+                error,
+            }
+        })?;
+        session.attached = true;
+        session.step = PairingStep::Done;
+        Ok(())
+    })
+}
+
+/// Closes a verified session without attaching.
+pub(crate) fn finish(ws: &str, id: &str) -> Result<PairingSession, PairingError> {
+    let session = at_step(ws, id, PairingStep::Done, &[PairingStep::Attach])?;
+    run_step(session, |session| {
+        session.step = PairingStep::Done;
+        Ok(())
+    })
+}
+
+pub(crate) fn cancel(ws: &str, id: &str) -> Result<PairingSession, PairingError> {
+    let session = get(ws, id)?;
+    if session.step.is_closed() {
+        return Err(PairingError::OutOfOrder {
+            expected: session.step,
+            attempted: PairingStep::Cancelled,
+        });
+    }
+    run_step(session, |session| {
+        session.step = PairingStep::Cancelled;
+        Ok(())
+    })
+}
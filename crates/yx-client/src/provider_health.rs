@@ -0,0 +1,432 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use yx_protocol::{Event as ProtocolEvent, Provider, ProviderState, Severity};
+
+use crate::config::ProviderHealthConfig;
+
+pub const HEALTH_TOPIC: &str = "provider.health_changed";
+/// Probes kept per workspace; the journal is compacted at twice this size.
+const PROBE_HISTORY_LIMIT: usize = 2_000;
+/// Failures listed in `ProviderHealth::recent_failures`.
+const RECENT_FAILURES: usize = 5;
+
+type HealthListener = Box<dyn Fn(&ProtocolEvent) + Send>;
+
+static PROBES: Mutex<Option<HashMap<String, Vec<Probe>>>> = Mutex::new(None);
+static LISTENERS: Mutex<Vec<HealthListener>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    /// Reachable, but slower than `slow_ms`.
+    Degraded,
+    Down,
+    #[default]
+    Unknown,
+}
+
+impl HealthStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthStatus::Up => "up",
+            HealthStatus::Degraded => "degraded",
+            HealthStatus::Down => "down",
+            HealthStatus::Unknown => "unknown",
+        }
+    }
+}
+
+/// One check of one provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Probe {
+    pub id: String,
+    pub ts_ms: u64,
+    /// Lifecycle state when probed.
+    pub state: ProviderState,
+    /// `tcp` or `unix` for a connect to the endpoint, `status` when only the
+    /// daemon's `providers.status` could vouch for it.
+    pub method: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub status: HealthStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderHealth {
+    pub id: String,
+    pub state: ProviderState,
+    pub status: HealthStatus,
+    /// Start of the current run of `status`.
+    pub since_ms: u64,
+    pub last_probe_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg_latency_ms: Option<u64>,
+    pub checks: u64,
+    pub failures: u64,
+    /// Share of retained probes that reached the provider, in percent.
+    pub uptime_pct: f64,
+    /// Newest first.
+    pub recent_failures: Vec<Probe>,
+}
+
+/// `data` of a `provider.health_changed` event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthChange {
+    pub ws: String,
+    pub id: String,
+    pub from: HealthStatus,
+    pub to: HealthStatus,
+    pub probe: Probe,
+}
+
+fn journal_path(ws: &str) -> PathBuf {
+    PathBuf::from(crate::ws_data_dir(ws)).join("provider_health.ndjson")
+}
+
+fn read_journal(ws: &str) -> Vec<Probe> {
+    fs::read_to_string(journal_path(ws))
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Appends `probes`, or rewrites the journal with them when `rewrite` is set.
+fn write_journal(ws: &str, probes: &[Probe], rewrite: bool) -> Result<()> {
+    let path = journal_path(ws);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    }
+    let mut out = String::new();
+    for probe in probes {
+        out.push_str(&serde_json::to_string(probe)?);
+        out.push('\n');
+    }
+    if rewrite {
+        fs::write(&path, out).with_context(|| format!("write {}", path.display()))?;
+    } else {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("open {}", path.display()))?;
+        file.write_all(out.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn with_ws<T>(ws: &str, f: impl FnOnce(&mut Vec<Probe>) -> T) -> T {
+    let mut slot = PROBES.lock().unwrap_or_else(|e| e.into_inner());
+    let probes = slot
+        .get_or_insert_with(HashMap::new)
+        .entry(ws.to_string())
+        .or_insert_with(|| read_journal(ws));
+    f(probes)
+}
+
+/// `(host, port)` of an endpoint such as `https://host/v1`, `host:8080` or
+/// `http://[::1]:11434`.
+fn host_port(endpoint: &str) -> Option<(String, u16)> {
+    let (scheme, rest) = endpoint.split_once("://").unwrap_or(("", endpoint));
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let default_port = match scheme {
+        "https" | "wss" | "grpcs" => Some(443),
+        "http" | "ws" => Some(80),
+        _ => None,
+    };
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(v6) => {
+            let (host, rest) = v6.split_once(']')?;
+            (host, rest.strip_prefix(':'))
+        }
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => default_port?,
+    };
+    (!host.is_empty()).then(|| (host.to_string(), port))
+}
+
+/// Connects to `endpoint` and hangs up; returns the method and connect time.
+fn connect(endpoint: &str, timeout: Duration) -> (&'static str, Result<u64, String>) {
+    let started = Instant::now();
+    let elapsed = || started.elapsed().as_millis() as u64;
+    if let Some(path) = endpoint.strip_prefix("unix://") {
+        #[cfg(unix)]
+        return (
+            "unix",
+            UnixStream::connect(path)
+                .map(|_| elapsed())
+                .map_err(|e| e.to_string()),
+        );
+        #[cfg(not(unix))]
+        return ("unix", Err(format!("unix endpoint unsupported: {path}")));
+    }
+    let Some((host, port)) = host_port(endpoint) else {
+        return ("tcp", Err(format!("cannot parse endpoint: {endpoint}")));
+    };
+    let addr = match (host.as_str(), port).to_socket_addrs() {
+        Ok(mut addrs) => match addrs.next() {
+            Some(addr) => addr,
+            None => return ("tcp", Err(format!("no address for {host}"))),
+        },
+        Err(e) => return ("tcp", Err(format!("resolve {host}: {e}"))),
+    };
+    (
+        "tcp",
+        TcpStream::connect_timeout(&addr, timeout)
+            .map(|_| elapsed())
+            .map_err(|e| e.to_string()),
+    )
+}
+
+/// A test request against `endpoint`: `GET` of its path for plain `http://`
+/// endpoints, where any HTTP response counts, and a bare connect for the
+/// rest. Returns the method, the round trip and what answered.
+pub(crate) fn test_request(
+    endpoint: &str,
+    timeout: Duration,
+) -> (&'static str, Result<(u64, String), String>) {
+    let Some(rest) = endpoint.strip_prefix("http://") else {
+        let (method, outcome) = connect(endpoint, timeout);
+        return (method, outcome.map(|ms| (ms, "connected".to_string())));
+    };
+    let Some((host, port)) = host_port(endpoint) else {
+        return ("http", Err(format!("cannot parse endpoint: {endpoint}")));
+    };
+    let path = rest.find('/').map_or("/", |idx| &rest[idx..]);
+    let started = Instant::now();
+    let outcome = (|| {
+        let addr = (host.as_str(), port)
+            .to_socket_addrs()
+            .map_err(|e| format!("resolve {host}: {e}"))?
+            .next()
+            .ok_or_else(|| format!("no address for {host}"))?;
+        let mut stream = TcpStream::connect_timeout(&addr, timeout).map_err(|e| e.to_string())?;
+        stream
+            .set_read_timeout(Some(timeout))
+            .map_err(|e| e.to_string())?;
+        write!(
+            stream,
+            "GET {path} HTTP/1.0\r\nHost: {host}\r\nUser-Agent: yx\r\n\r\n"
+        )
+        .map_err(|e| e.to_string())?;
+        let mut status = String::new();
+        BufReader::new(stream)
+            .read_line(&mut status)
+            .map_err(|e| e.to_string())?;
+        let status = status.trim_end().to_string();
+        if !status.starts_with("HTTP/") {
+            return Err(format!("not an HTTP endpoint: {status:?}"));
+        }
+        Ok((started.elapsed().as_millis() as u64, status))
+    })();
+    ("http", outcome)
+}
+
+/// What `providers.status` said in this round.
+pub(crate) struct StatusCheck {
+    pub(crate) active: Option<Provider>,
+    pub(crate) latency_ms: u64,
+    pub(crate) error: Option<String>,
+}
+
+/// Probes `provider`: a connect to its endpoint, with the daemon's view of
+/// the active provider taking precedence when it reports an error. Paired
+/// providers without an endpoint cannot be probed.
+pub(crate) fn probe(
+    provider: &Provider,
+    status: &StatusCheck,
+    config: &ProviderHealthConfig,
+) -> Option<Probe> {
+    let active = status.active.as_ref().filter(|a| a.id == provider.id);
+    let reported = active.and_then(|a| a.last_error.clone());
+    let (method, outcome) = match (&provider.endpoint, &reported) {
+        (_, Some(error)) => ("status", Err(error.clone())),
+        (Some(endpoint), None) => connect(endpoint, Duration::from_millis(config.timeout_ms)),
+        (None, None) if provider.state == ProviderState::Attached => (
+            "status",
+            match (&status.error, active) {
+                (Some(error), _) => Err(error.clone()),
+                (None, Some(_)) => Ok(status.latency_ms),
+                (None, None) => Err("not reported active by the daemon".to_string()),
+            },
+        ),
+        (None, None) => return None,
+    };
+    let (ok, latency_ms, error) = match outcome {
+        Ok(ms) => (true, Some(ms), None),
+        Err(error) => (false, None, Some(error)),
+    };
+    let status = match latency_ms {
+        None => HealthStatus::Down,
+        Some(ms) if ms > config.slow_ms => HealthStatus::Degraded,
+        Some(_) => HealthStatus::Up,
+    };
+    Some(Probe {
+        id: provider.id.clone(),
+        ts_ms: crate::now_ms(),
+        state: provider.state,
+        method: method.to_string(),
+        ok,
+        latency_ms,
+        error,
+        status,
+    })
+}
+
+fn change_event(ws: &str, change: HealthChange) -> ProtocolEvent {
+    let severity = match change.to {
+        HealthStatus::Down => Severity::Error,
+        HealthStatus::Degraded => Severity::Warn,
+        HealthStatus::Up | HealthStatus::Unknown => Severity::Notice,
+    };
+    let mut msg = format!(
+        "provider {} is {} (was {})",
+        change.id,
+        change.to.as_str(),
+        change.from.as_str()
+    );
+    if let Some(error) = &change.probe.error {
+        msg.push_str(": ");
+        msg.push_str(error);
+    }
+    ProtocolEvent {
+        topic: HEALTH_TOPIC.to_string(),
+        severity,
+        ts_ms: change.probe.ts_ms,
+        event_id: None,
+        ws: Some(ws.to_string()),
+        seq: None,
+        msg: Some(msg),
+        data: json!(change),
+        compliance: None,
+        trace_id: None,
+    }
+}
+
+/// Adds a round of probes; returns a `provider.health_changed` event for
+/// every provider whose status changed. A first probe only counts as a
+/// change when the provider is not up.
+pub(crate) fn record(ws: &str, round: Vec<Probe>) -> Vec<ProtocolEvent> {
+    let changes: Vec<HealthChange> = with_ws(ws, |probes| {
+        let changes = round
+            .iter()
+            .filter_map(|probe| {
+                let from = probes
+                    .iter()
+                    .rev()
+                    .find(|p| p.id == probe.id)
+                    .map(|p| p.status);
+                let changed = match from {
+                    Some(from) => from != probe.status,
+                    None => probe.status != HealthStatus::Up,
+                };
+                changed.then(|| HealthChange {
+                    ws: ws.to_string(),
+                    id: probe.id.clone(),
+                    from: from.unwrap_or_default(),
+                    to: probe.status,
+                    probe: probe.clone(),
+                })
+            })
+            .collect();
+        probes.extend(round.iter().cloned());
+        if probes.len() > PROBE_HISTORY_LIMIT * 2 {
+            probes.drain(..probes.len() - PROBE_HISTORY_LIMIT);
+            let _ = write_journal(ws, probes, true);
+        } else {
+            let _ = write_journal(ws, &round, false);
+        }
+        changes
+    });
+    changes
+        .into_iter()
+        .map(|change| change_event(ws, change))
+        .collect()
+}
+
+fn summarize(id: &str, probes: &[&Probe]) -> Option<ProviderHealth> {
+    let last = probes.last()?;
+    let since_ms = probes
+        .iter()
+        .rev()
+        .take_while(|p| p.status == last.status)
+        .last()
+        .map_or(last.ts_ms, |p| p.ts_ms);
+    let latencies: Vec<u64> = probes.iter().filter_map(|p| p.latency_ms).collect();
+    let failures = probes.iter().filter(|p| !p.ok).count() as u64;
+    let checks = probes.len() as u64;
+    Some(ProviderHealth {
+        id: id.to_string(),
+        state: last.state,
+        status: last.status,
+        since_ms,
+        last_probe_ms: last.ts_ms,
+        latency_ms: last.latency_ms,
+        avg_latency_ms: (!latencies.is_empty())
+            .then(|| latencies.iter().sum::<u64>() / latencies.len() as u64),
+        checks,
+        failures,
+        uptime_pct: (checks - failures) as f64 * 100.0 / checks as f64,
+        recent_failures: probes
+            .iter()
+            .rev()
+            .filter(|p| !p.ok)
+            .take(RECENT_FAILURES)
+            .map(|p| (*p).clone())
+            .collect(),
+    })
+}
+
+/// Health of every provider probed in `ws`, by id.
+pub(crate) fn health(ws: &str) -> Vec<ProviderHealth> {
+    with_ws(ws, |probes| {
+        let mut by_id: BTreeMap<&str, Vec<&Probe>> = BTreeMap::new();
+        for probe in probes.iter() {
+            by_id.entry(probe.id.as_str()).or_default().push(probe);
+        }
+        by_id
+            .into_iter()
+            .filter_map(|(id, probes)| summarize(id, &probes))
+            .collect()
+    })
+}
+
+pub(crate) fn subscribe(listener: HealthListener) {
+    let mut listeners = LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+    listeners.push(listener);
+}
+
+pub(crate) fn notify(event: &ProtocolEvent) {
+    let listeners = LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+    for listener in listeners.iter() {
+        listener(event);
+    }
+}
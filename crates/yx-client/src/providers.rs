@@ -0,0 +1,190 @@
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use yx_protocol::{CommandError, Provider, ProviderState};
+
+/// Last known providers per workspace, by id.
+static PROVIDERS: Mutex<Option<HashMap<String, BTreeMap<String, Provider>>>> = Mutex::new(None);
+
+fn parse(value: &Value) -> Option<Provider> {
+    serde_json::from_value::<Provider>(value.clone())
+        .ok()
+        .filter(|p| !p.id.is_empty())
+}
+
+/// Providers in a `providers.list` or `providers.discover` result.
+pub(crate) fn items_from(value: &Value) -> Vec<Provider> {
+    let items = value
+        .get("items")
+        .or_else(|| value.get("providers"))
+        .unwrap_or(value);
+    items
+        .as_array()
+        .map(|items| items.iter().filter_map(parse).collect())
+        .unwrap_or_default()
+}
+
+/// Active provider in a `providers.status` result, which may only name it.
+pub(crate) fn active_from(ws: &str, value: &Value) -> Option<Provider> {
+    let active = value.get("active").or_else(|| value.get("provider"))?;
+    let mut provider = match active {
+        Value::String(id) => {
+            get(ws, id).unwrap_or_else(|| Provider::new(id, ProviderState::Attached))
+        }
+        other => parse(other)?,
+    };
+    if active.get("state").is_none() && active.get("trust_state").is_none() {
+        provider.state = ProviderState::Attached;
+    }
+    Some(provider)
+}
+
+fn with_ws<T>(ws: &str, f: impl FnOnce(&mut BTreeMap<String, Provider>) -> T) -> T {
+    let mut slot = PROVIDERS.lock().unwrap_or_else(|e| e.into_inner());
+    f(slot
+        .get_or_insert_with(HashMap::new)
+        .entry(ws.to_string())
+        .or_default())
+}
+
+pub(crate) fn get(ws: &str, id: &str) -> Option<Provider> {
+    with_ws(ws, |known| known.get(id).cloned())
+}
+
+pub(crate) fn attached(ws: &str) -> Option<Provider> {
+    with_ws(ws, |known| {
+        known
+            .values()
+            .find(|p| p.state == ProviderState::Attached)
+            .cloned()
+    })
+}
+
+/// Providers worth health-checking: attached and paired ones.
+pub(crate) fn monitored(ws: &str) -> Vec<Provider> {
+    with_ws(ws, |known| {
+        known
+            .values()
+            .filter(|p| matches!(p.state, ProviderState::Attached | ProviderState::Paired))
+            .cloned()
+            .collect()
+    })
+}
+
+pub(crate) fn is_empty(ws: &str) -> bool {
+    with_ws(ws, |known| known.is_empty())
+}
+
+/// A full listing replaces what was known about `ws`.
+pub(crate) fn replace(ws: &str, items: &[Provider]) {
+    with_ws(ws, |known| {
+        *known = items.iter().map(|p| (p.id.clone(), p.clone())).collect();
+    });
+}
+
+pub(crate) fn merge(ws: &str, items: &[Provider]) {
+    with_ws(ws, |known| {
+        for provider in items {
+            known.insert(provider.id.clone(), provider.clone());
+        }
+    });
+}
+
+/// Records the active provider; only one can be attached at a time.
+pub(crate) fn set_active(ws: &str, active: Option<&Provider>) {
+    with_ws(ws, |known| {
+        for provider in known.values_mut() {
+            if provider.state == ProviderState::Attached
+                && active.is_none_or(|a| a.id != provider.id)
+            {
+                provider.state = ProviderState::Detached;
+            }
+        }
+        if let Some(active) = active {
+            known.insert(active.id.clone(), active.clone());
+        }
+    });
+}
+
+/// State a lifecycle command moves a provider to.
+pub(crate) fn target(command: &str) -> Option<ProviderState> {
+    match command {
+        "providers.pair" => Some(ProviderState::Paired),
+        "providers.attach" => Some(ProviderState::Attached),
+        "providers.detach" => Some(ProviderState::Detached),
+        "providers.revoke" => Some(ProviderState::Revoked),
+        _ => None,
+    }
+}
+
+fn refused(code: &str, message: String, details: Value) -> CommandError {
+    CommandError {
+        code: code.to_string(),
+        message,
+        details: Some(details),
+        trace_id: Some(crate::next_trace_id()),
+    }
+}
+
+/// Checks `command` against the last known state of provider `id` in `ws`;
+/// returns that state. Only pairing accepts a provider YX has not seen, since
+/// it carries its own endpoint.
+pub(crate) fn check(
+    ws: &str,
+    command: &str,
+    id: &str,
+) -> Result<Option<ProviderState>, CommandError> {
+    let Some(to) = target(command) else {
+        return Err(refused(
+            "invalid_transition",
+            format!("'{command}' is not a provider lifecycle command"),
+            json!({ "workspace": ws, "command": command }),
+        ));
+    };
+    if id.is_empty() {
+        return Err(refused(
+            "invalid_args",
+            format!("'{command}' needs a provider id"),
+            json!({ "workspace": ws, "command": command }),
+        ));
+    }
+    match get(ws, id) {
+        None if to == ProviderState::Paired => Ok(None),
+        None => Err(refused(
+            "unknown_provider",
+            format!("provider '{id}' is not known in workspace '{ws}'"),
+            json!({ "workspace": ws, "command": command, "provider": id }),
+        )),
+        Some(provider) if provider.state.can_transition_to(to) => Ok(Some(provider.state)),
+        Some(provider) => Err(refused(
+            "invalid_transition",
+            format!(
+                "cannot {} provider '{id}': it is {}",
+                command.trim_start_matches("providers."),
+                provider.state
+            ),
+            json!({
+                "workspace": ws,
+                "command": command,
+                "provider": id,
+                "from": provider.state,
+                "to": to,
+            }),
+        )),
+    }
+}
+
+/// Records a lifecycle command the daemon accepted.
+pub(crate) fn applied(ws: &str, id: &str, to: ProviderState, reported: Option<&Provider>) {
+    let mut provider =
+        with_ws(ws, |known| known.get(id).cloned()).unwrap_or_else(|| Provider::new(id, to));
+    if let Some(reported) = reported.filter(|p| p.id == id) {
+        provider = reported.clone();
+    }
+    provider.state = to;
+    if to == ProviderState::Attached {
+        set_active(ws, Some(&provider));
+    } else {
+        merge(ws, &[provider]);
+    }
+}
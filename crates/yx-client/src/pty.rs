@@ -0,0 +1,578 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::config::PtyConfig;
+use crate::event_store::glob_match;
+use crate::redact::Redactor;
+
+pub const DEFAULT_PTY_COLS: u16 = 80;
+pub const DEFAULT_PTY_ROWS: u16 = 24;
+const MOCK_PROMPT: &str = "$ ";
+/// A recorded event is written once its line ends; longer lines are cut here.
+const RECORD_LINE_MAX: usize = 8 * 1024;
+
+type OutputFn = Box<dyn FnMut(&PtyOutput) + Send>;
+
+static PTYS: Mutex<Option<HashMap<String, Arc<Pty>>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PtyOpenRequest {
+    /// Program to run; the daemon's login shell if unset.
+    pub cmd: Option<String>,
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    pub env: BTreeMap<String, String>,
+    /// `DEFAULT_PTY_COLS` if unset.
+    pub cols: Option<u16>,
+    /// `DEFAULT_PTY_ROWS` if unset.
+    pub rows: Option<u16>,
+    /// Record output to an asciicast file. Always on for workspaces matching
+    /// `[pty] record`.
+    pub record: bool,
+    /// Record what is typed too; needs `record` or a matching `[pty] record`.
+    pub record_input: bool,
+    /// Title stored in the recording.
+    pub title: Option<String>,
+}
+
+impl PtyOpenRequest {
+    pub fn cols(&self) -> u16 {
+        self.cols.unwrap_or(DEFAULT_PTY_COLS)
+    }
+
+    pub fn rows(&self) -> u16 {
+        self.rows.unwrap_or(DEFAULT_PTY_ROWS)
+    }
+
+    /// `shell.pty.open` args as sent to the daemon.
+    pub(crate) fn args_value(&self) -> Value {
+        json!({
+            "cmd": self.cmd,
+            "args": self.args,
+            "cwd": self.cwd,
+            "env": self.env,
+            "cols": self.cols(),
+            "rows": self.rows(),
+        })
+    }
+
+    /// Whether to record, and whether to record input, in `ws`.
+    pub(crate) fn recording(&self, config: &PtyConfig, ws: &str) -> (bool, bool) {
+        let record = self.record || config.record.iter().any(|p| glob_match(p, ws));
+        (record, record && (self.record_input || config.record_input))
+    }
+}
+
+/// An open PTY.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtySession {
+    pub pty_id: String,
+    /// Trace of the `shell.pty.open`; also the id of the recording.
+    pub trace_id: String,
+    pub ws: String,
+    pub operator_id: String,
+    pub cmd: Option<String>,
+    pub cols: u16,
+    pub rows: u16,
+    pub opened_ms: u64,
+    /// Id of the recording, when the session is recorded.
+    pub recording: Option<String>,
+    pub record_input: bool,
+}
+
+/// What `pty_open` hands its callback: output as it arrives, then the exit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PtyOutput {
+    Data {
+        pty_id: String,
+        data: String,
+        ts_ms: u64,
+    },
+    Exit {
+        pty_id: String,
+        exit_code: Option<i32>,
+        signal: Option<String>,
+    },
+}
+
+/// One event of an asciicast v2 file: seconds since the start, `o` for
+/// output or `i` for input, and the data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CastEvent(pub f64, pub String, pub String);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingSummary {
+    /// The trace id of the `shell.pty.open` that started it.
+    pub id: String,
+    pub ws: String,
+    pub operator_id: Option<String>,
+    pub pty_id: Option<String>,
+    pub title: Option<String>,
+    pub width: u16,
+    pub height: u16,
+    /// Start, in ms since the epoch.
+    pub started_ms: u64,
+    /// Time of the last event.
+    pub duration_secs: f64,
+    pub events: usize,
+    /// Holds input events as well as output.
+    pub input: bool,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub summary: RecordingSummary,
+    /// The asciicast header as written.
+    pub header: Value,
+    pub events: Vec<CastEvent>,
+}
+
+/// Appends redacted events to `<ws>/casts/<trace_id>.cast`. Data is held
+/// until its line ends, so a secret split across chunks is still redacted.
+pub(crate) struct Recorder {
+    file: File,
+    started: Instant,
+    input: bool,
+    redactor: Arc<Redactor>,
+    /// Code and data of the event whose line has not ended yet.
+    pending: Option<(&'static str, String)>,
+}
+
+fn casts_dir(ws: &str) -> PathBuf {
+    PathBuf::from(crate::ws_data_dir(ws)).join("casts")
+}
+
+fn cast_path(ws: &str, id: &str) -> Result<PathBuf> {
+    if id.is_empty() || id.contains(['/', '\\']) || id.starts_with('.') {
+        return Err(anyhow!("invalid recording id: {id}"));
+    }
+    Ok(casts_dir(ws).join(format!("{id}.cast")))
+}
+
+impl Recorder {
+    /// Creates the file and writes the header.
+    pub(crate) fn create(
+        session: &PtySession,
+        title: Option<&str>,
+        redactor: Arc<Redactor>,
+    ) -> Result<Recorder> {
+        let path = cast_path(&session.ws, &session.trace_id)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("create {}", path.display()))?;
+        let mut env = json!({ "TERM": "xterm-256color" });
+        if let Some(cmd) = &session.cmd {
+            env["SHELL"] = json!(cmd);
+        }
+        let header = json!({
+            "version": 2,
+            "width": session.cols,
+            "height": session.rows,
+            "timestamp": session.opened_ms / 1000,
+            "title": title,
+            "env": env,
+            "yx": {
+                "trace_id": session.trace_id,
+                "ws": session.ws,
+                "operator_id": session.operator_id,
+                "pty_id": session.pty_id,
+                "started_ms": session.opened_ms,
+                "input": session.record_input,
+            },
+        });
+        writeln!(file, "{header}").with_context(|| format!("write {}", path.display()))?;
+        Ok(Recorder {
+            file,
+            started: Instant::now(),
+            input: session.record_input,
+            redactor,
+            pending: None,
+        })
+    }
+
+    fn event(&mut self, code: &'static str, data: &str) {
+        if self.pending.as_ref().is_some_and(|(c, _)| *c != code) {
+            self.flush();
+        }
+        let (_, buffered) = self.pending.get_or_insert((code, String::new()));
+        buffered.push_str(data);
+        let complete = match buffered.rfind(['\n', '\r']) {
+            Some(end) => end + 1,
+            None if buffered.len() >= RECORD_LINE_MAX => buffered.len(),
+            None => return,
+        };
+        let rest = buffered.split_off(complete);
+        let line = std::mem::replace(buffered, rest);
+        self.write(code, &line);
+        if self.pending.as_ref().is_some_and(|(_, b)| b.is_empty()) {
+            self.pending = None;
+        }
+    }
+
+    /// Writes what is held back, line ended or not.
+    fn flush(&mut self) {
+        if let Some((code, data)) = self.pending.take() {
+            self.write(code, &data);
+        }
+    }
+
+    fn write(&mut self, code: &str, data: &str) {
+        let event = CastEvent(
+            self.started.elapsed().as_micros() as f64 / 1_000_000.0,
+            code.to_string(),
+            self.redactor.text(data),
+        );
+        if let Ok(line) = serde_json::to_string(&event) {
+            let _ = writeln!(self.file, "{line}");
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// A registered PTY: where its output goes and what is recorded.
+pub(crate) struct Pty {
+    pub(crate) session: PtySession,
+    recorder: Mutex<Option<Recorder>>,
+    on_output: Mutex<OutputFn>,
+    /// Typed text since the last newline, for the mock shell.
+    mock_line: Mutex<String>,
+    exited: AtomicBool,
+}
+
+impl Pty {
+    fn id(&self) -> String {
+        self.session.pty_id.clone()
+    }
+
+    pub(crate) fn output(&self, data: &str) {
+        if data.is_empty() || self.exited.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(recorder) = self
+            .recorder
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_mut()
+        {
+            recorder.event("o", data);
+        }
+        let output = PtyOutput::Data {
+            pty_id: self.id(),
+            data: data.to_string(),
+            ts_ms: crate::now_ms(),
+        };
+        (self.on_output.lock().unwrap_or_else(|e| e.into_inner()))(&output);
+    }
+
+    pub(crate) fn input(&self, data: &str) {
+        if let Some(recorder) = self
+            .recorder
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_mut()
+            .filter(|r| r.input)
+        {
+            recorder.event("i", data);
+        }
+    }
+
+    /// Reports the exit once, closes the recording and forgets the PTY.
+    pub(crate) fn exit(&self, exit_code: Option<i32>, signal: Option<String>) {
+        if self.exited.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        self.recorder
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let pty_id = self.id();
+        let output = PtyOutput::Exit {
+            pty_id: pty_id.clone(),
+            exit_code,
+            signal,
+        };
+        (self.on_output.lock().unwrap_or_else(|e| e.into_inner()))(&output);
+        if let Some(ptys) = PTYS.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            ptys.remove(&pty_id);
+        }
+    }
+}
+
+pub(crate) fn register(
+    session: PtySession,
+    recorder: Option<Recorder>,
+    on_output: OutputFn,
+) -> Arc<Pty> {
+    let pty = Arc::new(Pty {
+        session: session.clone(),
+        recorder: Mutex::new(recorder),
+        on_output: Mutex::new(on_output),
+        mock_line: Mutex::new(String::new()),
+        exited: AtomicBool::new(false),
+    });
+    PTYS.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(session.pty_id, Arc::clone(&pty));
+    pty
+}
+
+pub(crate) fn get(pty_id: &str) -> Option<Arc<Pty>> {
+    PTYS.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .and_then(|ptys| ptys.get(pty_id).cloned())
+}
+
+/// Open PTYs in `ws`, oldest first.
+pub(crate) fn sessions(ws: &str) -> Vec<PtySession> {
+    let mut out: Vec<PtySession> = PTYS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|ptys| {
+            ptys.values()
+                .map(|pty| pty.session.clone())
+                .filter(|s| s.ws == ws)
+                .collect()
+        })
+        .unwrap_or_default();
+    out.sort_by_key(|s| s.opened_ms);
+    out
+}
+
+/// Ends every PTY of `ws`, once its daemon is gone.
+pub(crate) fn hang_up(ws: &str) {
+    let open: Vec<Arc<Pty>> = PTYS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|ptys| {
+            ptys.values()
+                .filter(|pty| pty.session.ws == ws)
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    for pty in open {
+        pty.exit(None, Some("SIGHUP".to_string()));
+    }
+}
+
+fn text(value: &Value, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .find_map(|k| value.get(*k).and_then(Value::as_str))
+        .map(str::to_string)
+}
+
+fn error_of(value: &Value) -> Option<String> {
+    if value.get("type").and_then(Value::as_str) == Some("error")
+        || value.get("ok").and_then(Value::as_bool) == Some(false)
+    {
+        let error = value.get("error").unwrap_or(value);
+        return Some(text(error, &["message"]).unwrap_or_else(|| "daemon error".to_string()));
+    }
+    None
+}
+
+fn read_value(reader: &mut impl BufRead) -> Result<Value> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(anyhow!("daemon closed the connection"));
+        }
+        if let Ok(value) = serde_json::from_str::<Value>(line.trim_end()) {
+            return Ok(value);
+        }
+    }
+}
+
+/// Reads the daemon's answer to `shell.pty.open`: the id of the new PTY,
+/// at the top level or under `result`.
+pub(crate) fn read_opened(reader: &mut impl BufRead) -> Result<String> {
+    let value = read_value(reader)?;
+    if let Some(message) = error_of(&value) {
+        return Err(anyhow!(message));
+    }
+    let result = value.get("result").unwrap_or(&value);
+    text(result, &["pty_id", "id"]).ok_or_else(|| anyhow!("daemon did not return a pty_id"))
+}
+
+/// Reads the daemon's answer to `shell.pty.write` or `shell.pty.close`.
+pub(crate) fn read_ack(reader: &mut impl BufRead) -> Result<()> {
+    match error_of(&read_value(reader)?) {
+        Some(message) => Err(anyhow!(message)),
+        None => Ok(()),
+    }
+}
+
+/// Follows the PTY's output on the connection that opened it, until the
+/// exit or the connection drops. Output comes as `{"type":"pty.output",
+/// "data":..}` and the end as `{"type":"pty.exit","exit_code":..}`.
+pub(crate) fn follow(reader: &mut impl BufRead, pty: &Pty) {
+    loop {
+        let Ok(value) = read_value(reader) else {
+            pty.exit(None, None);
+            return;
+        };
+        let value = value
+            .get("result")
+            .filter(|r| r.is_object())
+            .unwrap_or(&value);
+        match value.get("type").and_then(Value::as_str).unwrap_or("") {
+            "pty.output" | "output" | "stdout" => {
+                pty.output(&text(value, &["data", "text"]).unwrap_or_default());
+            }
+            "pty.exit" | "exit" => {
+                let exit_code = value
+                    .get("exit_code")
+                    .or_else(|| value.get("code"))
+                    .and_then(Value::as_i64)
+                    .map(|c| c as i32);
+                let signal = value.get("signal").and_then(|s| match s {
+                    Value::String(name) => Some(name.clone()),
+                    Value::Number(n) => Some(n.to_string()),
+                    _ => None,
+                });
+                pty.exit(exit_code, signal);
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Mock terminal: echoes what is typed and answers each line, `exit`
+/// ends the session.
+pub(crate) fn mock_open(pty: &Pty) {
+    let cmd = pty.session.cmd.as_deref().unwrap_or("sh");
+    pty.output(&format!("mock pty: {cmd}\r\n{MOCK_PROMPT}"));
+}
+
+pub(crate) fn mock_write(pty: &Pty, data: &str) {
+    let mut echo = String::new();
+    let mut line = pty.mock_line.lock().unwrap_or_else(|e| e.into_inner());
+    for c in data.chars() {
+        match c {
+            '\r' | '\n' => {
+                let typed = std::mem::take(&mut *line);
+                if typed.trim() == "exit" {
+                    drop(line);
+                    pty.output(&format!("{echo}\r\n"));
+                    pty.exit(Some(0), None);
+                    return;
+                }
+                echo.push_str("\r\n");
+                if !typed.trim().is_empty() {
+                    echo.push_str(&format!("mock: {typed}\r\n"));
+                }
+                echo.push_str(MOCK_PROMPT);
+            }
+            '\u{7f}' | '\u{8}' => {
+                if line.pop().is_some() {
+                    echo.push_str("\u{8} \u{8}");
+                }
+            }
+            c => {
+                line.push(c);
+                echo.push(c);
+            }
+        }
+    }
+    drop(line);
+    pty.output(&echo);
+}
+
+fn summary_of(id: &str, ws: &str, header: &Value, bytes: u64) -> RecordingSummary {
+    let yx = header.get("yx").cloned().unwrap_or_default();
+    let timestamp = header.get("timestamp").and_then(Value::as_u64).unwrap_or(0);
+    RecordingSummary {
+        id: id.to_string(),
+        ws: text(&yx, &["ws"]).unwrap_or_else(|| ws.to_string()),
+        operator_id: text(&yx, &["operator_id"]),
+        pty_id: text(&yx, &["pty_id"]),
+        title: text(header, &["title"]),
+        width: header.get("width").and_then(Value::as_u64).unwrap_or(0) as u16,
+        height: header.get("height").and_then(Value::as_u64).unwrap_or(0) as u16,
+        started_ms: yx
+            .get("started_ms")
+            .and_then(Value::as_u64)
+            .unwrap_or(timestamp * 1000),
+        duration_secs: 0.0,
+        events: 0,
+        input: false,
+        bytes,
+    }
+}
+
+/// Parses an asciicast v2 file; lines that are not events are skipped.
+fn parse(id: &str, ws: &str, content: &str) -> Result<Recording> {
+    let mut lines = content.lines();
+    let header: Value = lines
+        .next()
+        .and_then(|line| serde_json::from_str(line).ok())
+        .filter(|h: &Value| h.get("version").and_then(Value::as_u64) == Some(2))
+        .ok_or_else(|| anyhow!("recording {id} has no asciicast v2 header"))?;
+    let events: Vec<CastEvent> = lines
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let mut summary = summary_of(id, ws, &header, content.len() as u64);
+    summary.duration_secs = events.last().map(|e| e.0).unwrap_or(0.0);
+    summary.events = events.len();
+    summary.input = events.iter().any(|e| e.1 == "i");
+    Ok(Recording {
+        summary,
+        header,
+        events,
+    })
+}
+
+/// Recordings of `ws`, newest first.
+pub(crate) fn recordings(ws: &str) -> Vec<RecordingSummary> {
+    let mut out: Vec<RecordingSummary> = fs::read_dir(casts_dir(ws))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let name = entry.file_name().into_string().ok()?;
+                    let id = name.strip_suffix(".cast")?;
+                    let content = fs::read_to_string(entry.path()).ok()?;
+                    parse(id, ws, &content).ok().map(|r| r.summary)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    out.sort_by_key(|s| std::cmp::Reverse(s.started_ms));
+    out
+}
+
+/// The raw asciicast file of recording `id`, for standard players.
+pub(crate) fn cast(ws: &str, id: &str) -> Result<String> {
+    let path = cast_path(ws, id)?;
+    fs::read_to_string(&path).with_context(|| format!("no recording {id} in {ws}"))
+}
+
+pub(crate) fn recording(ws: &str, id: &str) -> Result<Recording> {
+    parse(id, ws, &cast(ws, id)?)
+}
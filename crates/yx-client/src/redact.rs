@@ -0,0 +1,257 @@
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use yx_protocol::{CommandResult, Event as ProtocolEvent};
+
+use crate::config::{load_config, RedactionConfig};
+
+pub const REDACTED: &str = "[redacted]";
+
+static CURRENT: Mutex<Option<(RedactionConfig, Arc<Redactor>)>> = Mutex::new(None);
+
+/// Compiled form of a `RedactionConfig`.
+pub(crate) struct Redactor {
+    enabled: bool,
+    /// Key hints split into words.
+    keys: Vec<Vec<String>>,
+    patterns: Vec<Regex>,
+    entropy: Option<(usize, f64)>,
+}
+
+/// Shannon entropy of `token` in bits per char.
+fn entropy_bits(token: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in token.chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+    let len = token.chars().count() as f64;
+    counts
+        .values()
+        .map(|n| {
+            let p = *n as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '+' | '_' | '-' | '.' | '~')
+}
+
+/// Lowercase words of a key: `x-api_key`, `apiKey` and `API_KEY` all give
+/// `api`, `key`.
+fn key_words(key: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut prev_lower = false;
+    for c in key.chars() {
+        if !c.is_ascii_alphanumeric() {
+            prev_lower = false;
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        if c.is_ascii_uppercase() && prev_lower && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        word.push(c.to_ascii_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Ids and model names are handed back to the daemon and never hidden,
+/// whatever they look like. Endpoints are not among them: URLs carry
+/// `user:pass@` and `?token=`.
+fn identifier_key(words: &[String]) -> bool {
+    let identifier = |w: &String| matches!(w.as_str(), "id" | "ids" | "model" | "models");
+    words.first().is_some_and(identifier) || words.last().is_some_and(identifier)
+}
+
+/// ULIDs are 26 chars of upper-case Crockford base32 and look random.
+fn is_ulid(token: &str) -> bool {
+    token.len() == 26
+        && token.chars().all(|c| {
+            c.is_ascii_digit() || (c.is_ascii_uppercase() && !matches!(c, 'I' | 'L' | 'O' | 'U'))
+        })
+}
+
+/// Longest run of alphanumerics: dotted or dashed names such as model files
+/// are made of short words, secrets are not.
+fn longest_run(token: &str) -> usize {
+    token
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .map(str::len)
+        .max()
+        .unwrap_or(0)
+}
+
+impl Redactor {
+    pub(crate) fn new(config: &RedactionConfig) -> Redactor {
+        Redactor {
+            enabled: config.enabled,
+            keys: config.keys.iter().map(|k| key_words(k)).collect(),
+            // patterns are validated when the config is parsed
+            patterns: config
+                .patterns
+                .iter()
+                .filter_map(|p| Regex::new(p).ok())
+                .collect(),
+            entropy: config
+                .entropy
+                .then_some((config.entropy_min_len, config.entropy_min_bits)),
+        }
+    }
+
+    /// A hint matches whole words of the key, a trailing plural `s`
+    /// included: `auth` hides `x_auth` and `authToken` but not `author`.
+    fn secret_key(&self, words: &[String]) -> bool {
+        self.keys
+            .iter()
+            .filter(|hint| !hint.is_empty())
+            .any(|hint| {
+                words.windows(hint.len()).any(|window| {
+                    let last = hint.len() - 1;
+                    window[..last] == hint[..last]
+                        && (window[last] == hint[last]
+                            || window[last] == format!("{}s", hint[last]))
+                })
+            })
+    }
+
+    fn looks_random(&self, token: &str) -> bool {
+        let Some((min_len, min_bits)) = self.entropy else {
+            return false;
+        };
+        token.len() >= min_len
+            && longest_run(token) * 2 >= min_len
+            && !is_ulid(token)
+            && token.chars().any(|c| c.is_ascii_digit())
+            && token.chars().any(|c| c.is_ascii_alphabetic())
+            && entropy_bits(token) >= min_bits
+    }
+
+    pub(crate) fn text(&self, raw: &str) -> String {
+        let mut out = raw.to_string();
+        if !self.enabled {
+            return out;
+        }
+        for pattern in &self.patterns {
+            if !pattern.is_match(&out) {
+                continue;
+            }
+            out = pattern
+                .replace_all(&out, |caps: &regex::Captures| match caps.get(1) {
+                    Some(group) => {
+                        let whole = caps.get(0).expect("group 0 always matches");
+                        let start = group.start() - whole.start();
+                        let end = group.end() - whole.start();
+                        let text = whole.as_str();
+                        format!("{}{REDACTED}{}", &text[..start], &text[end..])
+                    }
+                    None => REDACTED.to_string(),
+                })
+                .into_owned();
+        }
+        if self.entropy.is_some() {
+            let mut scrubbed = String::with_capacity(out.len());
+            let mut token = String::new();
+            for c in out.chars().chain(std::iter::once('\0')) {
+                if is_token_char(c) {
+                    token.push(c);
+                    continue;
+                }
+                if self.looks_random(&token) {
+                    scrubbed.push_str(REDACTED);
+                } else {
+                    scrubbed.push_str(&token);
+                }
+                token.clear();
+                if c != '\0' {
+                    scrubbed.push(c);
+                }
+            }
+            out = scrubbed;
+        }
+        out
+    }
+
+    pub(crate) fn value(&self, value: &mut Value) {
+        if !self.enabled {
+            return;
+        }
+        match value {
+            Value::Object(map) => {
+                for (key, v) in map.iter_mut() {
+                    let words = key_words(key);
+                    if identifier_key(&words) {
+                        continue;
+                    }
+                    // counts such as `prompt_tokens` are not secrets
+                    if self.secret_key(&words) && (v.is_string() || v.is_array() || v.is_object()) {
+                        *v = Value::String(REDACTED.to_string());
+                    } else {
+                        self.value(v);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|v| self.value(v)),
+            Value::String(s) => *s = self.text(s),
+            _ => {}
+        }
+    }
+
+    pub(crate) fn redacted(&self, value: &Value) -> Value {
+        let mut out = value.clone();
+        self.value(&mut out);
+        out
+    }
+
+    pub(crate) fn result(&self, result: &mut CommandResult) {
+        if let Some(value) = result.result.as_mut() {
+            self.value(value);
+        }
+        if let Some(err) = result.error.as_mut() {
+            err.message = self.text(&err.message);
+            if let Some(details) = err.details.as_mut() {
+                self.value(details);
+            }
+        }
+    }
+
+    pub(crate) fn event(&self, event: &mut ProtocolEvent) {
+        if !self.enabled {
+            return;
+        }
+        if let Some(msg) = event.msg.as_mut() {
+            *msg = self.text(msg);
+        }
+        self.value(&mut event.data);
+        if let Some(compliance) = event.compliance.as_mut() {
+            self.value(compliance);
+        }
+    }
+}
+
+/// Redactor for `config`, rebuilt only when the config changes.
+pub(crate) fn for_config(config: &RedactionConfig) -> Arc<Redactor> {
+    let mut slot = CURRENT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached, redactor)) = slot.as_ref() {
+        if cached == config {
+            return Arc::clone(redactor);
+        }
+    }
+    let redactor = Arc::new(Redactor::new(config));
+    *slot = Some((config.clone(), Arc::clone(&redactor)));
+    redactor
+}
+
+/// Redactor for the yx.toml on disk.
+pub(crate) fn current() -> Arc<Redactor> {
+    for_config(&load_config().unwrap_or_default().redaction)
+}
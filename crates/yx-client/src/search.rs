@@ -0,0 +1,371 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Mutex;
+use yx_protocol::Severity;
+
+use crate::event_store::{self, glob_match, StoredEvent};
+
+pub const DEFAULT_SEARCH_LIMIT: usize = 100;
+/// Docs kept per workspace; past this the oldest half is dropped.
+const MAX_INDEXED_DOCS: usize = 100_000;
+const SNIPPET_CHARS: usize = 160;
+
+static INDEX: Mutex<Option<HashMap<String, WsIndex>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchRequest {
+    /// Terms, `"quoted phrases"` and `topic:`, `severity:`, `ws:` filters.
+    pub q: String,
+    pub since_ms: Option<u64>,
+    pub until_ms: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Highlight {
+    pub field: String,
+    pub snippet: String,
+    /// `[start, end)` char offsets of the matches within `snippet`.
+    pub ranges: Vec<[usize; 2]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub ws: String,
+    pub id: u64,
+    pub ts_ms: u64,
+    pub topic: String,
+    pub severity: Severity,
+    pub score: usize,
+    pub highlights: Vec<Highlight>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub ws: String,
+    pub total: usize,
+    /// Newest first.
+    pub hits: Vec<SearchHit>,
+}
+
+struct Doc {
+    ts_ms: u64,
+    topic: String,
+    severity: Severity,
+    fields: Vec<(&'static str, String)>,
+}
+
+#[derive(Default)]
+struct WsIndex {
+    docs: BTreeMap<u64, Doc>,
+    postings: HashMap<String, BTreeSet<u64>>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ParsedQuery {
+    terms: Vec<String>,
+    phrases: Vec<Vec<String>>,
+    topic: Option<String>,
+    severity: Option<Severity>,
+    ws: Option<String>,
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Flattens JSON into the text worth searching: string values and keys.
+fn flatten(value: &Value, out: &mut String) {
+    match value {
+        Value::String(s) => {
+            out.push_str(s);
+            out.push(' ');
+        }
+        Value::Object(map) => {
+            for (key, v) in map {
+                out.push_str(key);
+                out.push(' ');
+                flatten(v, out);
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|v| flatten(v, out)),
+        Value::Null => {}
+        other => {
+            out.push_str(&other.to_string());
+            out.push(' ');
+        }
+    }
+}
+
+fn doc_for(stored: &StoredEvent) -> Doc {
+    let event = &stored.event;
+    let mut fields = Vec::new();
+    if let Some(msg) = &event.msg {
+        fields.push(("msg", msg.clone()));
+    }
+    if let Some(line) = event.data.get("line").and_then(Value::as_str) {
+        fields.push(("log", line.to_string()));
+    }
+    let mut text = String::new();
+    flatten(&event.data, &mut text);
+    if !text.trim().is_empty() {
+        fields.push(("data", text.trim_end().to_string()));
+    }
+    Doc {
+        ts_ms: event.ts_ms,
+        topic: event.topic.clone(),
+        severity: event.severity.clone(),
+        fields,
+    }
+}
+
+impl WsIndex {
+    fn load(ws: &str) -> WsIndex {
+        let mut index = WsIndex::default();
+        for stored in event_store::scan(ws) {
+            index.insert(&stored);
+        }
+        index
+    }
+
+    fn insert(&mut self, stored: &StoredEvent) {
+        let doc = doc_for(stored);
+        for (_, text) in &doc.fields {
+            for term in tokenize(text) {
+                self.postings.entry(term).or_default().insert(stored.id);
+            }
+        }
+        self.docs.insert(stored.id, doc);
+        if self.docs.len() > MAX_INDEXED_DOCS {
+            self.compact();
+        }
+    }
+
+    fn compact(&mut self) {
+        let keep = self.docs.split_off(
+            &self
+                .docs
+                .keys()
+                .nth(self.docs.len() / 2)
+                .copied()
+                .unwrap_or(0),
+        );
+        self.docs = keep;
+        self.postings.clear();
+        for (id, doc) in &self.docs {
+            for (_, text) in &doc.fields {
+                for term in tokenize(text) {
+                    self.postings.entry(term).or_default().insert(*id);
+                }
+            }
+        }
+    }
+
+    /// Forgets events older than `oldest`, dropped from the store since
+    /// they were indexed.
+    fn prune_before(&mut self, oldest: u64) {
+        if self
+            .docs
+            .first_key_value()
+            .is_none_or(|(id, _)| *id >= oldest)
+        {
+            return;
+        }
+        self.docs = self.docs.split_off(&oldest);
+        self.postings.retain(|_, ids| {
+            *ids = ids.split_off(&oldest);
+            !ids.is_empty()
+        });
+    }
+
+    /// Candidate ids holding every term of the query.
+    fn candidates(&self, query: &ParsedQuery) -> Option<BTreeSet<u64>> {
+        let mut terms = query.terms.iter().chain(query.phrases.iter().flatten());
+        let first = terms.next()?;
+        let mut out = self.postings.get(first).cloned().unwrap_or_default();
+        for term in terms {
+            match self.postings.get(term) {
+                Some(ids) => out.retain(|id| ids.contains(id)),
+                None => return Some(BTreeSet::new()),
+            }
+        }
+        Some(out)
+    }
+}
+
+pub(crate) fn parse_query(q: &str) -> ParsedQuery {
+    let mut parsed = ParsedQuery::default();
+    let mut rest = q.trim();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            let phrase = tokenize(&quoted[..end]);
+            if !phrase.is_empty() {
+                parsed.phrases.push(phrase);
+            }
+            rest = quoted.get(end + 1..).unwrap_or("").trim_start();
+            continue;
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..end];
+        rest = rest[end..].trim_start();
+        match word.split_once(':') {
+            Some(("topic", v)) if !v.is_empty() => parsed.topic = Some(v.to_string()),
+            Some(("severity", v)) if !v.is_empty() => parsed.severity = Some(Severity::parse(v)),
+            Some(("ws", v)) if !v.is_empty() => parsed.ws = Some(v.to_string()),
+            _ => parsed.terms.extend(tokenize(word)),
+        }
+    }
+    parsed
+}
+
+/// Char ranges of every term and phrase occurrence in `text`.
+pub(crate) fn match_ranges(text: &str, query: &ParsedQuery) -> Vec<[usize; 2]> {
+    // token spans in char offsets
+    let mut spans = Vec::new();
+    let mut start = None;
+    let chars: Vec<char> = text.chars().collect();
+    for (i, c) in chars.iter().enumerate() {
+        let word = c.is_alphanumeric() || *c == '_';
+        match (word, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, chars.len()));
+    }
+    let token = |(s, e): (usize, usize)| chars[s..e].iter().collect::<String>().to_lowercase();
+    let tokens: Vec<String> = spans.iter().map(|span| token(*span)).collect();
+
+    let mut ranges = Vec::new();
+    for (i, tok) in tokens.iter().enumerate() {
+        if query.terms.contains(tok) {
+            ranges.push([spans[i].0, spans[i].1]);
+        }
+    }
+    for phrase in &query.phrases {
+        for i in 0..tokens.len().saturating_sub(phrase.len() - 1) {
+            if tokens[i..i + phrase.len()] == phrase[..] {
+                ranges.push([spans[i].0, spans[i + phrase.len() - 1].1]);
+            }
+        }
+    }
+    ranges.sort_unstable();
+    ranges
+}
+
+fn phrases_match(doc: &Doc, query: &ParsedQuery) -> bool {
+    query.phrases.iter().all(|phrase| {
+        doc.fields.iter().any(|(_, text)| {
+            let tokens = tokenize(text);
+            tokens.windows(phrase.len()).any(|w| w == &phrase[..])
+        })
+    })
+}
+
+/// Whether `text` holds every term and phrase of `query`; filters are not
+/// checked.
+pub(crate) fn text_matches(text: &str, query: &ParsedQuery) -> bool {
+    let tokens = tokenize(text);
+    query.terms.iter().all(|t| tokens.contains(t))
+        && query
+            .phrases
+            .iter()
+            .all(|phrase| tokens.windows(phrase.len()).any(|w| w == &phrase[..]))
+}
+
+pub(crate) fn highlight(field: &str, text: &str, ranges: &[[usize; 2]]) -> Highlight {
+    let chars: Vec<char> = text.chars().collect();
+    let first = ranges.first().map_or(0, |r| r[0]);
+    let start = first.saturating_sub(SNIPPET_CHARS / 4);
+    let end = (start + SNIPPET_CHARS).min(chars.len());
+    Highlight {
+        field: field.to_string(),
+        snippet: chars[start..end].iter().collect(),
+        ranges: ranges
+            .iter()
+            .filter(|r| r[0] >= start && r[1] <= end)
+            .map(|r| [r[0] - start, r[1] - start])
+            .collect(),
+    }
+}
+
+pub(crate) fn index_event(stored: &StoredEvent) {
+    let mut slot = INDEX.lock().unwrap_or_else(|e| e.into_inner());
+    // unloaded workspaces pick the event up from the store on first search
+    if let Some(index) = slot.as_mut().and_then(|m| m.get_mut(&stored.ws)) {
+        index.insert(stored);
+    }
+}
+
+pub(crate) fn search(default_ws: &str, request: &SearchRequest) -> SearchResults {
+    let query = parse_query(&request.q);
+    let ws = query.ws.clone().unwrap_or_else(|| default_ws.to_string());
+    let mut slot = INDEX.lock().unwrap_or_else(|e| e.into_inner());
+    let index = slot
+        .get_or_insert_with(HashMap::new)
+        .entry(ws.clone())
+        .or_insert_with(|| WsIndex::load(&ws));
+    // the store drops whole segments once it is over its size cap
+    index.prune_before(event_store::oldest_id(&ws).unwrap_or(u64::MAX));
+
+    let filtered = |id: &u64| {
+        let Some(doc) = index.docs.get(id) else {
+            return false;
+        };
+        request.since_ms.is_none_or(|ts| doc.ts_ms >= ts)
+            && request.until_ms.is_none_or(|ts| doc.ts_ms <= ts)
+            && query
+                .topic
+                .as_deref()
+                .is_none_or(|p| glob_match(p, &doc.topic))
+            && query.severity.as_ref().is_none_or(|s| doc.severity == *s)
+            && phrases_match(doc, &query)
+    };
+    let ids: Vec<u64> = match index.candidates(&query) {
+        Some(ids) => ids.into_iter().rev().filter(filtered).collect(),
+        // filters only
+        None => index.docs.keys().rev().copied().filter(filtered).collect(),
+    };
+
+    let limit = request.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+    let hits = ids
+        .iter()
+        .take(limit)
+        .filter_map(|id| {
+            let doc = index.docs.get(id)?;
+            let highlights: Vec<Highlight> = doc
+                .fields
+                .iter()
+                .filter_map(|(field, text)| {
+                    let ranges = match_ranges(text, &query);
+                    (!ranges.is_empty()).then(|| highlight(field, text, &ranges))
+                })
+                .collect();
+            Some(SearchHit {
+                ws: ws.clone(),
+                id: *id,
+                ts_ms: doc.ts_ms,
+                topic: doc.topic.clone(),
+                severity: doc.severity.clone(),
+                score: highlights.iter().map(|h| h.ranges.len()).sum(),
+                highlights,
+            })
+        })
+        .collect();
+    SearchResults {
+        ws,
+        total: ids.len(),
+        hits,
+    }
+}
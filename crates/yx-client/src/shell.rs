@@ -0,0 +1,381 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::time::{Duration, Instant};
+
+use crate::redact::Redactor;
+
+pub const DEFAULT_SHELL_TIMEOUT_MS: u64 = 30_000;
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+/// Output kept per stream in the trace and audit journals.
+const JOURNAL_OUTPUT_BYTES: usize = 64 * 1024;
+const MOCK_CHUNK_DELAY_MS: u64 = 40;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShellExecRequest {
+    pub cmd: String,
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    pub env: BTreeMap<String, String>,
+    /// Written to the process' stdin, which is then closed.
+    pub stdin: Option<String>,
+    /// Wall time before the process is killed; `DEFAULT_SHELL_TIMEOUT_MS` if unset.
+    pub timeout_ms: Option<u64>,
+    /// stdout and stderr bytes together; later output is dropped.
+    pub max_output_bytes: Option<usize>,
+}
+
+impl ShellExecRequest {
+    pub fn new(cmd: &str) -> Self {
+        ShellExecRequest {
+            cmd: cmd.to_string(),
+            ..ShellExecRequest::default()
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms.unwrap_or(DEFAULT_SHELL_TIMEOUT_MS))
+    }
+
+    pub fn max_output(&self) -> usize {
+        self.max_output_bytes.unwrap_or(DEFAULT_MAX_OUTPUT_BYTES)
+    }
+
+    /// `shell.exec` args as sent to the daemon.
+    pub(crate) fn args_value(&self) -> Value {
+        json!({
+            "cmd": self.cmd,
+            "args": self.args,
+            "cwd": self.cwd,
+            "env": self.env,
+            "stdin": self.stdin,
+            "timeout_ms": self.timeout().as_millis() as u64,
+            "max_output_bytes": self.max_output(),
+            "stream": true,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShellChunk {
+    pub trace_id: String,
+    /// Position in the interleaved output, from 0.
+    pub seq: u64,
+    pub stream: ShellStream,
+    pub data: String,
+    pub ts_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShellExit {
+    pub trace_id: String,
+    /// `None` when the process was killed by a signal or timed out.
+    pub exit_code: Option<i32>,
+    /// Name or number of the signal that ended the process.
+    pub signal: Option<String>,
+    pub timed_out: bool,
+    /// Output went past `max_output_bytes` and the rest was dropped.
+    pub truncated: bool,
+    pub stdout_bytes: usize,
+    pub stderr_bytes: usize,
+    pub duration_ms: u64,
+}
+
+impl ShellExit {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// What `shell_exec_stream` hands its callback: chunks as they arrive, then
+/// the exit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShellOutput {
+    Chunk(ShellChunk),
+    Exit(ShellExit),
+}
+
+/// Bounds, redacts and numbers output on its way to the callback, keeping
+/// the head of each stream for the journals.
+pub(crate) struct Sink<'a> {
+    trace_id: String,
+    redactor: &'a Redactor,
+    max_bytes: usize,
+    seq: u64,
+    stdout_bytes: usize,
+    stderr_bytes: usize,
+    truncated: bool,
+    stdout: String,
+    stderr: String,
+    on_output: &'a mut dyn FnMut(&ShellOutput),
+}
+
+impl<'a> Sink<'a> {
+    pub(crate) fn new(
+        trace_id: &str,
+        request: &ShellExecRequest,
+        redactor: &'a Redactor,
+        on_output: &'a mut dyn FnMut(&ShellOutput),
+    ) -> Self {
+        Sink {
+            trace_id: trace_id.to_string(),
+            redactor,
+            max_bytes: request.max_output(),
+            seq: 0,
+            stdout_bytes: 0,
+            stderr_bytes: 0,
+            truncated: false,
+            stdout: String::new(),
+            stderr: String::new(),
+            on_output,
+        }
+    }
+
+    fn push(&mut self, stream: ShellStream, data: &str) {
+        if self.truncated || data.is_empty() {
+            return;
+        }
+        let room = self.max_bytes - (self.stdout_bytes + self.stderr_bytes);
+        let data = if data.len() > room {
+            self.truncated = true;
+            &data[..floor_char_boundary(data, room)]
+        } else {
+            data
+        };
+        if data.is_empty() {
+            return;
+        }
+        let (bytes, kept) = match stream {
+            ShellStream::Stdout => (&mut self.stdout_bytes, &mut self.stdout),
+            ShellStream::Stderr => (&mut self.stderr_bytes, &mut self.stderr),
+        };
+        *bytes += data.len();
+        let keep = JOURNAL_OUTPUT_BYTES.saturating_sub(kept.len());
+        kept.push_str(&data[..floor_char_boundary(data, keep)]);
+        let data = match self.redactor.redacted(&Value::String(data.to_string())) {
+            Value::String(s) => s,
+            _ => data.to_string(),
+        };
+        let chunk = ShellChunk {
+            trace_id: self.trace_id.clone(),
+            seq: self.seq,
+            stream,
+            data,
+            ts_ms: crate::now_ms(),
+        };
+        self.seq += 1;
+        (self.on_output)(&ShellOutput::Chunk(chunk));
+    }
+
+    /// Reports the exit to the callback and returns it with the journal
+    /// form of the run.
+    pub(crate) fn finish(
+        self,
+        exit_code: Option<i32>,
+        signal: Option<String>,
+        timed_out: bool,
+        started: Instant,
+    ) -> (ShellExit, Value) {
+        let exit = ShellExit {
+            trace_id: self.trace_id,
+            exit_code,
+            signal,
+            timed_out,
+            truncated: self.truncated,
+            stdout_bytes: self.stdout_bytes,
+            stderr_bytes: self.stderr_bytes,
+            duration_ms: started.elapsed().as_millis() as u64,
+        };
+        (self.on_output)(&ShellOutput::Exit(exit.clone()));
+        let mut journal = serde_json::to_value(&exit).unwrap_or_default();
+        journal["stdout"] = Value::String(self.stdout);
+        journal["stderr"] = Value::String(self.stderr);
+        (exit, self.redactor.redacted(&journal))
+    }
+}
+
+fn floor_char_boundary(s: &str, mut at: usize) -> usize {
+    if at >= s.len() {
+        return s.len();
+    }
+    while !s.is_char_boundary(at) {
+        at -= 1;
+    }
+    at
+}
+
+/// What a line from the daemon means for a streaming exec.
+enum WireLine {
+    Chunk(ShellStream, String),
+    Exit {
+        exit_code: Option<i32>,
+        signal: Option<String>,
+        timed_out: bool,
+    },
+    Other,
+}
+
+fn text(value: &Value, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .find_map(|k| value.get(*k).and_then(Value::as_str))
+        .map(str::to_string)
+}
+
+/// Chunks come as `{"type":"stdout","data":..}` or `{"stream":"stderr",
+/// "data":..}`; the exit as `{"type":"exit","exit_code":..,"signal":..}`.
+/// A daemon that does not stream answers with one result holding
+/// `stdout`, `stderr` and `exit_code`, which is split into chunks.
+fn wire_lines(value: &Value) -> Vec<WireLine> {
+    let value = value
+        .get("result")
+        .filter(|r| r.is_object())
+        .unwrap_or(value);
+    let kind = value.get("type").and_then(Value::as_str).unwrap_or("");
+    let stream = match kind {
+        "stdout" | "shell.stdout" => Some(ShellStream::Stdout),
+        "stderr" | "shell.stderr" => Some(ShellStream::Stderr),
+        _ => match value.get("stream").and_then(Value::as_str) {
+            Some("stdout") => Some(ShellStream::Stdout),
+            Some("stderr") => Some(ShellStream::Stderr),
+            _ => None,
+        },
+    };
+    if let Some(stream) = stream {
+        let data = text(value, &["data", "text", "chunk"]).unwrap_or_default();
+        return vec![WireLine::Chunk(stream, data)];
+    }
+    let exit_code = value
+        .get("exit_code")
+        .or_else(|| value.get("code"))
+        .and_then(Value::as_i64)
+        .map(|c| c as i32);
+    let signal = value.get("signal").and_then(|s| match s {
+        Value::String(name) => Some(name.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    });
+    let is_exit =
+        matches!(kind, "exit" | "shell.exit" | "done") || exit_code.is_some() || signal.is_some();
+    if !is_exit {
+        return vec![WireLine::Other];
+    }
+    let mut lines = Vec::new();
+    if let Some(out) = text(value, &["stdout"]) {
+        lines.push(WireLine::Chunk(ShellStream::Stdout, out));
+    }
+    if let Some(err) = text(value, &["stderr"]) {
+        lines.push(WireLine::Chunk(ShellStream::Stderr, err));
+    }
+    lines.push(WireLine::Exit {
+        exit_code,
+        signal,
+        timed_out: value
+            .get("timed_out")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+    });
+    lines
+}
+
+/// Reads the daemon's answer to a streaming `shell.exec` until the exit or
+/// `deadline`. Output past the limit is read and dropped so the exit status
+/// still arrives.
+#[cfg(unix)]
+pub(crate) fn read_stream(
+    reader: &mut std::io::BufReader<std::os::unix::net::UnixStream>,
+    deadline: Instant,
+    sink: &mut Sink,
+) -> Result<(Option<i32>, Option<String>, bool)> {
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok((None, None, true));
+        }
+        reader.get_ref().set_read_timeout(Some(left)).ok();
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) => return Err(anyhow!("daemon closed the stream before the exit")),
+            Ok(_) => {}
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok((None, None, true));
+            }
+            Err(e) => return Err(e.into()),
+        }
+        let Ok(value) = serde_json::from_str::<Value>(line.trim_end()) else {
+            continue;
+        };
+        if value.get("type").and_then(Value::as_str) == Some("error") {
+            let message = text(&value, &["message"]).unwrap_or_else(|| "daemon error".into());
+            return Err(anyhow!(message));
+        }
+        for wire in wire_lines(&value) {
+            match wire {
+                WireLine::Chunk(stream, data) => sink.push(stream, &data),
+                WireLine::Exit {
+                    exit_code,
+                    signal,
+                    timed_out,
+                } => return Ok((exit_code, signal, timed_out)),
+                WireLine::Other => {}
+            }
+        }
+    }
+}
+
+/// Mock run: echoes the command line, then stdin, with a short pause
+/// between chunks, and respects the timeout like a real process would.
+pub(crate) fn mock_stream(
+    request: &ShellExecRequest,
+    deadline: Instant,
+    sink: &mut Sink,
+) -> (Option<i32>, Option<String>, bool) {
+    let mut chunks = vec![(
+        ShellStream::Stdout,
+        format!(
+            "$ {}\n",
+            std::iter::once(&request.cmd)
+                .chain(&request.args)
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+    )];
+    if let Some(cwd) = &request.cwd {
+        chunks.push((ShellStream::Stderr, format!("mock: cwd {cwd}\n")));
+    }
+    for key in request.env.keys() {
+        chunks.push((ShellStream::Stderr, format!("mock: env {key}\n")));
+    }
+    if let Some(stdin) = &request.stdin {
+        chunks.extend(
+            stdin
+                .split_inclusive('\n')
+                .map(|line| (ShellStream::Stdout, line.to_string())),
+        );
+    }
+    chunks.push((ShellStream::Stdout, "mock shell ok\n".to_string()));
+    for (stream, data) in chunks {
+        std::thread::sleep(Duration::from_millis(MOCK_CHUNK_DELAY_MS));
+        if Instant::now() >= deadline {
+            return (None, Some("SIGKILL".to_string()), true);
+        }
+        sink.push(stream, &data);
+    }
+    (Some(0), None, false)
+}
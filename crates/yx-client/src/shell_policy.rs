@@ -0,0 +1,422 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::{Component, Path, PathBuf};
+
+use crate::arming::ArmingSession;
+use crate::config::{ShellPolicy, ShellPolicyConfig, ShellRule};
+use crate::event_store::glob_match;
+
+/// A word that runs the rest of the line as the real command.
+struct Wrapper {
+    name: &'static str,
+    /// Options that take the next word as their value.
+    valued: &'static [&'static str],
+    /// Operands before the command, such as the duration of `timeout`.
+    operands: usize,
+}
+
+const WRAPPERS: [Wrapper; 14] = [
+    Wrapper {
+        name: "sudo",
+        valued: &[
+            "-u",
+            "-g",
+            "-h",
+            "-p",
+            "-C",
+            "-D",
+            "-R",
+            "-r",
+            "-t",
+            "-T",
+            "-U",
+            "--user",
+            "--group",
+            "--host",
+            "--prompt",
+            "--close-from",
+            "--chdir",
+            "--chroot",
+            "--role",
+            "--type",
+            "--command-timeout",
+            "--other-user",
+        ],
+        operands: 0,
+    },
+    Wrapper {
+        name: "doas",
+        valued: &["-u", "-C"],
+        operands: 0,
+    },
+    Wrapper {
+        name: "env",
+        valued: &["-u", "-C", "-S", "--unset", "--chdir", "--split-string"],
+        operands: 0,
+    },
+    Wrapper {
+        name: "exec",
+        valued: &["-a"],
+        operands: 0,
+    },
+    Wrapper {
+        name: "nohup",
+        valued: &[],
+        operands: 0,
+    },
+    Wrapper {
+        name: "time",
+        valued: &["-f", "-o", "--format", "--output"],
+        operands: 0,
+    },
+    Wrapper {
+        name: "nice",
+        valued: &["-n", "--adjustment"],
+        operands: 0,
+    },
+    Wrapper {
+        name: "ionice",
+        valued: &["-c", "-n", "--class", "--classdata"],
+        operands: 0,
+    },
+    Wrapper {
+        name: "command",
+        valued: &[],
+        operands: 0,
+    },
+    Wrapper {
+        name: "setsid",
+        valued: &[],
+        operands: 0,
+    },
+    Wrapper {
+        name: "stdbuf",
+        valued: &["-i", "-o", "-e", "--input", "--output", "--error"],
+        operands: 0,
+    },
+    Wrapper {
+        name: "timeout",
+        valued: &["-s", "-k", "--signal", "--kill-after"],
+        operands: 1,
+    },
+    Wrapper {
+        name: "xargs",
+        valued: &[
+            "-a",
+            "-d",
+            "-E",
+            "-I",
+            "-L",
+            "-n",
+            "-P",
+            "-s",
+            "--arg-file",
+            "--delimiter",
+            "--max-args",
+            "--max-procs",
+            "--max-chars",
+        ],
+        operands: 0,
+    },
+    Wrapper {
+        name: "chroot",
+        valued: &["--userspec", "--groups"],
+        operands: 1,
+    },
+];
+const SHELLS: [&str; 5] = ["sh", "bash", "zsh", "dash", "ksh"];
+
+/// Why a `shell.exec` was refused.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PolicyDenial {
+    /// `deny`, `allow`, `arm` or `cwd`.
+    pub rule: &'static str,
+    pub command: String,
+    pub pattern: String,
+    pub reason: Option<String>,
+}
+
+impl PolicyDenial {
+    pub(crate) fn message(&self) -> String {
+        let why = match self.rule {
+            "deny" => format!("'{}' matches deny rule '{}'", self.command, self.pattern),
+            "allow" => format!("'{}' is not on the allow list", self.command),
+            "arm" => format!(
+                "'{}' needs an arming session scoped to '{}'",
+                self.command, self.pattern
+            ),
+            _ if self.pattern.is_empty() => format!(
+                "cwd '{}' must be an absolute path without '..'",
+                self.command
+            ),
+            _ => format!("cwd '{}' is under '{}'", self.command, self.pattern),
+        };
+        match &self.reason {
+            Some(reason) => format!("shell policy: {why}: {reason}"),
+            None => format!("shell policy: {why}"),
+        }
+    }
+
+    pub(crate) fn details(&self, ws: &str) -> Value {
+        json!({
+            "workspace": ws,
+            "rule": self.rule,
+            "command": self.command,
+            "pattern": self.pattern,
+            "reason": self.reason,
+        })
+    }
+}
+
+/// One command of a line: the program and its arguments.
+struct Invocation {
+    program: String,
+    args: Vec<String>,
+    /// A wrapper such as `sudo`, checked against deny rules only.
+    wrapper: bool,
+}
+
+/// Words of `line` without quotes and redirections such as `2>/dev/null` or
+/// `> out.log`.
+fn words(line: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut raw = line.split_whitespace();
+    while let Some(word) = raw.next() {
+        let op = word.trim_start_matches(|c: char| c.is_ascii_digit());
+        if op.starts_with(['>', '<']) {
+            if op.trim_start_matches(['>', '<']).is_empty() {
+                raw.next();
+            }
+            continue;
+        }
+        let word = word.trim_matches(['\'', '"']);
+        if !word.is_empty() {
+            out.push(word.to_string());
+        }
+    }
+    out
+}
+
+/// Takes the options of `wrapper` and the values they consume off `words`,
+/// then its operands. Returns the command lines hidden in option values,
+/// such as `env -S '...'`.
+fn skip_options(
+    wrapper: &Wrapper,
+    words: &mut std::iter::Peekable<std::vec::IntoIter<String>>,
+    taken: &mut Vec<String>,
+) -> Vec<String> {
+    let mut nested = Vec::new();
+    while let Some(word) = words.next_if(|w| w.starts_with('-') && w != "-") {
+        taken.push(word.clone());
+        if word == "--" {
+            break;
+        }
+        let value = if let Some(long) = word.strip_prefix("--") {
+            match long.split_once('=') {
+                Some((_, value)) => Some(value.to_string()),
+                None if wrapper.valued.contains(&word.as_str()) => words.next(),
+                None => None,
+            }
+        } else {
+            // a cluster such as `-Eu root` or `-uroot`: the first valued
+            // option takes the rest of the word, or the next word
+            let flags: Vec<char> = word.chars().skip(1).collect();
+            let at = flags
+                .iter()
+                .position(|c| wrapper.valued.contains(&format!("-{c}").as_str()));
+            match at {
+                Some(at) if at + 1 < flags.len() => Some(flags[at + 1..].iter().collect()),
+                Some(_) => words.next(),
+                None => None,
+            }
+        };
+        if let Some(value) = value {
+            taken.push(value.clone());
+            if wrapper.name == "env" && (word.starts_with("-S") || word.starts_with("--split")) {
+                nested.push(value);
+            }
+        }
+    }
+    for _ in 0..wrapper.operands {
+        taken.extend(words.next());
+    }
+    nested
+}
+
+/// The string a shell runs with `-c`, also in clusters like `-lc` or `-ec`.
+fn shell_body(args: &[String]) -> Option<String> {
+    let at = args.iter().position(|a| {
+        a.len() > 1 && a.starts_with('-') && !a.starts_with("--") && a.contains('c')
+    })?;
+    Some(args[at + 1..].join(" "))
+}
+
+/// The commands `cmd args...` would run: split on `;`, `&&`, `||`, `|` and
+/// newlines, past wrappers like `sudo` with their options and `VAR=value`
+/// prefixes, and into `sh -c '...'` strings. Wrappers are listed as well.
+fn invocations(line: &str, out: &mut Vec<Invocation>) {
+    // `2>&1` is a redirection, not a background `&`
+    let line = line.replace(">&", ">").replace("<&", "<");
+    for segment in line.split([';', '|', '&', '\n', '(', ')', '`']) {
+        let mut words = words(segment).into_iter().peekable();
+        loop {
+            if words
+                .next_if(|w| w.contains('=') && !w.starts_with('-'))
+                .is_some()
+            {
+                continue;
+            }
+            let Some(wrapper) = words
+                .peek()
+                .and_then(|w| WRAPPERS.iter().find(|x| x.name == file_name(w)))
+            else {
+                break;
+            };
+            let program = words.next().unwrap_or_default();
+            let mut args = Vec::new();
+            for nested in skip_options(wrapper, &mut words, &mut args) {
+                invocations(&nested, out);
+            }
+            args.extend(words.clone());
+            out.push(Invocation {
+                program,
+                args,
+                wrapper: true,
+            });
+        }
+        let Some(program) = words.next() else {
+            continue;
+        };
+        let args: Vec<String> = words.collect();
+        if SHELLS.contains(&file_name(&program)) {
+            if let Some(body) = shell_body(&args) {
+                invocations(&body, out);
+            }
+        }
+        out.push(Invocation {
+            program,
+            args,
+            wrapper: false,
+        });
+    }
+}
+
+fn file_name(program: &str) -> &str {
+    program.rsplit('/').next().unwrap_or(program)
+}
+
+fn matches(rule: &ShellRule, invocation: &Invocation) -> bool {
+    let program = &invocation.program;
+    if !glob_match(&rule.cmd, program) && !glob_match(&rule.cmd, file_name(program)) {
+        return false;
+    }
+    match &rule.args {
+        Some(pattern) => Regex::new(pattern)
+            .map(|re| re.is_match(&invocation.args.join(" ")))
+            .unwrap_or(false),
+        None => true,
+    }
+}
+
+/// `path` with `.` and `..` resolved lexically.
+fn normalize(path: &str) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::CurDir => {}
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Global rules plus those of every workspace section matching `ws`.
+fn effective(config: &ShellPolicyConfig, ws: &str) -> ShellPolicy {
+    let mut policy = config.all.clone();
+    for (pattern, extra) in &config.workspaces {
+        if glob_match(pattern, ws) {
+            policy.allow.extend(extra.allow.iter().cloned());
+            policy.deny.extend(extra.deny.iter().cloned());
+            policy.arm.extend(extra.arm.iter().cloned());
+            policy
+                .forbidden_cwd
+                .extend(extra.forbidden_cwd.iter().cloned());
+        }
+    }
+    policy
+}
+
+fn denial(
+    rule: &'static str,
+    command: &str,
+    pattern: &str,
+    reason: Option<&String>,
+) -> PolicyDenial {
+    PolicyDenial {
+        rule,
+        command: command.to_string(),
+        pattern: pattern.to_string(),
+        reason: reason.cloned(),
+    }
+}
+
+/// Checks `shell.exec` args against the policy for `ws`: forbidden cwd, then
+/// deny rules, the allow list and arming rules, for every command in the line.
+pub(crate) fn check(
+    config: &ShellPolicyConfig,
+    ws: &str,
+    args: &Value,
+    session: Option<&ArmingSession>,
+) -> Option<PolicyDenial> {
+    let policy = effective(config, ws);
+    if let Some(cwd) = args.get("cwd").and_then(Value::as_str) {
+        // the daemon resolves relative paths against a base YX can't see
+        let raw = Path::new(cwd);
+        if !policy.forbidden_cwd.is_empty()
+            && (raw.is_relative() || raw.components().any(|c| c == Component::ParentDir))
+        {
+            return Some(denial("cwd", cwd, "", None));
+        }
+        let cwd = normalize(cwd);
+        for prefix in &policy.forbidden_cwd {
+            if cwd.starts_with(normalize(prefix)) {
+                return Some(denial("cwd", &cwd.to_string_lossy(), prefix, None));
+            }
+        }
+    }
+    let cmd = args.get("cmd").and_then(Value::as_str).unwrap_or_default();
+    let extra: Vec<&str> = args
+        .get("args")
+        .and_then(Value::as_array)
+        .map(|a| a.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let line = std::iter::once(cmd)
+        .chain(extra)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut commands = Vec::new();
+    invocations(&line, &mut commands);
+    for invocation in &commands {
+        let name = file_name(&invocation.program);
+        if let Some(rule) = policy.deny.iter().find(|r| matches(r, invocation)) {
+            return Some(denial("deny", name, &rule.cmd, rule.reason.as_ref()));
+        }
+        if invocation.wrapper {
+            continue;
+        }
+        if !policy.allow.is_empty() && !policy.allow.iter().any(|r| matches(r, invocation)) {
+            return Some(denial("allow", name, "", None));
+        }
+        if let Some(rule) = policy.arm.iter().find(|r| matches(r, invocation)) {
+            let scope = format!("shell.exec:{name}");
+            if !session.is_some_and(|s| s.covers(&scope)) {
+                return Some(denial("arm", name, &scope, rule.reason.as_ref()));
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use yx_protocol::Event as ProtocolEvent;
+
+use crate::event_store;
+
+const BUCKET_MS: u64 = 10_000;
+const WINDOWS_MS: [u64; 3] = [60_000, 300_000, 3_600_000];
+/// Upper bounds of the lag histogram buckets; the last bucket is unbounded.
+const LAG_BOUNDS_MS: [u64; 8] = [10, 50, 100, 250, 500, 1_000, 5_000, 30_000];
+/// A topic is silent once it has been quiet this many times its usual
+/// interval over the last hour...
+const SILENCE_FACTOR: u64 = 5;
+/// ...and for at least this long.
+const MIN_SILENCE_MS: u64 = 30_000;
+/// Matches seen in the last hour before silence is judged at all.
+const MIN_SILENCE_SAMPLES: u64 = 3;
+
+static STATS: Mutex<Option<HashMap<String, WsStats>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RateWindows {
+    pub count_1m: u64,
+    pub count_5m: u64,
+    pub count_1h: u64,
+    /// Events per second over each window.
+    pub rate_1m: f64,
+    pub rate_5m: f64,
+    pub rate_1h: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopicStats {
+    pub topic: String,
+    pub rates: RateWindows,
+    pub last_seen_ms: u64,
+    /// Quiet for much longer than the topic's usual interval.
+    pub silent: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LagBucket {
+    /// Inclusive upper bound; `None` for the overflow bucket.
+    pub le_ms: Option<u64>,
+    pub count: u64,
+}
+
+/// Receive time minus daemon `ts_ms`; clock skew in the daemon's favour
+/// counts as zero.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LagHistogram {
+    pub count: u64,
+    pub mean_ms: u64,
+    pub max_ms: u64,
+    pub buckets: Vec<LagBucket>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventStats {
+    pub ws: String,
+    pub generated_ms: u64,
+    pub total: RateWindows,
+    /// Busiest first over the last hour.
+    pub by_topic: Vec<TopicStats>,
+    pub by_severity: BTreeMap<String, RateWindows>,
+    pub lag: LagHistogram,
+    pub silent_topics: Vec<String>,
+}
+
+/// Event counts per `BUCKET_MS` slot, oldest first, covering the last hour.
+#[derive(Default)]
+struct Series {
+    buckets: VecDeque<(u64, u64)>,
+    last_seen_ms: u64,
+}
+
+impl Series {
+    fn add(&mut self, now: u64) {
+        let slot = now / BUCKET_MS;
+        match self.buckets.back_mut() {
+            Some((last, count)) if *last == slot => *count += 1,
+            _ => self.buckets.push_back((slot, 1)),
+        }
+        self.last_seen_ms = self.last_seen_ms.max(now);
+        self.prune(now);
+    }
+
+    fn prune(&mut self, now: u64) {
+        let oldest = now.saturating_sub(WINDOWS_MS[2]) / BUCKET_MS;
+        while self.buckets.front().is_some_and(|(slot, _)| *slot < oldest) {
+            self.buckets.pop_front();
+        }
+    }
+
+    fn count_since(&self, now: u64, window_ms: u64) -> u64 {
+        let oldest = now.saturating_sub(window_ms) / BUCKET_MS;
+        self.buckets
+            .iter()
+            .filter(|(slot, _)| *slot >= oldest)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    fn rates(&self, now: u64) -> RateWindows {
+        let [c1, c5, c60] = WINDOWS_MS.map(|w| self.count_since(now, w));
+        let per_sec = |count: u64, window_ms: u64| count as f64 * 1000.0 / window_ms as f64;
+        RateWindows {
+            count_1m: c1,
+            count_5m: c5,
+            count_1h: c60,
+            rate_1m: per_sec(c1, WINDOWS_MS[0]),
+            rate_5m: per_sec(c5, WINDOWS_MS[1]),
+            rate_1h: per_sec(c60, WINDOWS_MS[2]),
+        }
+    }
+
+    fn is_silent(&self, now: u64) -> bool {
+        let count = self.count_since(now, WINDOWS_MS[2]);
+        if count < MIN_SILENCE_SAMPLES {
+            return false;
+        }
+        let interval = WINDOWS_MS[2] / count;
+        let quiet = now.saturating_sub(self.last_seen_ms);
+        quiet >= MIN_SILENCE_MS && quiet > interval * SILENCE_FACTOR
+    }
+}
+
+#[derive(Default)]
+struct WsStats {
+    total: Series,
+    topics: HashMap<String, Series>,
+    severities: HashMap<String, Series>,
+    lag_buckets: [u64; LAG_BOUNDS_MS.len() + 1],
+    lag_count: u64,
+    lag_sum_ms: u64,
+    lag_max_ms: u64,
+}
+
+impl WsStats {
+    /// Seeds from the last hour of the event store.
+    fn load(ws: &str) -> WsStats {
+        let mut stats = WsStats::default();
+        let horizon = crate::now_ms().saturating_sub(WINDOWS_MS[2]);
+        for stored in event_store::scan(ws) {
+            if stored.received_ms >= horizon {
+                stats.record(&stored.event, stored.received_ms);
+            }
+        }
+        stats
+    }
+
+    fn record(&mut self, event: &ProtocolEvent, received_ms: u64) {
+        self.total.add(received_ms);
+        self.topics
+            .entry(event.topic.clone())
+            .or_default()
+            .add(received_ms);
+        self.severities
+            .entry(event.severity.as_str().to_string())
+            .or_default()
+            .add(received_ms);
+
+        let lag = received_ms.saturating_sub(event.ts_ms);
+        let bucket = LAG_BOUNDS_MS
+            .iter()
+            .position(|bound| lag <= *bound)
+            .unwrap_or(LAG_BOUNDS_MS.len());
+        self.lag_buckets[bucket] += 1;
+        self.lag_count += 1;
+        self.lag_sum_ms += lag;
+        self.lag_max_ms = self.lag_max_ms.max(lag);
+    }
+
+    fn snapshot(&mut self, ws: &str, now: u64) -> EventStats {
+        self.topics.values_mut().for_each(|s| s.prune(now));
+        let mut by_topic: Vec<TopicStats> = self
+            .topics
+            .iter()
+            .map(|(topic, series)| TopicStats {
+                topic: topic.clone(),
+                rates: series.rates(now),
+                last_seen_ms: series.last_seen_ms,
+                silent: series.is_silent(now),
+            })
+            .collect();
+        by_topic.sort_by(|a, b| {
+            b.rates
+                .count_1h
+                .cmp(&a.rates.count_1h)
+                .then_with(|| a.topic.cmp(&b.topic))
+        });
+        let silent_topics = by_topic
+            .iter()
+            .filter(|t| t.silent)
+            .map(|t| t.topic.clone())
+            .collect();
+        let buckets = self
+            .lag_buckets
+            .iter()
+            .enumerate()
+            .map(|(idx, count)| LagBucket {
+                le_ms: LAG_BOUNDS_MS.get(idx).copied(),
+                count: *count,
+            })
+            .collect();
+        EventStats {
+            ws: ws.to_string(),
+            generated_ms: now,
+            total: self.total.rates(now),
+            by_topic,
+            by_severity: self
+                .severities
+                .iter()
+                .map(|(sev, series)| (sev.clone(), series.rates(now)))
+                .collect(),
+            lag: LagHistogram {
+                count: self.lag_count,
+                mean_ms: self.lag_sum_ms.checked_div(self.lag_count).unwrap_or(0),
+                max_ms: self.lag_max_ms,
+                buckets,
+            },
+            silent_topics,
+        }
+    }
+}
+
+fn with_ws<T>(ws: &str, f: impl FnOnce(&mut WsStats) -> T) -> T {
+    let mut slot = STATS.lock().unwrap_or_else(|e| e.into_inner());
+    let stats = slot
+        .get_or_insert_with(HashMap::new)
+        .entry(ws.to_string())
+        .or_insert_with(|| WsStats::load(ws));
+    f(stats)
+}
+
+/// Counts a live event; call before it is appended to the store, which seeds
+/// the workspace on first use.
+pub(crate) fn record(ws: &str, event: &ProtocolEvent) {
+    with_ws(ws, |stats| stats.record(event, crate::now_ms()));
+}
+
+pub(crate) fn stats(ws: &str) -> EventStats {
+    with_ws(ws, |stats| stats.snapshot(ws, crate::now_ms()))
+}
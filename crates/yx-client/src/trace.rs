@@ -0,0 +1,217 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use yx_protocol::{CommandResult, Event as ProtocolEvent};
+
+use crate::event_store::{self, StoredEvent};
+use crate::journal;
+
+/// Command records kept per workspace; the file is compacted at twice this.
+const COMMAND_LOG_LIMIT: usize = 5_000;
+/// Keys an event may carry to point back at the command that caused it.
+const CORRELATION_KEYS: [&str; 4] = [
+    "trace_id",
+    "causation_id",
+    "correlation_id",
+    "parent_trace_id",
+];
+
+/// Correlation id to the stored event ids carrying it.
+type TraceIndex = HashMap<String, BTreeSet<u64>>;
+
+/// Size in bytes and lines of each workspace's command log as this process
+/// last left it; also serializes this process's writes to it.
+static COMMAND_LOG_LINES: Mutex<Option<HashMap<String, (u64, usize)>>> = Mutex::new(None);
+static TRACE_INDEX: Mutex<Option<HashMap<String, TraceIndex>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandRecord {
+    pub trace_id: String,
+    pub ws: String,
+    pub name: String,
+    pub args: Value,
+    pub arming: bool,
+    pub requested_ms: u64,
+    pub result: CommandResult,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceEntryKind {
+    Request,
+    Response,
+    Event,
+    Log,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceEntry {
+    pub ts_ms: u64,
+    pub kind: TraceEntryKind,
+    pub summary: String,
+    pub detail: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trace {
+    pub trace_id: String,
+    pub ws: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<CommandRecord>,
+    /// Request, response, events and log lines ordered by time.
+    pub timeline: Vec<TraceEntry>,
+}
+
+fn command_log_path(ws: &str) -> PathBuf {
+    PathBuf::from(crate::ws_data_dir(ws)).join("commands.ndjson")
+}
+
+fn read_command_log(ws: &str) -> Vec<CommandRecord> {
+    fs::read_to_string(command_log_path(ws))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+pub(crate) fn record_command(record: &CommandRecord) -> Result<()> {
+    let mut counts = COMMAND_LOG_LINES.lock().unwrap_or_else(|e| e.into_inner());
+    let path = command_log_path(&record.ws);
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    journal::locked(&path, || {
+        let bytes = fs::metadata(&path).map_or(0, |m| m.len());
+        let known = counts
+            .get_or_insert_with(HashMap::new)
+            .entry(record.ws.clone())
+            .or_insert((u64::MAX, 0));
+        // the desktop app and the CLI both append: recount after the other did
+        if known.0 != bytes {
+            *known = (
+                bytes,
+                fs::read_to_string(&path).map_or(0, |c| c.lines().count()),
+            );
+        }
+        journal::append(&path, &line)?;
+        *known = (known.0 + line.len() as u64, known.1 + 1);
+
+        if known.1 > COMMAND_LOG_LIMIT * 2 {
+            let records = read_command_log(&record.ws);
+            let kept = &records[records.len().saturating_sub(COMMAND_LOG_LIMIT)..];
+            let mut out = String::new();
+            for r in kept {
+                out.push_str(&serde_json::to_string(r)?);
+                out.push('\n');
+            }
+            journal::replace(&path, &out)?;
+            *known = (out.len() as u64, kept.len());
+        }
+        Ok(())
+    })
+}
+
+/// Every trace or causation id an event carries, at the top level or in `data`.
+pub(crate) fn correlation_ids(event: &ProtocolEvent) -> BTreeSet<String> {
+    let mut ids: BTreeSet<String> = event.trace_id.iter().cloned().collect();
+    for key in CORRELATION_KEYS {
+        if let Some(id) = event.data.get(key).and_then(Value::as_str) {
+            ids.insert(id.to_string());
+        }
+    }
+    ids
+}
+
+fn index_into(index: &mut TraceIndex, stored: &StoredEvent) {
+    for id in correlation_ids(&stored.event) {
+        index.entry(id).or_default().insert(stored.id);
+    }
+}
+
+pub(crate) fn index_event(stored: &StoredEvent) {
+    let mut slot = TRACE_INDEX.lock().unwrap_or_else(|e| e.into_inner());
+    // unloaded workspaces are built from the store on first lookup
+    if let Some(index) = slot.as_mut().and_then(|m| m.get_mut(&stored.ws)) {
+        index_into(index, stored);
+    }
+}
+
+fn correlated_event_ids(ws: &str, trace_id: &str) -> BTreeSet<u64> {
+    let mut slot = TRACE_INDEX.lock().unwrap_or_else(|e| e.into_inner());
+    let index = slot
+        .get_or_insert_with(HashMap::new)
+        .entry(ws.to_string())
+        .or_insert_with(|| {
+            let mut index = HashMap::new();
+            for stored in event_store::scan(ws) {
+                index_into(&mut index, &stored);
+            }
+            index
+        });
+    index.get(trace_id).cloned().unwrap_or_default()
+}
+
+fn is_log(event: &ProtocolEvent) -> bool {
+    event.topic.to_ascii_lowercase().contains("log")
+}
+
+/// Latest recorded command with `trace_id`.
+pub(crate) fn command(ws: &str, trace_id: &str) -> Option<CommandRecord> {
+    read_command_log(ws)
+        .into_iter()
+        .rev()
+        .find(|r| r.trace_id == trace_id)
+}
+
+pub(crate) fn trace(ws: &str, trace_id: &str) -> Trace {
+    let command = command(ws, trace_id);
+    let mut timeline = Vec::new();
+    if let Some(record) = &command {
+        timeline.push(TraceEntry {
+            ts_ms: record.requested_ms,
+            kind: TraceEntryKind::Request,
+            summary: record.name.clone(),
+            detail: json!({ "name": record.name, "args": record.args, "arming": record.arming }),
+        });
+        timeline.push(TraceEntry {
+            ts_ms: record.result.ts_ms,
+            kind: TraceEntryKind::Response,
+            summary: match &record.result.error {
+                Some(err) => format!("{}: {}", err.code, err.message),
+                None => "ok".to_string(),
+            },
+            detail: serde_json::to_value(&record.result).unwrap_or(Value::Null),
+        });
+    }
+
+    let ids = correlated_event_ids(ws, trace_id);
+    if let Some(first) = ids.first() {
+        let events = event_store::scan(ws)
+            .into_iter()
+            .filter(|e| e.id >= *first && ids.contains(&e.id));
+        for stored in events {
+            let event = &stored.event;
+            timeline.push(TraceEntry {
+                ts_ms: event.ts_ms,
+                kind: if is_log(event) {
+                    TraceEntryKind::Log
+                } else {
+                    TraceEntryKind::Event
+                },
+                summary: event.msg.clone().unwrap_or_else(|| event.topic.clone()),
+                detail: serde_json::to_value(&stored).unwrap_or(Value::Null),
+            });
+        }
+    }
+    timeline.sort_by_key(|e| e.ts_ms);
+
+    Trace {
+        trace_id: trace_id.to_string(),
+        ws: ws.to_string(),
+        command,
+        timeline,
+    }
+}
@@ -0,0 +1,290 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use yx_protocol::ChatUsage;
+
+use crate::config::{PriceRule, UsageConfig};
+use crate::event_store::glob_match;
+use crate::journal;
+
+const USAGE_HISTORY_LIMIT: usize = 20_000;
+
+static USAGE: Mutex<Option<HashMap<String, Vec<UsageRecord>>>> = Mutex::new(None);
+
+/// One `chat.send` round trip as journalled per workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub ts_ms: u64,
+    pub ws: String,
+    pub session_id: String,
+    pub trace_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_tokens: Option<u64>,
+    pub latency_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGroupBy {
+    Session,
+    #[default]
+    Provider,
+    Model,
+    Workspace,
+    /// UTC calendar day.
+    Day,
+}
+
+impl FromStr for UsageGroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "session" => Ok(UsageGroupBy::Session),
+            "provider" => Ok(UsageGroupBy::Provider),
+            "model" => Ok(UsageGroupBy::Model),
+            "ws" | "workspace" => Ok(UsageGroupBy::Workspace),
+            "day" => Ok(UsageGroupBy::Day),
+            other => Err(format!(
+                "unknown grouping '{other}' (session, provider, model, ws, day)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageQuery {
+    /// Workspace to report on; the selected one if unset.
+    pub ws: Option<String>,
+    /// Every workspace with recorded usage, ignoring `ws`.
+    pub all_workspaces: bool,
+    pub since_ms: Option<u64>,
+    pub until_ms: Option<u64>,
+    pub session_id: Option<String>,
+    pub group_by: UsageGroupBy,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageRow {
+    pub key: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    /// Requests the daemon reported no token counts for.
+    pub unreported: u64,
+    pub avg_latency_ms: u64,
+    /// Cost of the priced requests; `None` when none matched a price rule.
+    pub cost: Option<f64>,
+    /// Requests with token counts but no matching price rule.
+    pub unpriced: u64,
+    #[serde(skip)]
+    latency_sum_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub currency: String,
+    pub group_by: UsageGroupBy,
+    pub workspaces: Vec<String>,
+    pub totals: UsageRow,
+    /// Most tokens first; newest first when grouped by day.
+    pub rows: Vec<UsageRow>,
+}
+
+fn journal_path(ws: &str) -> PathBuf {
+    PathBuf::from(crate::ws_data_dir(ws)).join("usage.ndjson")
+}
+
+fn read_journal(ws: &str) -> Vec<UsageRecord> {
+    fs::read_to_string(journal_path(ws))
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Appends `records`, or replaces the journal with them when `rewrite` is
+/// set; callers hold the journal lock.
+fn write_journal(ws: &str, records: &[UsageRecord], rewrite: bool) -> Result<()> {
+    let mut out = String::new();
+    for record in records {
+        out.push_str(&serde_json::to_string(record)?);
+        out.push('\n');
+    }
+    let path = journal_path(ws);
+    if rewrite {
+        journal::replace(&path, &out)
+    } else {
+        journal::append(&path, &out)
+    }
+}
+
+fn with_ws<T>(ws: &str, f: impl FnOnce(&mut Vec<UsageRecord>) -> T) -> T {
+    let mut slot = USAGE.lock().unwrap_or_else(|e| e.into_inner());
+    let records = slot
+        .get_or_insert_with(HashMap::new)
+        .entry(ws.to_string())
+        .or_insert_with(|| read_journal(ws));
+    f(records)
+}
+
+pub(crate) fn record(record: UsageRecord) -> Result<()> {
+    let ws = record.ws.clone();
+    with_ws(&ws, |records| {
+        journal::locked(&journal_path(&ws), || {
+            write_journal(&ws, std::slice::from_ref(&record), false)?;
+            records.push(record);
+            if records.len() > USAGE_HISTORY_LIMIT * 2 {
+                // the desktop app and the CLI both append, so compact what is on disk
+                let mut on_disk = read_journal(&ws);
+                on_disk.drain(..on_disk.len().saturating_sub(USAGE_HISTORY_LIMIT));
+                write_journal(&ws, &on_disk, true)?;
+                *records = on_disk;
+            }
+            Ok(())
+        })
+    })
+}
+
+/// The usage of one reply: counts from the daemon, latency as measured.
+pub(crate) fn usage_of(reported: Option<ChatUsage>, latency_ms: u64) -> ChatUsage {
+    ChatUsage {
+        latency_ms: Some(latency_ms),
+        ..reported.unwrap_or_default()
+    }
+}
+
+/// Workspaces with a usage journal.
+fn recorded_workspaces() -> Vec<String> {
+    let mut out: Vec<String> = fs::read_dir(crate::yx_data_dir())
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.path().join("usage.ndjson").is_file())
+                .filter_map(|e| e.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    out.sort();
+    out
+}
+
+fn price_for<'a>(prices: &'a [PriceRule], record: &UsageRecord) -> Option<&'a PriceRule> {
+    let model = record.model.as_deref()?;
+    prices.iter().find(|rule| {
+        glob_match(&rule.model, model)
+            && rule.provider.as_deref().is_none_or(|p| {
+                record
+                    .provider
+                    .as_deref()
+                    .is_some_and(|id| glob_match(p, id))
+            })
+    })
+}
+
+fn group_key(group_by: UsageGroupBy, record: &UsageRecord) -> String {
+    let or_unknown = |v: &Option<String>| v.clone().unwrap_or_else(|| "(unknown)".to_string());
+    match group_by {
+        UsageGroupBy::Session => record.session_id.clone(),
+        UsageGroupBy::Provider => or_unknown(&record.provider),
+        UsageGroupBy::Model => or_unknown(&record.model),
+        UsageGroupBy::Workspace => record.ws.clone(),
+        UsageGroupBy::Day => crate::utc_date(record.ts_ms),
+    }
+}
+
+impl UsageRow {
+    fn add(&mut self, record: &UsageRecord, price: Option<&PriceRule>) {
+        self.requests += 1;
+        self.latency_sum_ms += record.latency_ms;
+        self.avg_latency_ms = self.latency_sum_ms / self.requests;
+        if record.prompt_tokens.is_none() && record.completion_tokens.is_none() {
+            self.unreported += 1;
+            return;
+        }
+        let prompt = record.prompt_tokens.unwrap_or(0);
+        let completion = record.completion_tokens.unwrap_or(0);
+        self.prompt_tokens += prompt;
+        self.completion_tokens += completion;
+        self.total_tokens += prompt + completion;
+        match price {
+            Some(rule) => {
+                let cost = (prompt as f64 * rule.prompt_per_mtok
+                    + completion as f64 * rule.completion_per_mtok)
+                    / 1_000_000.0;
+                self.cost = Some(self.cost.unwrap_or(0.0) + cost);
+            }
+            None => self.unpriced += 1,
+        }
+    }
+}
+
+/// Usage matching `query` from the journal of `ws`, or of every workspace,
+/// priced with the current `config`.
+pub(crate) fn report(ws: &str, query: &UsageQuery, config: &UsageConfig) -> UsageReport {
+    let workspaces = if query.all_workspaces {
+        recorded_workspaces()
+    } else {
+        vec![query.ws.clone().unwrap_or_else(|| ws.to_string())]
+    };
+    let mut totals = UsageRow {
+        key: "total".to_string(),
+        ..UsageRow::default()
+    };
+    let mut rows: BTreeMap<String, UsageRow> = BTreeMap::new();
+    for ws in &workspaces {
+        with_ws(ws, |records| {
+            let matching = records.iter().filter(|r| {
+                query.since_ms.is_none_or(|since| r.ts_ms >= since)
+                    && query.until_ms.is_none_or(|until| r.ts_ms < until)
+                    && query
+                        .session_id
+                        .as_deref()
+                        .is_none_or(|id| r.session_id == id)
+            });
+            for record in matching {
+                let price = price_for(&config.prices, record);
+                let key = group_key(query.group_by, record);
+                rows.entry(key.clone())
+                    .or_insert_with(|| UsageRow {
+                        key,
+                        ..UsageRow::default()
+                    })
+                    .add(record, price);
+                totals.add(record, price);
+            }
+        });
+    }
+    let mut rows: Vec<UsageRow> = rows.into_values().collect();
+    if query.group_by == UsageGroupBy::Day {
+        rows.reverse();
+    } else {
+        rows.sort_by(|a, b| {
+            b.total_tokens
+                .cmp(&a.total_tokens)
+                .then(b.requests.cmp(&a.requests))
+        });
+    }
+    UsageReport {
+        currency: config.currency.clone(),
+        group_by: query.group_by,
+        workspaces,
+        totals,
+        rows,
+    }
+}
@@ -1,5 +1,7 @@
-use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Envelope {
@@ -10,6 +12,25 @@ pub struct Envelope {
     pub payload: Value,
 }
 
+/// Operator roles, ordered by privilege.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Operator,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommandRequest {
     pub protocol_version: String,
@@ -18,6 +39,10 @@ pub struct CommandRequest {
     pub name: String,
     pub args: Value,
     pub arming: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,12 +67,930 @@ pub struct CommandResult {
     pub error: Option<CommandError>,
 }
 
+/// Event severities, ordered from least to most severe. Levels the daemon
+/// sends that map to none of these are kept verbatim in `Unknown`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Severity {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Notice,
+    Warn,
+    Error,
+    Critical,
+    Unknown(String),
+}
+
+impl Severity {
+    pub const ALL: [Severity; 7] = [
+        Severity::Trace,
+        Severity::Debug,
+        Severity::Info,
+        Severity::Notice,
+        Severity::Warn,
+        Severity::Error,
+        Severity::Critical,
+    ];
+
+    /// Position in `ALL`; unknown levels rank with `Info`.
+    pub fn rank(&self) -> u8 {
+        match self {
+            Severity::Trace => 0,
+            Severity::Debug => 1,
+            Severity::Info | Severity::Unknown(_) => 2,
+            Severity::Notice => 3,
+            Severity::Warn => 4,
+            Severity::Error => 5,
+            Severity::Critical => 6,
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Severity::Unknown(_))
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Severity::Trace => "trace",
+            Severity::Debug => "debug",
+            Severity::Info => "info",
+            Severity::Notice => "notice",
+            Severity::Warn => "warn",
+            Severity::Error => "error",
+            Severity::Critical => "critical",
+            Severity::Unknown(raw) => raw,
+        }
+    }
+
+    /// Tolerant parse: case-insensitive, accepts the aliases daemons and log
+    /// libraries commonly emit, and never fails.
+    pub fn parse(raw: &str) -> Severity {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "trace" | "trc" | "verbose" => Severity::Trace,
+            "debug" | "dbg" => Severity::Debug,
+            "info" | "inf" | "information" | "informational" => Severity::Info,
+            "notice" | "note" => Severity::Notice,
+            "warn" | "wrn" | "warning" => Severity::Warn,
+            "error" | "err" => Severity::Error,
+            "critical" | "crit" | "fatal" | "alert" | "emerg" | "emergency" | "panic" => {
+                Severity::Critical
+            }
+            _ => Severity::Unknown(raw.to_string()),
+        }
+    }
+}
+
+impl Ord for Severity {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.rank()
+            .cmp(&other.rank())
+            .then_with(|| self.as_str().cmp(other.as_str()))
+    }
+}
+
+impl PartialOrd for Severity {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for Severity {
+    fn from(raw: String) -> Self {
+        Severity::parse(&raw)
+    }
+}
+
+impl From<Severity> for String {
+    fn from(severity: Severity) -> Self {
+        severity.as_str().to_string()
+    }
+}
+
+impl FromStr for Severity {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Severity::parse(s))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "EventRecord")]
 pub struct Event {
     pub topic: String,
-    pub severity: String,
+    pub severity: Severity,
     pub ts_ms: u64,
-    pub payload: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ws: Option<String>,
+    /// Daemon sequence number within the workspace stream.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub msg: Option<String>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub data: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compliance: Option<Value>,
+    /// Trace of the command that caused the event, not the event's own id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+}
+
+/// Deserialization form of `Event`, also accepting records written before the
+/// fields were typed, when they lived in a `payload` object.
+#[derive(Deserialize)]
+struct EventRecord {
+    topic: String,
+    #[serde(default)]
+    severity: Severity,
+    ts_ms: u64,
+    #[serde(default)]
+    event_id: Option<String>,
+    #[serde(default)]
+    ws: Option<String>,
+    #[serde(default)]
+    seq: Option<u64>,
+    #[serde(default)]
+    msg: Option<String>,
+    #[serde(default)]
+    data: Value,
+    #[serde(default)]
+    compliance: Option<Value>,
+    #[serde(default)]
+    trace_id: Option<String>,
+    #[serde(default)]
+    payload: Value,
+}
+
+impl From<EventRecord> for Event {
+    fn from(record: EventRecord) -> Self {
+        let legacy = |key: &str| record.payload.get(key).filter(|v| !v.is_null()).cloned();
+        let legacy_str = |key: &str| legacy(key).and_then(|v| v.as_str().map(str::to_string));
+        Event {
+            event_id: record.event_id.or_else(|| legacy_str("event_id")),
+            ws: record.ws.or_else(|| legacy_str("ws")),
+            seq: record
+                .seq
+                .or_else(|| legacy("seq").and_then(|v| v.as_u64())),
+            msg: record.msg.or_else(|| legacy_str("msg")),
+            data: match record.data {
+                Value::Null => legacy("data").unwrap_or(Value::Null),
+                data => data,
+            },
+            compliance: record.compliance.or_else(|| legacy("compliance")),
+            topic: record.topic,
+            severity: record.severity,
+            ts_ms: record.ts_ms,
+            trace_id: record.trace_id,
+        }
+    }
+}
+
+/// Runtime law states, see docs/UI_LAW_MAPPING.md.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LawState {
+    Preboot,
+    Running,
+    Degraded,
+    Lockdown,
+    Halted,
+}
+
+impl LawState {
+    pub const ALL: [LawState; 5] = [
+        LawState::Preboot,
+        LawState::Running,
+        LawState::Degraded,
+        LawState::Lockdown,
+        LawState::Halted,
+    ];
+
+    /// Lockdown and halted only admit diagnostics and recovery.
+    pub fn is_restricted(&self) -> bool {
+        matches!(self, LawState::Lockdown | LawState::Halted)
+    }
+
+    /// Transitions the runtime is expected to make. The daemon stays the
+    /// authority; anything else is still tracked but flagged.
+    pub fn can_transition_to(&self, next: LawState) -> bool {
+        use LawState::*;
+        matches!(
+            (self, next),
+            (Preboot, Running | Degraded | Halted)
+                | (Running, Degraded | Lockdown | Halted)
+                | (Degraded, Running | Lockdown | Halted)
+                | (Lockdown, Running | Degraded | Halted)
+                | (Halted, Preboot)
+        )
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LawState::Preboot => "preboot",
+            LawState::Running => "running",
+            LawState::Degraded => "degraded",
+            LawState::Lockdown => "lockdown",
+            LawState::Halted => "halted",
+        }
+    }
+}
+
+impl FromStr for LawState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "preboot" => Ok(LawState::Preboot),
+            "running" => Ok(LawState::Running),
+            "degraded" => Ok(LawState::Degraded),
+            "lockdown" => Ok(LawState::Lockdown),
+            "halted" => Ok(LawState::Halted),
+            other => Err(format!("unknown law state: {other}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LawTransition {
+    pub ws: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<LawState>,
+    pub to: LawState,
+    pub ts_ms: u64,
+    /// Whether `from -> to` is an expected transition.
+    pub legal: bool,
+    /// Topic or command the new state was read from.
+    pub source: String,
+}
+
+fn all_law_states() -> Vec<LawState> {
+    LawState::ALL.to_vec()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandSpec {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capability: Option<String>,
+    /// JSON Schema of the command arguments.
+    #[serde(default)]
+    pub args_schema: Value,
+    #[serde(default)]
+    pub arming_required: bool,
+    #[serde(default = "all_law_states")]
+    pub allowed_states: Vec<LawState>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CapabilityCatalog {
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    pub commands: Vec<CommandSpec>,
+}
+
+impl CapabilityCatalog {
+    pub fn command(&self, name: &str) -> Option<&CommandSpec> {
+        self.commands.iter().find(|c| c.name == name)
+    }
+
+    /// Unknown commands are never allowed.
+    pub fn is_allowed(&self, name: &str, state: LawState) -> bool {
+        self.command(name)
+            .is_some_and(|c| c.allowed_states.contains(&state))
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Provider lifecycle: discovered -> paired -> attached <-> detached. Any
+/// provider can be revoked or fail; revoked and failed ones start over from
+/// discovery or pairing.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderState {
+    Discovered,
+    Paired,
+    Attached,
+    Detached,
+    Revoked,
+    Error,
+}
+
+impl ProviderState {
+    pub const ALL: [ProviderState; 6] = [
+        ProviderState::Discovered,
+        ProviderState::Paired,
+        ProviderState::Attached,
+        ProviderState::Detached,
+        ProviderState::Revoked,
+        ProviderState::Error,
+    ];
+
+    pub fn can_transition_to(&self, next: ProviderState) -> bool {
+        use ProviderState::*;
+        matches!(
+            (self, next),
+            (Discovered | Error, Paired)
+                | (Paired | Detached, Attached)
+                | (Attached, Detached)
+                | (Revoked | Error, Discovered)
+                | (Discovered | Paired | Attached | Detached | Error, Revoked)
+                | (Discovered | Paired | Attached | Detached, Error)
+        )
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProviderState::Discovered => "discovered",
+            ProviderState::Paired => "paired",
+            ProviderState::Attached => "attached",
+            ProviderState::Detached => "detached",
+            ProviderState::Revoked => "revoked",
+            ProviderState::Error => "error",
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ProviderState {
+    type Err = String;
+
+    /// Also accepts the daemon's `trust_state` values.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "discovered" | "new" | "untrusted" | "candidate" => Ok(ProviderState::Discovered),
+            "paired" | "trusted" => Ok(ProviderState::Paired),
+            "attached" | "active" => Ok(ProviderState::Attached),
+            "detached" | "inactive" => Ok(ProviderState::Detached),
+            "revoked" => Ok(ProviderState::Revoked),
+            "error" | "failed" => Ok(ProviderState::Error),
+            other => Err(format!("unknown provider state: {other}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "ProviderRecord")]
+pub struct Provider {
+    pub id: String,
+    pub state: ProviderState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Models the provider offers, when discovery lists them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<String>,
+    /// Epoch ms, whatever unit the daemon reported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl Provider {
+    pub fn new(id: &str, state: ProviderState) -> Provider {
+        Provider {
+            id: id.to_string(),
+            state,
+            endpoint: None,
+            model: None,
+            models: Vec::new(),
+            last_seen_ms: None,
+            last_error: None,
+        }
+    }
+}
+
+/// Deserialization form of `Provider`, covering the field names daemons have
+/// used: `provider_id`/`name` for the id, `trust_state` for the state and
+/// `last_seen` in epoch seconds or ms. Mistyped fields read as absent.
+#[derive(Deserialize)]
+struct ProviderRecord {
+    #[serde(default, deserialize_with = "lenient_string")]
+    id: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    provider_id: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    name: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    state: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    trust_state: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    endpoint: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    model: Option<String>,
+    #[serde(default, deserialize_with = "lenient_vec")]
+    models: Vec<Value>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    last_seen_ms: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    last_seen: Option<f64>,
+    #[serde(default)]
+    last_error: Option<Value>,
+}
+
+/// Below this an epoch timestamp is in seconds (year 5138 in seconds).
+const EPOCH_MS_THRESHOLD: f64 = 100_000_000_000.0;
+
+/// Epoch ms from a timestamp in seconds or ms.
+fn epoch_ms(n: f64) -> Option<u64> {
+    if !n.is_finite() || n <= 0.0 {
+        return None;
+    }
+    Some(if n < EPOCH_MS_THRESHOLD {
+        (n * 1000.0) as u64
+    } else {
+        n as u64
+    })
+}
+
+// Field readers for daemon records: a mistyped field reads as absent
+// instead of dropping the whole record.
+
+/// A number, or a string holding one.
+fn lenient_f64<'de, D: Deserializer<'de>>(d: D) -> Result<Option<f64>, D::Error> {
+    Ok(match Value::deserialize(d)? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    })
+}
+
+fn lenient_u64<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u64>, D::Error> {
+    Ok(lenient_f64(d)?
+        .filter(|n| n.is_finite() && *n >= 0.0)
+        .map(|n| n as u64))
+}
+
+/// A string, or a number or bool written out.
+fn lenient_string<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    Ok(match Value::deserialize(d)? {
+        Value::String(s) => Some(s),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    })
+}
+
+fn lenient_option<'de, D: Deserializer<'de>, T: DeserializeOwned>(
+    d: D,
+) -> Result<Option<T>, D::Error> {
+    Ok(serde_json::from_value(Value::deserialize(d)?).ok())
+}
+
+/// The items that parse, when the field is a list at all.
+fn lenient_vec<'de, D: Deserializer<'de>, T: DeserializeOwned>(d: D) -> Result<Vec<T>, D::Error> {
+    Ok(match Value::deserialize(d)? {
+        Value::Array(items) => items
+            .into_iter()
+            .filter_map(|v| serde_json::from_value(v).ok())
+            .collect(),
+        _ => Vec::new(),
+    })
+}
+
+impl From<ProviderRecord> for Provider {
+    fn from(record: ProviderRecord) -> Self {
+        let last_error = match record.last_error {
+            None | Some(Value::Null) => None,
+            Some(Value::String(s)) => Some(s).filter(|s| !s.is_empty()),
+            Some(other) => Some(
+                other
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .map_or_else(|| other.to_string(), str::to_string),
+            ),
+        };
+        let state = record
+            .state
+            .iter()
+            .chain(record.trust_state.iter())
+            .find_map(|s| s.parse().ok())
+            .unwrap_or(if last_error.is_some() {
+                ProviderState::Error
+            } else {
+                ProviderState::Discovered
+            });
+        let last_seen_ms = record
+            .last_seen_ms
+            .or_else(|| record.last_seen.and_then(epoch_ms));
+        Provider {
+            id: record
+                .id
+                .or(record.provider_id)
+                .or(record.name)
+                .unwrap_or_default(),
+            state,
+            endpoint: record.endpoint.filter(|s| !s.is_empty()),
+            model: record.model.filter(|s| !s.is_empty()),
+            // plain names or `{ "name": .. }` / `{ "id": .. }` objects
+            models: record
+                .models
+                .iter()
+                .filter_map(|m| match m {
+                    Value::String(s) => Some(s.clone()),
+                    other => ["name", "id", "model"]
+                        .iter()
+                        .find_map(|k| other.get(k).and_then(Value::as_str))
+                        .map(str::to_string),
+                })
+                .collect(),
+            last_seen_ms,
+            last_error,
+        }
+    }
+}
+
+/// Result of `providers.list` and `providers.discover`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderList {
+    pub items: Vec<Provider>,
+}
+
+/// Result of `providers.status`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderStatus {
+    pub active: Option<Provider>,
+}
+
+/// Result of `providers.pair`, `attach`, `detach` and `revoke`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderTransition {
+    pub command: String,
+    pub id: String,
+    /// State before the command, when the provider was known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<ProviderState>,
+    pub to: ProviderState,
+    /// The provider as the daemon reported it back, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<Provider>,
+    pub trace_id: String,
+}
+
+/// Who wrote a chat message. Roles the daemon sends that map to none of these
+/// are kept verbatim in `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+    Tool,
+    Other(String),
+}
+
+impl ChatRole {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ChatRole::System => "system",
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+            ChatRole::Tool => "tool",
+            ChatRole::Other(s) => s,
+        }
+    }
+}
+
+impl std::fmt::Display for ChatRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for ChatRole {
+    fn from(s: String) -> Self {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "system" => ChatRole::System,
+            "user" | "human" | "operator" => ChatRole::User,
+            "assistant" | "ai" | "bot" | "model" => ChatRole::Assistant,
+            "tool" | "function" => ChatRole::Tool,
+            _ => ChatRole::Other(s),
+        }
+    }
+}
+
+impl From<ChatRole> for String {
+    fn from(role: ChatRole) -> Self {
+        role.as_str().to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "ChatMessageRecord")]
+pub struct ChatMessage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub role: ChatRole,
+    pub content: String,
+    /// Epoch ms, whatever unit the daemon reported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ts_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Trace of the `chat.send` that produced the message.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub trace_id: Option<String>,
+    /// Context attached to the message when it was sent.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context: Vec<ChatContextRef>,
+    /// Tokens and latency of the reply, when the daemon reported them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ChatUsage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "ChatUsageRecord")]
+pub struct ChatUsage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_tokens: Option<u64>,
+    /// Round trip of the request as measured by the client.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+}
+
+impl ChatUsage {
+    pub fn has_tokens(&self) -> bool {
+        self.prompt_tokens.is_some() || self.completion_tokens.is_some()
+    }
+}
+
+/// Deserialization form of `ChatUsage`, also accepting the `input_tokens` /
+/// `output_tokens` and `prompt_eval_count` / `eval_count` names.
+#[derive(Deserialize)]
+struct ChatUsageRecord {
+    #[serde(default, deserialize_with = "lenient_u64")]
+    prompt_tokens: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    input_tokens: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    prompt_eval_count: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    completion_tokens: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    output_tokens: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    eval_count: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    total_tokens: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    latency_ms: Option<u64>,
+}
+
+impl From<ChatUsageRecord> for ChatUsage {
+    fn from(record: ChatUsageRecord) -> Self {
+        let prompt_tokens = record
+            .prompt_tokens
+            .or(record.input_tokens)
+            .or(record.prompt_eval_count);
+        let completion_tokens = record
+            .completion_tokens
+            .or(record.output_tokens)
+            .or(record.eval_count);
+        let total_tokens = record
+            .total_tokens
+            .or(match (prompt_tokens, completion_tokens) {
+                (None, None) => None,
+                (p, c) => Some(p.unwrap_or(0) + c.unwrap_or(0)),
+            });
+        ChatUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens,
+            latency_ms: record.latency_ms,
+        }
+    }
+}
+
+/// One item of context attached to a chat message. Only the reference is
+/// kept in the history, not the data that was sent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatContextRef {
+    /// `law_snapshot`, `events`, `logs`, `command_result` or `trace`.
+    pub kind: String,
+    pub label: String,
+    /// Trace id, law snapshot seq or newest event id, to find the source again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Entries attached, after truncation.
+    pub count: usize,
+    /// Size of the attached data as JSON.
+    pub bytes: usize,
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+impl ChatMessage {
+    pub fn new(role: ChatRole, content: &str) -> ChatMessage {
+        ChatMessage {
+            id: None,
+            role,
+            content: content.to_string(),
+            ts_ms: None,
+            provider: None,
+            model: None,
+            trace_id: None,
+            context: Vec::new(),
+            usage: None,
+        }
+    }
+}
+
+/// Deserialization form of `ChatMessage`: `text` for the content, `ts` or
+/// `created_at` in epoch seconds or ms, and `message_id` for the id.
+/// Mistyped fields read as absent.
+#[derive(Deserialize)]
+struct ChatMessageRecord {
+    #[serde(default, deserialize_with = "lenient_string")]
+    id: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    message_id: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    role: Option<String>,
+    #[serde(default)]
+    content: Option<Value>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    text: Option<String>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    ts_ms: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    ts: Option<f64>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    created_at: Option<f64>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    provider: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    model: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    trace_id: Option<String>,
+    #[serde(default, deserialize_with = "lenient_vec")]
+    context: Vec<ChatContextRef>,
+    #[serde(default, deserialize_with = "lenient_option")]
+    usage: Option<ChatUsage>,
+}
+
+impl From<ChatMessageRecord> for ChatMessage {
+    fn from(record: ChatMessageRecord) -> Self {
+        // content is a string or a list of `{ "type": "text", "text": .. }` parts
+        let content = match record.content {
+            Some(Value::String(s)) => s,
+            Some(Value::Array(parts)) => parts
+                .iter()
+                .filter_map(|p| p.as_str().or_else(|| p.get("text").and_then(Value::as_str)))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            None | Some(Value::Null) => record.text.unwrap_or_default(),
+            Some(other) => other.to_string(),
+        };
+        ChatMessage {
+            id: record.id.or(record.message_id),
+            role: record.role.map_or(ChatRole::Assistant, ChatRole::from),
+            content,
+            ts_ms: record
+                .ts_ms
+                .or_else(|| record.ts.or(record.created_at).and_then(epoch_ms)),
+            provider: record.provider.filter(|s| !s.is_empty()),
+            model: record.model.filter(|s| !s.is_empty()),
+            trace_id: record.trace_id,
+            context: record.context,
+            usage: record.usage,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "ChatSessionRecord")]
+pub struct ChatSession {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_count: Option<usize>,
+}
+
+impl ChatSession {
+    pub fn new(id: &str) -> ChatSession {
+        ChatSession {
+            id: id.to_string(),
+            title: None,
+            created_ms: None,
+            updated_ms: None,
+            provider: None,
+            model: None,
+            message_count: None,
+        }
+    }
+}
+
+/// Deserialization form of `ChatSession`: `session_id`/`name` for the id and
+/// `created_at`/`updated_at` in epoch seconds or ms.
+#[derive(Deserialize)]
+struct ChatSessionRecord {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    session_id: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    created_ms: Option<u64>,
+    #[serde(default)]
+    created_at: Option<f64>,
+    #[serde(default)]
+    updated_ms: Option<u64>,
+    #[serde(default)]
+    updated_at: Option<f64>,
+    #[serde(default)]
+    provider: Option<String>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    message_count: Option<usize>,
+    #[serde(default)]
+    messages: Option<usize>,
+}
+
+impl From<ChatSessionRecord> for ChatSession {
+    fn from(record: ChatSessionRecord) -> Self {
+        // `name` is the id when nothing else is, else a title
+        let (id, name) = match record.id.or(record.session_id) {
+            Some(id) => (id, record.name),
+            None => (record.name.unwrap_or_default(), None),
+        };
+        ChatSession {
+            id,
+            title: record.title.or(name).filter(|s| !s.is_empty()),
+            created_ms: record
+                .created_ms
+                .or_else(|| record.created_at.and_then(epoch_ms)),
+            updated_ms: record
+                .updated_ms
+                .or_else(|| record.updated_at.and_then(epoch_ms)),
+            provider: record.provider.filter(|s| !s.is_empty()),
+            model: record.model.filter(|s| !s.is_empty()),
+            message_count: record.message_count.or(record.messages),
+        }
+    }
+}
+
+/// Result of `chat.sessions.list`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatSessionList {
+    pub items: Vec<ChatSession>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selected: Option<String>,
+    /// Set when served from the local cache instead of the daemon: when the
+    /// cached copy was fetched, epoch ms.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_ms: Option<u64>,
+}
+
+/// Result of `chat.history`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatHistory {
+    pub session: ChatSession,
+    pub items: Vec<ChatMessage>,
+    /// As on `ChatSessionList`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_ms: Option<u64>,
 }
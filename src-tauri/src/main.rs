@@ -9,6 +9,7 @@ fn yx_workspaces_list() -> Result<Value, String> {
 
 #[tauri::command]
 fn yx_workspace_select(ws: String) -> Result<Value, String> {
+    yx_client::disarm(yx_client::DisarmReason::WorkspaceSwitch);
     std::env::set_var("YAI_WS", ws);
     serde_json::to_value(yx_client::connection_state()).map_err(|e| e.to_string())
 }
@@ -29,6 +30,380 @@ fn yx_send_command(name: String, args: Value, arming: Option<bool>) -> Result<Va
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+fn yx_send_command_unredacted(name: String, args: Value) -> Result<Value, String> {
+    serde_json::to_value(yx_client::send_command_unredacted(&name, args)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_whoami() -> Result<Value, String> {
+    serde_json::to_value(yx_client::whoami()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_capabilities(refresh: Option<bool>) -> Result<Value, String> {
+    Ok(yx_client::redacted(&yx_client::capability_catalog(
+        refresh.unwrap_or(false),
+    )))
+}
+
+#[tauri::command]
+fn yx_is_allowed(command: String, state: String) -> Result<bool, String> {
+    let state = state.parse::<yx_protocol::LawState>()?;
+    Ok(yx_client::is_allowed(&command, state))
+}
+
+#[tauri::command]
+fn yx_law_status() -> Result<Value, String> {
+    serde_json::to_value(yx_client::law_status()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_law_history(limit: Option<usize>) -> Result<Value, String> {
+    let limit = limit.unwrap_or(yx_client::LAW_HISTORY_LIMIT);
+    let history = yx_client::law_history(limit).map_err(|e| e.to_string())?;
+    serde_json::to_value(history).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_law_diff(from_seq: u64, to_seq: u64) -> Result<Value, String> {
+    let diff = yx_client::law_diff(from_seq, to_seq).map_err(|e| e.to_string())?;
+    serde_json::to_value(diff).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_law_diff_since(since_ts_ms: u64) -> Result<Value, String> {
+    let diff = yx_client::law_diff_since(since_ts_ms).map_err(|e| e.to_string())?;
+    serde_json::to_value(diff).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_events_query(query: Option<yx_client::EventQuery>) -> Result<Value, String> {
+    serde_json::to_value(yx_client::events_query(&query.unwrap_or_default()))
+        .map_err(|e| e.to_string())
+}
+
+/// `request.path` names a file in the workspace's exports directory.
+#[tauri::command]
+fn yx_events_export(request: yx_client::ExportRequest) -> Result<Value, String> {
+    let summary = yx_client::events_export_to_workspace(&request).map_err(|e| e.to_string())?;
+    serde_json::to_value(summary).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_events_stats(ws: Option<String>) -> Result<Value, String> {
+    serde_json::to_value(yx_client::events_stats(ws.as_deref())).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_search(request: yx_client::SearchRequest) -> Result<Value, String> {
+    serde_json::to_value(yx_client::search(&request)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_trace(trace_id: String) -> Result<Value, String> {
+    serde_json::to_value(yx_client::trace(&trace_id)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_alerts(include_resolved: Option<bool>) -> Result<Value, String> {
+    serde_json::to_value(yx_client::alerts(include_resolved.unwrap_or(false)))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_alert_ack(id: String) -> Result<Value, String> {
+    let alert = yx_client::alert_ack(&id).map_err(|e| e.to_string())?;
+    serde_json::to_value(alert).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_alert_resolve(id: String) -> Result<Value, String> {
+    let alert = yx_client::alert_resolve(&id).map_err(|e| e.to_string())?;
+    serde_json::to_value(alert).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_providers_list() -> Result<Value, String> {
+    let list = yx_client::providers_list().map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&list))
+}
+
+#[tauri::command]
+fn yx_providers_status() -> Result<Value, String> {
+    let status = yx_client::providers_status().map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&status))
+}
+
+#[tauri::command]
+fn yx_providers_discover(endpoint: Option<String>, model: Option<String>) -> Result<Value, String> {
+    let list = yx_client::providers_discover(endpoint.as_deref(), model.as_deref())
+        .map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&list))
+}
+
+#[tauri::command]
+fn yx_providers_pair(id: String, endpoint: String, model: String) -> Result<Value, String> {
+    let change = yx_client::providers_pair(&id, &endpoint, &model).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&change))
+}
+
+#[tauri::command]
+fn yx_providers_attach(id: String, model: Option<String>) -> Result<Value, String> {
+    let change = yx_client::providers_attach(&id, model.as_deref()).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&change))
+}
+
+#[tauri::command]
+fn yx_providers_detach() -> Result<Value, String> {
+    let change = yx_client::providers_detach().map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&change))
+}
+
+#[tauri::command]
+fn yx_providers_revoke(id: String) -> Result<Value, String> {
+    let change = yx_client::providers_revoke(&id).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&change))
+}
+
+#[tauri::command]
+fn yx_providers_health(ws: Option<String>) -> Result<Value, String> {
+    Ok(yx_client::redacted(&yx_client::providers_health(
+        ws.as_deref(),
+    )))
+}
+
+#[tauri::command]
+fn yx_providers_probe() -> Result<Value, String> {
+    Ok(yx_client::redacted(&yx_client::providers_probe()))
+}
+
+#[tauri::command]
+fn yx_pairing_start(endpoint: Option<String>, model: Option<String>) -> Result<Value, String> {
+    let session = yx_client::pairing_start(endpoint.as_deref(), model.as_deref())
+        .map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&session))
+}
+
+#[tauri::command]
+fn yx_pairing_sessions(include_closed: Option<bool>) -> Result<Value, String> {
+    Ok(yx_client::redacted(&yx_client::pairing_sessions(
+        include_closed.unwrap_or(false),
+    )))
+}
+
+#[tauri::command]
+fn yx_pairing_rediscover(id: String) -> Result<Value, String> {
+    let session = yx_client::pairing_rediscover(&id).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&session))
+}
+
+#[tauri::command]
+fn yx_pairing_pick(id: String, candidate: String, model: Option<String>) -> Result<Value, String> {
+    let session =
+        yx_client::pairing_pick(&id, &candidate, model.as_deref()).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&session))
+}
+
+#[tauri::command]
+fn yx_pairing_pair(id: String) -> Result<Value, String> {
+    let session = yx_client::pairing_pair(&id).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&session))
+}
+
+#[tauri::command]
+fn yx_pairing_verify(id: String) -> Result<Value, String> {
+    let session = yx_client::pairing_verify(&id).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&session))
+}
+
+#[tauri::command]
+fn yx_pairing_attach(id: String) -> Result<Value, String> {
+    let session = yx_client::pairing_attach(&id).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&session))
+}
+
+#[tauri::command]
+fn yx_pairing_finish(id: String) -> Result<Value, String> {
+    let session = yx_client::pairing_finish(&id).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&session))
+}
+
+#[tauri::command]
+fn yx_pairing_cancel(id: String) -> Result<Value, String> {
+    let session = yx_client::pairing_cancel(&id).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&session))
+}
+
+#[tauri::command]
+fn yx_chat_sessions() -> Result<Value, String> {
+    let list = yx_client::chat_sessions().map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&list))
+}
+
+#[tauri::command]
+fn yx_chat_history(session_id: String) -> Result<Value, String> {
+    let history = yx_client::chat_history(&session_id).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&history))
+}
+
+#[tauri::command]
+fn yx_chat_session_new(title: Option<String>) -> Result<Value, String> {
+    let session = yx_client::chat_session_new(title.as_deref()).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&session))
+}
+
+#[tauri::command]
+fn yx_chat_context(request: yx_client::ChatContextRequest) -> Result<Value, String> {
+    let context = yx_client::chat_context(&request).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&context))
+}
+
+#[tauri::command]
+fn yx_chat_send(
+    session_id: String,
+    text: String,
+    context: Option<yx_client::ChatContextRequest>,
+) -> Result<Value, String> {
+    let exchange =
+        yx_client::chat_send(&session_id, &text, context.as_ref()).map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&exchange))
+}
+
+#[tauri::command]
+fn yx_chat_search(request: yx_client::ChatSearchRequest) -> Result<Value, String> {
+    Ok(yx_client::redacted(&yx_client::chat_search(&request)))
+}
+
+/// Runs a streaming `shell.exec`, emitting each chunk and the exit as
+/// `yx:shell` events tagged with `stream_id`.
+#[tauri::command]
+async fn yx_shell_exec(
+    app: tauri::AppHandle,
+    stream_id: String,
+    request: yx_client::ShellExecRequest,
+) -> Result<Value, String> {
+    let exit = tauri::async_runtime::spawn_blocking(move || {
+        yx_client::shell_exec_stream(&request, |output| {
+            let mut payload = serde_json::to_value(output).unwrap_or_default();
+            payload["stream_id"] = json!(stream_id);
+            let _ = app.emit("yx:shell", payload);
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+    serde_json::to_value(exit).map_err(|e| e.to_string())
+}
+
+/// Opens a PTY; its output and exit arrive as `yx:pty` events carrying the
+/// `pty_id` of the returned session.
+#[tauri::command]
+async fn yx_pty_open(
+    app: tauri::AppHandle,
+    request: yx_client::PtyOpenRequest,
+) -> Result<Value, String> {
+    let session = tauri::async_runtime::spawn_blocking(move || {
+        yx_client::pty_open(&request, move |output| {
+            let _ = app.emit("yx:pty", output);
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+    serde_json::to_value(session).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_pty_write(pty_id: String, data: String) -> Result<(), String> {
+    yx_client::pty_write(&pty_id, &data).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_pty_close(pty_id: String) -> Result<(), String> {
+    yx_client::pty_close(&pty_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_pty_sessions() -> Result<Value, String> {
+    serde_json::to_value(yx_client::pty_sessions()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_recordings() -> Result<Value, String> {
+    serde_json::to_value(yx_client::recordings()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_recording(id: String) -> Result<Value, String> {
+    let recording = yx_client::recording(&id).map_err(|e| format!("{e:#}"))?;
+    serde_json::to_value(recording).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_recording_cast(id: String) -> Result<String, String> {
+    yx_client::recording_cast(&id).map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+fn yx_down_preflight() -> Result<Value, String> {
+    Ok(yx_client::redacted(&yx_client::down_preflight()))
+}
+
+/// Runs the guarded `down`; each phase arrives as a `yx:down` event.
+#[tauri::command]
+async fn yx_down(app: tauri::AppHandle, request: yx_client::DownRequest) -> Result<Value, String> {
+    let outcome = tauri::async_runtime::spawn_blocking(move || {
+        yx_client::down(&request, |progress| {
+            let _ = app.emit("yx:down", yx_client::redacted(progress));
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+    Ok(yx_client::redacted(&outcome))
+}
+
+#[tauri::command]
+fn yx_usage_report(query: yx_client::UsageQuery) -> Result<Value, String> {
+    serde_json::to_value(yx_client::usage_report(&query)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_chat_export(session_id: String, format: String) -> Result<String, String> {
+    let format = format.parse::<yx_client::ChatExportFormat>()?;
+    yx_client::chat_export(&session_id, format).map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+fn yx_arm(reason: String, scope: Vec<String>, ttl_secs: u64) -> Result<Value, String> {
+    let session = yx_client::arm(&reason, scope, ttl_secs).map_err(|e| e.to_string())?;
+    serde_json::to_value(session).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_disarm() -> Result<Value, String> {
+    yx_client::disarm(yx_client::DisarmReason::Manual);
+    serde_json::to_value(yx_client::arming_state()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_arming_state() -> Result<Value, String> {
+    serde_json::to_value(yx_client::arming_state()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_audit_query(filter: Option<yx_client::AuditQuery>) -> Result<Value, String> {
+    let entries = yx_client::audit_query(&filter.unwrap_or_default()).map_err(|e| e.to_string())?;
+    serde_json::to_value(entries).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn yx_audit_verify() -> Result<Value, String> {
+    let report = yx_client::audit_verify().map_err(|e| e.to_string())?;
+    serde_json::to_value(report).map_err(|e| e.to_string())
+}
+
 fn main() {
     tauri::Builder::default()
         .setup(|app| {
@@ -45,10 +420,32 @@ fn main() {
                     let _ = handle.emit("yx:connection", payload);
                 }
             });
+            let law_handle = app.handle().clone();
+            yx_client::on_law_transition(move |transition| {
+                let _ = law_handle.emit("yx:law", transition);
+            });
+            let alert_handle = app.handle().clone();
+            yx_client::on_alert(move |alert| {
+                let _ = alert_handle.emit("yx:alert", alert);
+            });
+            let arming_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut tick = tokio::time::interval(Duration::from_secs(1));
+                let mut was_armed = false;
+                loop {
+                    tick.tick().await;
+                    let state = yx_client::arming_state();
+                    if state.armed || was_armed {
+                        let _ = arming_handle.emit("yx:arming", &state);
+                    }
+                    was_armed = state.armed;
+                }
+            });
+            yx_client::start_provider_health_monitor();
             let event_handle = app.handle().clone();
             tauri::async_runtime::spawn_blocking(move || {
-                let _ = yx_client::start_event_stream(move |event| {
-                    let _ = event_handle.emit("yx:event", event);
+                let _ = yx_client::start_batched_event_stream(move |batch| {
+                    let _ = event_handle.emit("yx:events", batch);
                 });
             });
             Ok(())
@@ -58,7 +455,64 @@ fn main() {
             yx_workspace_select,
             yx_connection_state,
             yx_ping,
-            yx_send_command
+            yx_send_command,
+            yx_send_command_unredacted,
+            yx_whoami,
+            yx_capabilities,
+            yx_is_allowed,
+            yx_law_status,
+            yx_law_history,
+            yx_law_diff,
+            yx_law_diff_since,
+            yx_events_query,
+            yx_events_stats,
+            yx_events_export,
+            yx_search,
+            yx_trace,
+            yx_alerts,
+            yx_alert_ack,
+            yx_alert_resolve,
+            yx_providers_list,
+            yx_providers_status,
+            yx_providers_discover,
+            yx_providers_pair,
+            yx_providers_attach,
+            yx_providers_detach,
+            yx_providers_revoke,
+            yx_providers_health,
+            yx_providers_probe,
+            yx_pairing_start,
+            yx_pairing_sessions,
+            yx_pairing_rediscover,
+            yx_pairing_pick,
+            yx_pairing_pair,
+            yx_pairing_verify,
+            yx_pairing_attach,
+            yx_pairing_finish,
+            yx_pairing_cancel,
+            yx_chat_sessions,
+            yx_chat_history,
+            yx_chat_session_new,
+            yx_chat_context,
+            yx_chat_send,
+            yx_chat_search,
+            yx_chat_export,
+            yx_usage_report,
+            yx_shell_exec,
+            yx_pty_open,
+            yx_pty_write,
+            yx_pty_close,
+            yx_pty_sessions,
+            yx_recordings,
+            yx_recording,
+            yx_recording_cast,
+            yx_down_preflight,
+            yx_down,
+            yx_arm,
+            yx_disarm,
+            yx_arming_state,
+            yx_audit_query,
+            yx_audit_verify
         ])
         .run(tauri::generate_context!())
         .expect("run yx app");
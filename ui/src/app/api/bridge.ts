@@ -1,6 +1,36 @@
 import type { Dispatch } from "react";
 import type { Action } from "../state/store.tsx";
-import type { ConnectionState, EventItem, WorkspaceInfo } from "../state/types";
+import { severityRank } from "../state/types";
+import type {
+  ChatContext,
+  ChatContextRequest,
+  ChatExchange,
+  ChatHistory,
+  ChatSearchResults,
+  ChatSession,
+  ChatSessionList,
+  ConnectionState,
+  DownOutcome,
+  DownPreflight,
+  DownProgress,
+  DownRequest,
+  EventItem,
+  PairingSession,
+  ProviderHealth,
+  ProviderItem,
+  ProviderTransition,
+  PtyOpenRequest,
+  PtyOutput,
+  PtySession,
+  Recording,
+  RecordingSummary,
+  ShellExecRequest,
+  ShellExit,
+  ShellOutput,
+  UsageQuery,
+  UsageReport,
+  WorkspaceInfo,
+} from "../state/types";
 
 const DEV_MODE = import.meta.env.VITE_DEV_MODE === "true";
 
@@ -64,27 +94,338 @@ export async function sendCommand(name: string, args: Record<string, unknown> =
   return t.core.invoke("yx_send_command", { name, args, arming });
 }
 
+export async function whoami() {
+  const t = tauri();
+  if (!t?.core?.invoke) return null;
+  return t.core.invoke("yx_whoami");
+}
+
+export async function arm(reason: string, scope: string[], ttlSecs: number) {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_arm", { reason, scope, ttlSecs });
+}
+
+export async function disarm() {
+  const t = tauri();
+  if (!t?.core?.invoke) return { armed: false };
+  return t.core.invoke("yx_disarm");
+}
+
+export async function getArmingState() {
+  const t = tauri();
+  if (!t?.core?.invoke) return { armed: false };
+  return t.core.invoke("yx_arming_state");
+}
+
+export async function getEventStats(ws?: string) {
+  const t = tauri();
+  if (!t?.core?.invoke) return null;
+  return t.core.invoke("yx_events_stats", { ws });
+}
+
+export async function listAlerts(includeResolved = false) {
+  const t = tauri();
+  if (!t?.core?.invoke) return [];
+  return t.core.invoke("yx_alerts", { includeResolved });
+}
+
+export async function ackAlert(id: string) {
+  const t = tauri();
+  if (!t?.core?.invoke) return null;
+  return t.core.invoke("yx_alert_ack", { id });
+}
+
+export async function resolveAlert(id: string) {
+  const t = tauri();
+  if (!t?.core?.invoke) return null;
+  return t.core.invoke("yx_alert_resolve", { id });
+}
+
+export async function listProviders(): Promise<{ items: ProviderItem[] }> {
+  const t = tauri();
+  if (!t?.core?.invoke) return { items: [] };
+  return t.core.invoke("yx_providers_list");
+}
+
+export async function providerStatus(): Promise<{ active: ProviderItem | null }> {
+  const t = tauri();
+  if (!t?.core?.invoke) return { active: null };
+  return t.core.invoke("yx_providers_status");
+}
+
+export async function discoverProviders(endpoint?: string, model?: string): Promise<{ items: ProviderItem[] }> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_providers_discover", { endpoint: endpoint || null, model: model || null });
+}
+
+/** Lifecycle commands are checked against the provider's known state first. */
+export async function pairProvider(id: string, endpoint: string, model: string): Promise<ProviderTransition> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_providers_pair", { id, endpoint, model });
+}
+
+export async function attachProvider(id: string, model?: string): Promise<ProviderTransition> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_providers_attach", { id, model: model || null });
+}
+
+export async function detachProvider(): Promise<ProviderTransition> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_providers_detach");
+}
+
+export async function revokeProvider(id: string): Promise<ProviderTransition> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_providers_revoke", { id });
+}
+
+export async function getProviderHealth(ws?: string): Promise<ProviderHealth[]> {
+  const t = tauri();
+  if (!t?.core?.invoke) return [];
+  return t.core.invoke("yx_providers_health", { ws });
+}
+
+/** Runs a health round now instead of waiting for the monitor. */
+export async function probeProviders(): Promise<ProviderHealth[]> {
+  const t = tauri();
+  if (!t?.core?.invoke) return [];
+  return t.core.invoke("yx_providers_probe");
+}
+
+export async function pairingSessions(includeClosed = false): Promise<PairingSession[]> {
+  const t = tauri();
+  if (!t?.core?.invoke) return [];
+  return t.core.invoke("yx_pairing_sessions", { includeClosed });
+}
+
+/**
+ * Guided pairing: start (runs discovery), pick, pair, verify, then attach or
+ * finish. Every call returns the updated session; failed steps can be retried.
+ */
+export async function pairingStep(
+  step: "start" | "rediscover" | "pick" | "pair" | "verify" | "attach" | "finish" | "cancel",
+  args: Record<string, unknown>
+): Promise<PairingSession> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke(`yx_pairing_${step}`, args);
+}
+
+/** Chat sessions; served from the local cache when the daemon is unreachable. */
+export async function chatSessions(): Promise<ChatSessionList> {
+  const t = tauri();
+  if (!t?.core?.invoke) return { items: [] };
+  return t.core.invoke("yx_chat_sessions");
+}
+
+export async function chatHistory(sessionId: string): Promise<ChatHistory> {
+  const t = tauri();
+  if (!t?.core?.invoke) return { session: { id: sessionId }, items: [] };
+  return t.core.invoke("yx_chat_history", { sessionId });
+}
+
+export async function newChatSession(title?: string): Promise<ChatSession> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_chat_session_new", { title });
+}
+
+/** What `context` would attach, after truncation to its byte budget. */
+export async function previewChatContext(request: ChatContextRequest): Promise<ChatContext> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_chat_context", { request });
+}
+
+export async function sendChat(sessionId: string, text: string, context?: ChatContextRequest): Promise<ChatExchange> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_chat_send", { sessionId, text, context: context?.items.length ? context : null });
+}
+
+/** Full-text search over cached chat histories, newest first. */
+export async function searchChat(q: string, sessionId?: string, limit = 50): Promise<ChatSearchResults> {
+  const t = tauri();
+  if (!t?.core?.invoke) return { ws: "", total: 0, hits: [] };
+  return t.core.invoke("yx_chat_search", { request: { q, session_id: sessionId ?? null, limit } });
+}
+
+/** The session as a Markdown or JSON document with provider, model, timestamps and trace ids. */
+export async function exportChat(sessionId: string, format: "markdown" | "json"): Promise<string> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_chat_export", { sessionId, format });
+}
+
+let shellStreams = 0;
+
+/** Runs a streaming shell.exec; chunks and the exit reach `onOutput` as they arrive. */
+export async function execShell(request: ShellExecRequest, onOutput: (output: ShellOutput) => void): Promise<ShellExit> {
+  const t = tauri();
+  if (!t?.core?.invoke || !t?.event?.listen) throw new Error("tauri runtime unavailable");
+  shellStreams += 1;
+  const streamId = `shell-${Date.now()}-${shellStreams}`;
+  const unlisten = await t.event.listen("yx:shell", (evt: any) => {
+    if (evt?.payload?.stream_id === streamId) onOutput(evt.payload);
+  });
+  try {
+    return await t.core.invoke("yx_shell_exec", { streamId, request });
+  } finally {
+    unlisten();
+  }
+}
+
+/** Opens a PTY; its output and exit reach `onOutput` until the returned unlisten is called. */
+export async function openPty(
+  request: PtyOpenRequest,
+  onOutput: (output: PtyOutput) => void,
+): Promise<{ session: PtySession; unlisten: () => void }> {
+  const t = tauri();
+  if (!t?.core?.invoke || !t?.event?.listen) throw new Error("tauri runtime unavailable");
+  // output can arrive before the session is returned, so hold it until the id is known
+  let ptyId: string | null = null;
+  const early: PtyOutput[] = [];
+  const unlisten = await t.event.listen("yx:pty", (evt: any) => {
+    const output = evt?.payload as PtyOutput;
+    if (ptyId === null) early.push(output);
+    else if (output?.pty_id === ptyId) onOutput(output);
+  });
+  try {
+    const session: PtySession = await t.core.invoke("yx_pty_open", { request });
+    ptyId = session.pty_id;
+    early.filter((o) => o?.pty_id === ptyId).forEach(onOutput);
+    return { session, unlisten };
+  } catch (e) {
+    unlisten();
+    throw e;
+  }
+}
+
+export async function writePty(ptyId: string, data: string): Promise<void> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_pty_write", { ptyId, data });
+}
+
+export async function closePty(ptyId: string): Promise<void> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_pty_close", { ptyId });
+}
+
+/** PTY recordings of the selected workspace, newest first. */
+export async function listRecordings(): Promise<RecordingSummary[]> {
+  const t = tauri();
+  if (!t?.core?.invoke) return [];
+  return t.core.invoke("yx_recordings");
+}
+
+export async function getRecording(id: string): Promise<Recording> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_recording", { id });
+}
+
+/** The recording as an asciicast v2 file, for asciinema and other players. */
+export async function recordingCast(id: string): Promise<string> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_recording_cast", { id });
+}
+
+/** What down would interrupt: status, attached providers, open PTYs, commands in flight. */
+export async function downPreflight(): Promise<DownPreflight> {
+  const t = tauri();
+  if (!t?.core?.invoke) throw new Error("tauri runtime unavailable");
+  return t.core.invoke("yx_down_preflight");
+}
+
+/** Guarded down; each phase reaches `onProgress` until the daemon's socket is gone. */
+export async function runDown(request: DownRequest, onProgress: (progress: DownProgress) => void): Promise<DownOutcome> {
+  const t = tauri();
+  if (!t?.core?.invoke || !t?.event?.listen) throw new Error("tauri runtime unavailable");
+  const unlisten = await t.event.listen("yx:down", (evt: any) => onProgress(evt.payload));
+  try {
+    return await t.core.invoke("yx_down", { request });
+  } finally {
+    unlisten();
+  }
+}
+
+/** Chat tokens, latency and cost grouped by session, provider, model, workspace or day. */
+export async function usageReport(query: UsageQuery = {}): Promise<UsageReport | null> {
+  const t = tauri();
+  if (!t?.core?.invoke) return null;
+  return t.core.invoke("yx_usage_report", { query });
+}
+
+/**
+ * Desktop notification for a firing alert whose rule asks for one. The client
+ * only announces raised alerts and cooldown re-fires; the tag replaces an
+ * earlier notification of the same alert.
+ */
+function notifyAlert(alert: any) {
+  if (!alert?.notify || alert.status !== "firing") return;
+  if (typeof Notification === "undefined") return;
+  const show = () => new Notification(`YX alert: ${alert.rule_id}`, { body: alert.message, tag: alert.id });
+  if (Notification.permission === "granted") show();
+  else if (Notification.permission !== "denied") {
+    Notification.requestPermission().then((perm) => perm === "granted" && show());
+  }
+}
+
 function normalizeEvent(raw: any): EventItem {
   return {
     topic: String(raw?.topic || "unknown"),
-    severity: String(raw?.severity || "info"),
+    // the client already resolved aliases; keep whatever level it reports
+    severity: String(raw?.severity ?? "info"),
     ts_ms: Number(raw?.ts_ms || Date.now()),
-    payload: raw?.payload ?? {},
+    event_id: raw?.event_id ?? null,
+    ws: raw?.ws ?? null,
+    seq: typeof raw?.seq === "number" ? raw.seq : null,
+    msg: raw?.msg ?? null,
+    data: raw?.data ?? {},
+    compliance: raw?.compliance ?? null,
     trace_id: raw?.trace_id ?? null,
   };
 }
 
+function isLogEvent(event: EventItem): boolean {
+  // logs get log topics, diagnostics below info and anything warn or worse
+  const rank = severityRank(event.severity);
+  const noteworthy = rank !== null && (rank < severityRank("info")! || rank >= severityRank("warn")!);
+  return event.topic.toLowerCase().includes("log") || noteworthy;
+}
+
 function ingestEvent(dispatch: Dispatch<Action>, event: EventItem) {
   dispatch({ type: "events/add", event });
-  if (event.topic.toLowerCase().includes("log") || event.severity !== "info") {
+  if (isLogEvent(event)) {
     dispatch({ type: "logs/add", log: event });
   }
+  ingestGraph(dispatch, event);
+}
+
+/** One dispatch per batch so a daemon burst costs a single render. */
+function ingestBatch(dispatch: Dispatch<Action>, events: EventItem[]) {
+  if (!events.length) return;
+  dispatch({ type: "events/batch", events, logs: events.filter(isLogEvent) });
+  const graph = events.filter((evt) => evt.topic.startsWith("mind.graph")).pop();
+  if (graph) ingestGraph(dispatch, graph);
+}
+
+function ingestGraph(dispatch: Dispatch<Action>, event: EventItem) {
   if (event.topic.startsWith("mind.graph")) {
-    const payload = event.payload as any;
-    const nodeId = String(payload?.id || payload?.node || "unknown");
-    const nodes = [{ id: nodeId, label: payload?.label || nodeId, score: Number(payload?.score || payload?.weight || 0.5) }];
-    const edges = Array.isArray(payload?.neighbors)
-      ? payload.neighbors.map((n: any, idx: number) => ({ id: `${nodeId}-${idx}`, source: nodeId, target: String(n), weight: 1 }))
+    const data = event.data as any;
+    const nodeId = String(data?.id || data?.node || "unknown");
+    const nodes = [{ id: nodeId, label: data?.label || nodeId, score: Number(data?.score || data?.weight || 0.5) }];
+    const edges = Array.isArray(data?.neighbors)
+      ? data.neighbors.map((n: any, idx: number) => ({ id: `${nodeId}-${idx}`, source: nodeId, target: String(n), weight: 1 }))
       : [];
     dispatch({ type: "graph/set", nodes, edges });
   }
@@ -97,8 +438,8 @@ function startOfflineGenerator(dispatch: React.Dispatch<Action>) {
   offlineTimer = window.setInterval(() => {
     const now = Date.now();
     const sample = [
-      { topic: "state.changed", severity: "warn", payload: { mode: "degraded", connected: false }, ts_ms: now },
-      { topic: "log.line", severity: "info", payload: { line: "offline dev generator" }, ts_ms: now },
+      { topic: "state.changed", severity: "warn", data: { mode: "degraded", connected: false }, ts_ms: now },
+      { topic: "log.line", severity: "info", msg: "offline dev generator", data: { line: "offline dev generator" }, ts_ms: now },
     ];
     ingestEvent(dispatch, normalizeEvent(sample[Math.floor(Math.random() * sample.length)]));
   }, 1500);
@@ -117,8 +458,13 @@ export async function connectAndSubscribe(dispatch: Dispatch<Action>) {
 
   const t = tauri();
   if (t?.event?.listen) {
-    await t.event.listen("yx:event", (evt: any) => {
-      ingestEvent(dispatch, normalizeEvent(evt?.payload || {}));
+    await t.event.listen("yx:events", (evt: any) => {
+      const batch = Array.isArray(evt?.payload) ? evt.payload : [];
+      ingestBatch(dispatch, batch.map(normalizeEvent));
+    });
+
+    await t.event.listen("yx:alert", (evt: any) => {
+      notifyAlert(evt?.payload);
     });
 
     await t.event.listen("yx:connection", (evt: any) => {
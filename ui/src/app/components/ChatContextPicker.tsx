@@ -0,0 +1,78 @@
+import React, { useEffect, useState } from "react";
+import { previewChatContext } from "../api/bridge";
+import type { ChatContext, ChatContextItem, ChatContextRequest } from "../state/types";
+
+const DEFAULT_BUDGET = 16 * 1024;
+
+export function ChatContextPicker({ onChange }: { onChange: (request: ChatContextRequest) => void }) {
+  const [law, setLaw] = useState(false);
+  const [events, setEvents] = useState(false);
+  const [topic, setTopic] = useState("");
+  const [logs, setLogs] = useState(false);
+  const [lines, setLines] = useState(50);
+  const [traceId, setTraceId] = useState("");
+  const [traceMode, setTraceMode] = useState<"command_result" | "trace">("trace");
+  const [budget, setBudget] = useState(DEFAULT_BUDGET);
+  const [preview, setPreview] = useState<ChatContext | null>(null);
+  const [error, setError] = useState("");
+
+  const items: ChatContextItem[] = [];
+  if (law) items.push({ kind: "law_snapshot" });
+  if (events) items.push({ kind: "events", query: { topic: topic || undefined } });
+  if (logs) items.push({ kind: "logs", lines });
+  if (traceId.trim()) items.push({ kind: traceMode, trace_id: traceId.trim() });
+  const request: ChatContextRequest = { items, budget_bytes: budget };
+  const key = JSON.stringify(request);
+
+  useEffect(() => {
+    onChange(request);
+    setPreview(null);
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, [key]);
+
+  const showPreview = async () => {
+    try {
+      setPreview(await previewChatContext(request));
+      setError("");
+    } catch (e) {
+      setPreview(null);
+      setError(String(e));
+    }
+  };
+
+  return (
+    <div className="yx-chat-context">
+      <div className="yx-input-row">
+        <label>
+          <input type="checkbox" checked={law} onChange={(e) => setLaw(e.target.checked)} /> law snapshot
+        </label>
+        <label>
+          <input type="checkbox" checked={events} onChange={(e) => setEvents(e.target.checked)} /> events
+        </label>
+        <input type="text" placeholder="topic glob" value={topic} disabled={!events} onChange={(e) => setTopic(e.target.value)} />
+        <label>
+          <input type="checkbox" checked={logs} onChange={(e) => setLogs(e.target.checked)} /> logs
+        </label>
+        <input type="number" min={1} value={lines} disabled={!logs} onChange={(e) => setLines(Number(e.target.value) || 1)} />
+      </div>
+      <div className="yx-input-row">
+        <input type="text" placeholder="trace id" value={traceId} onChange={(e) => setTraceId(e.target.value)} />
+        <select value={traceMode} onChange={(e) => setTraceMode(e.target.value as "command_result" | "trace")}>
+          <option value="trace">trace timeline</option>
+          <option value="command_result">command result</option>
+        </select>
+        <input type="number" min={256} step={1024} title="budget (bytes)" value={budget} onChange={(e) => setBudget(Number(e.target.value) || DEFAULT_BUDGET)} />
+        <button type="button" disabled={!items.length} onClick={showPreview}>
+          Preview
+        </button>
+      </div>
+      {preview ? (
+        <div className="yx-muted">
+          {preview.used_bytes} / {preview.budget_bytes} bytes:{" "}
+          {preview.blocks.map((b) => `${b.label} (${b.count}${b.truncated ? ", truncated" : ""})`).join(", ")}
+        </div>
+      ) : null}
+      {error ? <div className="yx-error">{error}</div> : null}
+    </div>
+  );
+}
@@ -0,0 +1,117 @@
+import React, { useEffect, useState } from "react";
+import { downPreflight, runDown } from "../api/bridge";
+import { Card } from "./Card";
+import type { DownPreflight, DownProgress } from "../state/types";
+
+/** Guarded down of the selected workspace: preflight, confirmation, then progress until the socket is gone. */
+export function DownPanel({ onClose }: { onClose: () => void }) {
+  const [preflight, setPreflight] = useState<DownPreflight | null>(null);
+  const [force, setForce] = useState(false);
+  const [shutdown, setShutdown] = useState(false);
+  const [confirm, setConfirm] = useState("");
+  const [progress, setProgress] = useState<DownProgress[]>([]);
+  const [running, setRunning] = useState(false);
+  const [error, setError] = useState("");
+
+  const check = () => {
+    setError("");
+    downPreflight()
+      .then(setPreflight)
+      .catch((e) => setError(String(e)));
+  };
+
+  useEffect(check, []);
+
+  const blocked = Boolean(preflight?.blockers.length) && !force;
+  const unconfirmed = Boolean(preflight?.confirm_required) && confirm.trim() !== preflight?.ws;
+
+  const run = async () => {
+    setRunning(true);
+    setError("");
+    setProgress([]);
+    try {
+      await runDown({ force, shutdown, confirm: confirm.trim() || undefined }, (p) =>
+        setProgress((prev) => [...prev, p]),
+      );
+    } catch (e) {
+      // arming_required, confirmation_required and down_blocked come back as "code: message"
+      setError(String(e));
+    } finally {
+      setRunning(false);
+    }
+  };
+
+  return (
+    <Card
+      title={`Down ${preflight?.ws ?? ""}`}
+      subtitle="Stops the workspace daemon; needs an arming session covering down"
+      actions={
+        <button type="button" disabled={running} onClick={onClose}>
+          Close
+        </button>
+      }
+    >
+      {!preflight ? (
+        <div className="yx-muted">Running preflight…</div>
+      ) : (
+        <div className="yx-down">
+          <div>
+            daemon: {preflight.reachable ? "reachable" : "unreachable"}
+            {preflight.law_state ? ` · law ${preflight.law_state}` : ""}
+          </div>
+          <div>
+            attached providers:{" "}
+            {preflight.attached_providers.length ? preflight.attached_providers.map((p) => p.id).join(", ") : "none"}
+          </div>
+          <div>
+            open PTYs:{" "}
+            {preflight.pty_sessions.length
+              ? preflight.pty_sessions.map((s) => `${s.pty_id} (${s.cmd ?? "shell"})`).join(", ")
+              : "none"}
+          </div>
+          <div>
+            in flight: {preflight.in_flight.length ? preflight.in_flight.map((c) => c.name).join(", ") : "none"}
+          </div>
+          {preflight.blockers.map((b) => (
+            <div key={b} className="yx-terminal__stderr">
+              {b}
+            </div>
+          ))}
+          <div className="yx-toolbar">
+            <label>
+              <input type="checkbox" checked={force} onChange={(e) => setForce(e.target.checked)} /> force
+            </label>
+            <label>
+              <input type="checkbox" checked={shutdown} onChange={(e) => setShutdown(e.target.checked)} /> shutdown
+            </label>
+            <button type="button" disabled={running} onClick={check}>
+              Re-check
+            </button>
+          </div>
+          {preflight.confirm_required ? (
+            <input
+              type="text"
+              placeholder={`type ${preflight.ws} to confirm`}
+              value={confirm}
+              onChange={(e) => setConfirm(e.target.value)}
+            />
+          ) : null}
+          <button type="button" className="yx-down__go" disabled={running || blocked || unconfirmed} onClick={run}>
+            {running ? "Stopping…" : `Take ${preflight.ws} down`}
+          </button>
+        </div>
+      )}
+      {error ? <div className="yx-terminal__stderr">{error}</div> : null}
+      {progress.length ? (
+        <ol className="yx-down__progress">
+          {progress.map((p) => (
+            <li key={`${p.phase}-${p.ts_ms}`} className={`yx-down__phase yx-down__phase--${p.phase}`}>
+              {new Date(p.ts_ms).toLocaleTimeString()} · {p.message}
+              {p.trace_id ? <span className="yx-muted"> · {p.trace_id}</span> : null}
+            </li>
+          ))}
+        </ol>
+      ) : null}
+    </Card>
+  );
+}
@@ -1,12 +1,14 @@
 import React from "react";
-import type { EventItem } from "../state/types";
+import { severityRank } from "../state/types";
+import type { EventItem, Severity } from "../state/types";
 import { Badge } from "./Badge";
 
-function toneFromSeverity(sev: string): "ok" | "warn" | "deny" | "info" | "neutral" {
-  const s = sev.toLowerCase();
-  if (s === "error" || s === "fatal") return "deny";
-  if (s === "warn" || s === "warning") return "warn";
-  if (s === "info") return "info";
+function toneFromSeverity(sev: Severity): "ok" | "warn" | "deny" | "info" | "neutral" {
+  const rank = severityRank(sev);
+  if (rank === null) return "neutral";
+  if (rank >= severityRank("error")!) return "deny";
+  if (rank === severityRank("warn")) return "warn";
+  if (rank >= severityRank("info")!) return "info";
   return "neutral";
 }
 
@@ -25,7 +27,7 @@ export function FeedList({ items, onSelect }: { items: EventItem[]; onSelect?: (
           <div>{item.topic}</div>
           <Badge tone={toneFromSeverity(item.severity)} text={item.severity.toUpperCase()} />
           <div className="yx-feed__summary">
-            {typeof item.payload === "string" ? item.payload : JSON.stringify(item.payload)}
+            {item.msg ?? JSON.stringify(item.data)}
           </div>
         </button>
       ))}
@@ -0,0 +1,148 @@
+import React, { useEffect, useState } from "react";
+import { Card } from "./Card";
+import { pairingSessions, pairingStep } from "../api/bridge";
+import type { PairingSession } from "../state/types";
+
+const STEPS = ["discover", "pick", "pair", "verify", "attach"] as const;
+
+export function PairingWizard({ onChanged }: { onChanged: () => void }) {
+  const [session, setSession] = useState<PairingSession | null>(null);
+  const [endpoint, setEndpoint] = useState("");
+  const [model, setModel] = useState("");
+  const [candidate, setCandidate] = useState("");
+  const [pickedModel, setPickedModel] = useState("");
+  const [error, setError] = useState("");
+  const [busy, setBusy] = useState(false);
+
+  // resume the newest unfinished session, e.g. after a restart
+  useEffect(() => {
+    pairingSessions()
+      .then((open) => setSession(open[0] ?? null))
+      .catch(() => undefined);
+  }, []);
+
+  async function step(name: Parameters<typeof pairingStep>[0], args: Record<string, unknown> = {}) {
+    setBusy(true);
+    try {
+      const next = await pairingStep(name, session && name !== "start" ? { id: session.id, ...args } : args);
+      setSession(next);
+      setError("");
+      if (name === "pair" || name === "attach") onChanged();
+    } catch (e) {
+      setError(String(e));
+      // keep the session's recorded error and step in sync
+      pairingSessions(true)
+        .then((all) => {
+          const current = all.find((s) => s.id === session?.id);
+          if (current) setSession(current);
+        })
+        .catch(() => undefined);
+    } finally {
+      setBusy(false);
+    }
+  }
+
+  const selected = session?.candidates.find((c) => c.id === candidate);
+  const closed = !session || session.step === "done" || session.step === "cancelled";
+
+  return (
+    <Card title="Pair a provider" subtitle={session ? `${session.id} · ${session.step}` : "Discover, pick, pair, verify, attach"}>
+      {session && !closed ? (
+        <div className="yx-muted">
+          {STEPS.map((s) => (s === session.step ? `[${s}]` : s)).join(" → ")}
+        </div>
+      ) : null}
+
+      {closed ? (
+        <>
+          <div className="yx-input-grid">
+            <input type="text" placeholder="endpoint hint" value={endpoint} onChange={(e) => setEndpoint(e.target.value)} />
+            <input type="text" placeholder="model hint" value={model} onChange={(e) => setModel(e.target.value)} />
+          </div>
+          <div className="yx-providers__actions" style={{ marginTop: 8 }}>
+            <button type="button" disabled={busy} onClick={() => step("start", { endpoint: endpoint || null, model: model || null })}>
+              Discover
+            </button>
+          </div>
+        </>
+      ) : null}
+
+      {session?.step === "discover" ? (
+        <div className="yx-providers__actions">
+          <button type="button" disabled={busy} onClick={() => step("rediscover")}>Discover again</button>
+        </div>
+      ) : null}
+
+      {session && (session.step === "pick" || session.step === "pair") ? (
+        <>
+          <div className="yx-input-grid">
+            <select value={candidate} onChange={(e) => setCandidate(e.target.value)}>
+              <option value="">candidate…</option>
+              {session.candidates.map((c) => (
+                <option key={c.id} value={c.id}>
+                  {c.id} ({c.endpoint})
+                </option>
+              ))}
+            </select>
+            {selected?.models?.length ? (
+              <select value={pickedModel} onChange={(e) => setPickedModel(e.target.value)}>
+                <option value="">model…</option>
+                {selected.models.map((m) => (
+                  <option key={m} value={m}>
+                    {m}
+                  </option>
+                ))}
+              </select>
+            ) : (
+              <input type="text" placeholder="model" value={pickedModel} onChange={(e) => setPickedModel(e.target.value)} />
+            )}
+          </div>
+          <div className="yx-providers__actions" style={{ marginTop: 8 }}>
+            <button type="button" disabled={busy || !candidate} onClick={() => step("pick", { candidate, model: pickedModel || null })}>
+              Pick
+            </button>
+            <button type="button" disabled={busy} onClick={() => step("rediscover")}>Discover again</button>
+            {session.step === "pair" ? (
+              <button type="button" disabled={busy} onClick={() => step("pair")}>
+                Pair {session.picked?.id} / {session.picked?.model}
+              </button>
+            ) : null}
+          </div>
+        </>
+      ) : null}
+
+      {session?.step === "verify" ? (
+        <div className="yx-providers__actions">
+          <button type="button" disabled={busy} onClick={() => step("verify")}>Send test request</button>
+        </div>
+      ) : null}
+
+      {session?.step === "attach" ? (
+        <>
+          <div className="yx-muted">
+            Verified via {session.verification?.method} in {session.verification?.latency_ms} ms: {session.verification?.detail}
+          </div>
+          <div className="yx-providers__actions" style={{ marginTop: 8 }}>
+            <button type="button" disabled={busy} onClick={() => step("attach")}>Attach</button>
+            <button type="button" disabled={busy} onClick={() => step("finish")}>Finish without attaching</button>
+          </div>
+        </>
+      ) : null}
+
+      {session?.step === "done" ? (
+        <div className="yx-muted">
+          Paired {session.picked?.id}
+          {session.attached ? " and attached" : ""}.
+        </div>
+      ) : null}
+
+      {session && !closed ? (
+        <div className="yx-providers__actions" style={{ marginTop: 8 }}>
+          <button type="button" disabled={busy} onClick={() => step("cancel")}>Cancel</button>
+        </div>
+      ) : null}
+
+      {error ? <div className="yx-error">{error}</div> : null}
+    </Card>
+  );
+}
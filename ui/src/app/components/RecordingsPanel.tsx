@@ -0,0 +1,146 @@
+import React, { useEffect, useRef, useState } from "react";
+import { getRecording, listRecordings, recordingCast } from "../api/bridge";
+import type { Recording, RecordingSummary } from "../state/types";
+
+// colours, cursor moves and title sequences; the player shows plain text
+const ANSI = /\x1b\[[0-9;?]*[ -/]*[@-~]|\x1b\][^\x07]*(\x07|\x1b\\)|\x1b[()][0-9A-B]/g;
+
+function plain(data: string) {
+  return data.replace(ANSI, "").replace(/\r\n/g, "\n").replace(/\r/g, "");
+}
+
+function download(name: string, content: string) {
+  const url = URL.createObjectURL(new Blob([content], { type: "application/x-asciicast" }));
+  const a = document.createElement("a");
+  a.href = url;
+  a.download = name;
+  a.click();
+  URL.revokeObjectURL(url);
+}
+
+function duration(secs: number) {
+  const s = Math.round(secs);
+  return s >= 60 ? `${Math.floor(s / 60)}m ${s % 60}s` : `${s}s`;
+}
+
+/** PTY recordings of the workspace with a text player and .cast download. */
+export function RecordingsPanel() {
+  const [items, setItems] = useState<RecordingSummary[]>([]);
+  const [current, setCurrent] = useState<Recording | null>(null);
+  const [shown, setShown] = useState(0);
+  const [playing, setPlaying] = useState(false);
+  const [showInput, setShowInput] = useState(false);
+  const [error, setError] = useState("");
+  const timers = useRef<number[]>([]);
+
+  const stop = () => {
+    timers.current.forEach((id) => window.clearTimeout(id));
+    timers.current = [];
+    setPlaying(false);
+  };
+
+  const refresh = () => {
+    listRecordings()
+      .then(setItems)
+      .catch((e) => setError(String(e)));
+  };
+
+  useEffect(() => {
+    refresh();
+    return stop;
+  }, []);
+
+  const open = async (id: string) => {
+    stop();
+    setError("");
+    try {
+      const recording = await getRecording(id);
+      setCurrent(recording);
+      setShown(recording.events.length);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const play = (speed: number) => {
+    if (!current) return;
+    stop();
+    setShown(0);
+    setPlaying(true);
+    current.events.forEach(([t], i) => {
+      timers.current.push(window.setTimeout(() => setShown(i + 1), (t * 1000) / speed));
+    });
+    const end = current.summary.duration_secs * 1000;
+    timers.current.push(window.setTimeout(() => setPlaying(false), end / speed));
+  };
+
+  const save = async (id: string) => {
+    try {
+      download(`${id}.cast`, await recordingCast(id));
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <div className="yx-recordings">
+      <div className="yx-terminal__header">
+        <div>Recordings</div>
+        <div className="yx-terminal__controls">
+          <button type="button" onClick={refresh}>
+            Refresh
+          </button>
+        </div>
+      </div>
+      {error ? <div className="yx-terminal__stderr">{error}</div> : null}
+      {items.length === 0 ? (
+        <div className="yx-muted">No PTY sessions recorded.</div>
+      ) : (
+        <div className="yx-list-compact">
+          {items.map((r) => (
+            <button key={r.id} type="button" onClick={() => open(r.id)}>
+              {new Date(r.started_ms).toLocaleString()} · {r.title || r.id} · {r.operator_id ?? "unknown"} ·{" "}
+              {duration(r.duration_secs)}
+              {r.input ? " · input" : ""}
+            </button>
+          ))}
+        </div>
+      )}
+      {current ? (
+        <div className="yx-terminal__entry">
+          <div className="yx-terminal__controls">
+            <button type="button" onClick={() => play(1)}>
+              Play
+            </button>
+            <button type="button" onClick={() => play(4)}>
+              Play 4×
+            </button>
+            <button type="button" disabled={!playing} onClick={stop}>
+              Stop
+            </button>
+            <label>
+              <input type="checkbox" checked={showInput} onChange={(e) => setShowInput(e.target.checked)} /> input
+            </label>
+            <button type="button" onClick={() => save(current.summary.id)}>
+              Download .cast
+            </button>
+          </div>
+          <div className="yx-muted">
+            {current.summary.id} · {current.summary.ws} · {current.summary.width}×{current.summary.height}
+          </div>
+          <pre className="yx-recordings__screen">
+            {current.events.slice(0, shown).map(([t, code, data], i) =>
+              code === "o" ? (
+                <span key={i}>{plain(data)}</span>
+              ) : showInput && code === "i" ? (
+                <span key={i} className="yx-recordings__input" title={`${t.toFixed(2)}s`}>
+                  {plain(data)}
+                </span>
+              ) : null,
+            )}
+          </pre>
+        </div>
+      ) : null}
+    </div>
+  );
+}
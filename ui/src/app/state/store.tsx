@@ -21,6 +21,7 @@ export type Action =
   | { type: "connection/set"; payload: Partial<ConnectionState> }
   | { type: "workspaces/set"; selected_ws?: string; items?: WorkspaceInfo[] }
   | { type: "events/add"; event: EventItem }
+  | { type: "events/batch"; events: EventItem[]; logs: EventItem[] }
   | { type: "logs/add"; log: EventItem }
   | { type: "feeds/clear" }
   | { type: "command/add"; item: CommandHistoryItem }
@@ -82,6 +83,12 @@ function reducer(state: AppState, action: Action): AppState {
       const events = [action.event, ...state.events].slice(0, MAX_EVENTS);
       return { ...state, events };
     }
+    case "events/batch": {
+      // batches arrive oldest first, the feeds are newest first
+      const events = [...action.events].reverse().concat(state.events).slice(0, MAX_EVENTS);
+      const logs = action.logs.length ? [...action.logs].reverse().concat(state.logs).slice(0, MAX_LOGS) : state.logs;
+      return { ...state, events, logs };
+    }
     case "logs/add": {
       const logs = [action.log, ...state.logs].slice(0, MAX_LOGS);
       return { ...state, logs };
@@ -20,11 +20,27 @@ export type WorkspacesState = {
   items: WorkspaceInfo[];
 };
 
+export const SEVERITIES = ["trace", "debug", "info", "notice", "warn", "error", "critical"] as const;
+
+/** Known levels from least to most severe; unknown daemon levels pass through verbatim. */
+export type Severity = (typeof SEVERITIES)[number] | (string & {});
+
+/** Position of a known severity, lowest first; `null` for levels yx does not know. */
+export function severityRank(severity: Severity): number | null {
+  const idx = (SEVERITIES as readonly string[]).indexOf(severity);
+  return idx < 0 ? null : idx;
+}
+
 export type EventItem = {
   topic: string;
-  severity: string;
+  severity: Severity;
   ts_ms: number;
-  payload: Record<string, unknown> | unknown;
+  event_id?: string | null;
+  ws?: string | null;
+  seq?: number | null;
+  msg?: string | null;
+  data: Record<string, unknown> | unknown;
+  compliance?: unknown;
   trace_id?: string | null;
 };
 
@@ -68,17 +84,217 @@ export type ShellEntry = {
   stdout: string;
   stderr: string;
   exit_code: number;
+  /** Interleaved output of a streamed run, in arrival order. */
+  chunks?: ShellChunk[];
+  signal?: string | null;
+  timed_out?: boolean;
+  truncated?: boolean;
+  trace_id?: string;
 };
 
+export type ShellExecRequest = {
+  cmd: string;
+  args?: string[];
+  cwd?: string;
+  env?: Record<string, string>;
+  stdin?: string;
+  timeout_ms?: number;
+  max_output_bytes?: number;
+};
+
+export type ShellChunk = {
+  trace_id: string;
+  seq: number;
+  stream: "stdout" | "stderr";
+  data: string;
+  ts_ms: number;
+};
+
+export type ShellExit = {
+  trace_id: string;
+  exit_code: number | null;
+  signal: string | null;
+  timed_out: boolean;
+  truncated: boolean;
+  stdout_bytes: number;
+  stderr_bytes: number;
+  duration_ms: number;
+};
+
+export type ShellOutput = ({ type: "chunk" } & ShellChunk) | ({ type: "exit" } & ShellExit);
+
+export type PtyOpenRequest = {
+  cmd?: string;
+  args?: string[];
+  cwd?: string;
+  env?: Record<string, string>;
+  cols?: number;
+  rows?: number;
+  record?: boolean;
+  record_input?: boolean;
+  title?: string;
+};
+
+export type PtySession = {
+  pty_id: string;
+  trace_id: string;
+  ws: string;
+  operator_id: string;
+  cmd: string | null;
+  cols: number;
+  rows: number;
+  opened_ms: number;
+  /** Recording id, when recorded. */
+  recording: string | null;
+  record_input: boolean;
+};
+
+export type PtyOutput =
+  | { type: "data"; pty_id: string; data: string; ts_ms: number }
+  | { type: "exit"; pty_id: string; exit_code: number | null; signal: string | null };
+
+/** asciicast v2 event: seconds since start, "o" output or "i" input, data. */
+export type CastEvent = [number, string, string];
+
+export type RecordingSummary = {
+  id: string;
+  ws: string;
+  operator_id: string | null;
+  pty_id: string | null;
+  title: string | null;
+  width: number;
+  height: number;
+  started_ms: number;
+  duration_secs: number;
+  events: number;
+  input: boolean;
+  bytes: number;
+};
+
+export type Recording = {
+  summary: RecordingSummary;
+  header: Record<string, unknown>;
+  events: CastEvent[];
+};
+
+export type InFlightCommand = { trace_id: string; ws: string; name: string; started_ms: number };
+
+export type DownRequest = {
+  force?: boolean;
+  shutdown?: boolean;
+  /** The workspace name typed back, where confirmation is required. */
+  confirm?: string;
+  timeout_secs?: number;
+};
+
+export type DownPreflight = {
+  ws: string;
+  socket_path: string;
+  reachable: boolean;
+  status: Record<string, unknown> | null;
+  law_state: string | null;
+  attached_providers: ProviderItem[];
+  pty_sessions: PtySession[];
+  in_flight: InFlightCommand[];
+  confirm_required: boolean;
+  /** Why down needs force; empty when it does not. */
+  blockers: string[];
+};
+
+export type DownPhase =
+  | "preflight"
+  | "confirmed"
+  | "sending"
+  | "accepted"
+  | "stopping"
+  | "unresponsive"
+  | "stopped"
+  | "timed_out"
+  | "failed";
+
+export type DownProgress = {
+  ws: string;
+  phase: DownPhase;
+  ts_ms: number;
+  message: string;
+  trace_id: string | null;
+  detail?: unknown;
+};
+
+export type DownOutcome = {
+  ws: string;
+  trace_id: string;
+  preflight: DownPreflight;
+  phase: DownPhase;
+  waited_ms: number;
+};
+
+export const PROVIDER_STATES = ["discovered", "paired", "attached", "detached", "revoked", "error"] as const;
+export type ProviderLifecycle = (typeof PROVIDER_STATES)[number];
+
 export type ProviderItem = {
   id: string;
+  state: ProviderLifecycle;
   endpoint?: string;
   model?: string;
-  state?: string;
-  last_seen?: number;
+  models?: string[];
+  last_seen_ms?: number;
   last_error?: string;
 };
 
+export type ProviderProbe = {
+  id: string;
+  ts_ms: number;
+  state: ProviderLifecycle;
+  method: string;
+  ok: boolean;
+  latency_ms?: number;
+  error?: string;
+  status: HealthStatus;
+};
+
+export type HealthStatus = "up" | "degraded" | "down" | "unknown";
+
+export type ProviderHealth = {
+  id: string;
+  state: ProviderLifecycle;
+  status: HealthStatus;
+  since_ms: number;
+  last_probe_ms: number;
+  latency_ms?: number;
+  avg_latency_ms?: number;
+  checks: number;
+  failures: number;
+  uptime_pct: number;
+  recent_failures: ProviderProbe[];
+};
+
+export type ProviderTransition = {
+  command: string;
+  id: string;
+  from?: ProviderLifecycle;
+  to: ProviderLifecycle;
+  provider?: ProviderItem;
+  trace_id: string;
+};
+
+export type PairingStep = "discover" | "pick" | "pair" | "verify" | "attach" | "done" | "cancelled";
+
+export type PairingSession = {
+  id: string;
+  ws: string;
+  step: PairingStep;
+  created_ms: number;
+  updated_ms: number;
+  endpoint_hint?: string;
+  model_hint?: string;
+  candidates: ProviderItem[];
+  picked?: ProviderItem;
+  verification?: { ts_ms: number; method: string; latency_ms: number; detail: string };
+  attached: boolean;
+  last_error?: { code: string; [key: string]: unknown };
+};
+
 export type ProviderState = {
   items: ProviderItem[];
   active: ProviderItem | null;
@@ -88,12 +304,131 @@ export type ProviderState = {
 export type ChatSession = {
   id: string;
   title?: string;
+  created_ms?: number;
+  updated_ms?: number;
+  provider?: string;
+  model?: string;
+  message_count?: number;
 };
 
 export type ChatMessage = {
+  id?: string;
   role: string;
   content: string;
   ts_ms?: number;
+  provider?: string;
+  model?: string;
+  /** Trace of the chat.send that produced the message. */
+  trace_id?: string;
+  /** References to the context attached when it was sent. */
+  context?: ChatContextRef[];
+  /** Tokens and latency of a reply, when the daemon reported them. */
+  usage?: ChatUsage;
+};
+
+export type ChatUsage = {
+  prompt_tokens?: number;
+  completion_tokens?: number;
+  total_tokens?: number;
+  latency_ms?: number;
+};
+
+export type ChatContextRef = {
+  kind: string;
+  label: string;
+  id?: string;
+  count: number;
+  bytes: number;
+  truncated: boolean;
+};
+
+export type ChatContextItem =
+  | { kind: "law_snapshot" }
+  | { kind: "events"; query: { topic?: string; severity?: string; since_ms?: number; limit?: number } }
+  | { kind: "logs"; lines?: number }
+  | { kind: "command_result"; trace_id: string }
+  | { kind: "trace"; trace_id: string };
+
+export type ChatContextRequest = {
+  items: ChatContextItem[];
+  budget_bytes?: number;
+};
+
+export type ChatContext = {
+  ws: string;
+  budget_bytes: number;
+  used_bytes: number;
+  blocks: (ChatContextRef & { data: unknown })[];
+};
+
+/** `cached_ms` is set when served from the local cache (daemon unreachable). */
+export type ChatSessionList = {
+  items: ChatSession[];
+  selected?: string;
+  cached_ms?: number;
+};
+
+export type ChatHistory = {
+  session: ChatSession;
+  items: ChatMessage[];
+  cached_ms?: number;
+};
+
+export type ChatExchange = {
+  session_id: string;
+  trace_id: string;
+  sent: ChatMessage;
+  reply?: ChatMessage;
+};
+
+export type ChatSearchHit = {
+  session_id: string;
+  session_title?: string;
+  index: number;
+  role: string;
+  ts_ms?: number;
+  trace_id?: string;
+  score: number;
+  highlight: { field: string; snippet: string; ranges: [number, number][] };
+};
+
+export type ChatSearchResults = {
+  ws: string;
+  total: number;
+  hits: ChatSearchHit[];
+};
+
+export type UsageGroupBy = "session" | "provider" | "model" | "workspace" | "day";
+
+export type UsageQuery = {
+  ws?: string;
+  all_workspaces?: boolean;
+  since_ms?: number;
+  until_ms?: number;
+  session_id?: string;
+  group_by?: UsageGroupBy;
+};
+
+export type UsageRow = {
+  key: string;
+  requests: number;
+  prompt_tokens: number;
+  completion_tokens: number;
+  total_tokens: number;
+  /** Requests the daemon reported no token counts for. */
+  unreported: number;
+  avg_latency_ms: number;
+  /** Cost of the priced requests; null when no price rule matched. */
+  cost: number | null;
+  unpriced: number;
+};
+
+export type UsageReport = {
+  currency: string;
+  group_by: UsageGroupBy;
+  workspaces: string[];
+  totals: UsageRow;
+  rows: UsageRow[];
 };
 
 export type ChatState = {
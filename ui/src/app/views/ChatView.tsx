@@ -1,24 +1,39 @@
 import React, { useEffect, useState } from "react";
 import { useAppDispatch, useAppState } from "../state/store.tsx";
-import { executeCommand } from "../core/commands";
-import type { ChatMessage, ChatSession } from "../state/types";
-
-function normalizeSessions(result: any): { items: ChatSession[]; selected: string | null } {
-  if (!result) return { items: [], selected: null };
-  const items = result.items || result.sessions || [];
-  const normalized = Array.isArray(items)
-    ? items.map((s: any) => ({ id: String(s.id || s.session_id || s.name), title: s.title }))
-    : [];
-  return { items: normalized, selected: result.selected || (normalized[0]?.id ?? null) };
+import { chatHistory, chatSessions, exportChat, newChatSession, searchChat, sendChat } from "../api/bridge";
+import { ChatContextPicker } from "../components/ChatContextPicker";
+import type { ChatContextRequest, ChatMessage, ChatSearchHit } from "../state/types";
+
+function stamp(ms?: number) {
+  return ms ? new Date(ms).toLocaleString() : "";
+}
+
+function messageMeta(m: ChatMessage) {
+  const tokens = m.usage?.total_tokens != null ? `${m.usage.total_tokens} tokens` : "";
+  const latency = m.usage?.latency_ms != null ? `${m.usage.latency_ms} ms` : "";
+  return [stamp(m.ts_ms), m.provider, m.model, tokens, latency, m.trace_id].filter(Boolean).join(" · ");
 }
 
-function normalizeMessages(result: any): ChatMessage[] {
-  if (!result) return [];
-  const items = result.items || result.messages || result.history || [];
-  if (Array.isArray(items)) {
-    return items.map((m: any) => ({ role: String(m.role || "assistant").toLowerCase(), content: m.content || "" }));
-  }
-  return [];
+function download(name: string, content: string, type: string) {
+  const url = URL.createObjectURL(new Blob([content], { type }));
+  const a = document.createElement("a");
+  a.href = url;
+  a.download = name;
+  a.click();
+  URL.revokeObjectURL(url);
+}
+
+function Snippet({ hit }: { hit: ChatSearchHit }) {
+  const { snippet, ranges } = hit.highlight;
+  const parts: React.ReactNode[] = [];
+  let at = 0;
+  ranges.forEach(([start, end], i) => {
+    if (start < at) return;
+    parts.push(snippet.slice(at, start), <mark key={i}>{snippet.slice(start, end)}</mark>);
+    at = end;
+  });
+  parts.push(snippet.slice(at));
+  return <span>{parts}</span>;
 }
 
 export function ChatView() {
@@ -26,46 +41,76 @@ export function ChatView() {
   const dispatch = useAppDispatch();
   const [text, setText] = useState("");
   const [newTitle, setNewTitle] = useState("");
+  const [query, setQuery] = useState("");
+  const [hits, setHits] = useState<ChatSearchHit[] | null>(null);
+  const [cachedMs, setCachedMs] = useState<number | undefined>();
+  const [error, setError] = useState("");
+  const [context, setContext] = useState<ChatContextRequest>({ items: [] });
 
-  const loadSessions = async () => {
-    const res = await executeCommand(dispatch, "chat.sessions.list");
-    if (res.ok) {
-      const parsed = normalizeSessions(res.result as any);
-      dispatch({ type: "chat/sessions", sessions: parsed.items, selected: parsed.selected });
-      if (parsed.selected) {
-        await loadHistory(parsed.selected);
-      }
+  const loadHistory = async (sessionId: string) => {
+    try {
+      const history = await chatHistory(sessionId);
+      dispatch({ type: "chat/messages", messages: history.items });
+      setCachedMs(history.cached_ms);
+      setError("");
+    } catch (e) {
+      setError(String(e));
     }
   };
 
-  const loadHistory = async (session_id: string) => {
-    const res = await executeCommand(dispatch, "chat.history", { session_id });
-    if (res.ok) {
-      dispatch({ type: "chat/messages", messages: normalizeMessages(res.result as any) });
+  const loadSessions = async (select?: string) => {
+    try {
+      const list = await chatSessions();
+      const selected = select ?? chat.selectedSession ?? list.selected ?? list.items[0]?.id ?? null;
+      dispatch({ type: "chat/sessions", sessions: list.items, selected });
+      setCachedMs(list.cached_ms);
+      if (selected) await loadHistory(selected);
+    } catch (e) {
+      setError(String(e));
     }
   };
 
   const createSession = async () => {
-    const res = await executeCommand(dispatch, "chat.session.new", { title: newTitle || undefined });
-    if (res.ok) {
-      await loadSessions();
+    try {
+      const session = await newChatSession(newTitle || undefined);
       setNewTitle("");
+      await loadSessions(session.id);
+    } catch (e) {
+      setError(String(e));
     }
   };
 
   const send = async () => {
-    if (!text.trim()) return;
-    const session_id = chat.selectedSession || undefined;
-    const res = await executeCommand(dispatch, "chat.send", { session_id, text });
-    if (res.ok) {
-      const result = res.result as any;
-      const message = result?.message || result?.item;
-      if (message) {
-        dispatch({ type: "chat/append", message: { role: message.role || "assistant", content: message.content || "" } });
-      } else {
-        dispatch({ type: "chat/append", message: { role: "assistant", content: "(ok)" } });
-      }
+    if (!text.trim() || !chat.selectedSession) return;
+    try {
+      const exchange = await sendChat(chat.selectedSession, text, context);
+      dispatch({ type: "chat/append", message: exchange.sent });
+      if (exchange.reply) dispatch({ type: "chat/append", message: exchange.reply });
       setText("");
+      setError("");
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const search = async () => {
+    if (!query.trim()) {
+      setHits(null);
+      return;
+    }
+    const results = await searchChat(query).catch(() => null);
+    setHits(results?.hits ?? []);
+  };
+
+  const exportAs = async (format: "markdown" | "json") => {
+    const id = chat.selectedSession;
+    if (!id) return;
+    try {
+      const doc = await exportChat(id, format);
+      if (format === "json") download(`chat-${id}.json`, doc, "application/json");
+      else download(`chat-${id}.md`, doc, "text/markdown");
+    } catch (e) {
+      setError(String(e));
     }
   };
 
@@ -74,13 +119,15 @@ export function ChatView() {
     // eslint-disable-next-line react-hooks/exhaustive-deps
   }, []);
 
+  const current = chat.sessions.find((s) => s.id === chat.selectedSession);
+
   return (
     <div className="yx-chat-view">
       <div className="yx-chat-grid">
         <section className="yx-panel yx-chat-sessions">
           <header>
             <h3>Sessions</h3>
-            <p className="yx-muted">Select or create</p>
+            <p className="yx-muted">{cachedMs ? `Offline: cached ${stamp(cachedMs)}` : "Select or create"}</p>
           </header>
           <div className="yx-input-row">
             <input
@@ -105,16 +152,63 @@ export function ChatView() {
             {chat.sessions.map((s) => (
               <option key={s.id} value={s.id}>
                 {s.title || s.id}
+                {s.message_count != null ? ` (${s.message_count})` : ""}
               </option>
             ))}
           </select>
+
+          <div className="yx-input-row" style={{ marginTop: 12 }}>
+            <input
+              type="text"
+              placeholder='search all sessions, "phrases"'
+              value={query}
+              onChange={(e) => setQuery(e.target.value)}
+              onKeyDown={(e) => {
+                if (e.key === "Enter") search();
+              }}
+            />
+            <button type="button" onClick={search}>
+              Search
+            </button>
+          </div>
+          {hits ? (
+            <div className="yx-chat-hits">
+              {hits.length === 0 ? <div className="yx-muted">No matches.</div> : null}
+              {hits.map((hit) => (
+                <button
+                  type="button"
+                  key={`${hit.session_id}-${hit.index}`}
+                  className="yx-chat-hit"
+                  onClick={() => {
+                    dispatch({ type: "chat/sessions", sessions: chat.sessions, selected: hit.session_id });
+                    loadHistory(hit.session_id);
+                  }}
+                >
+                  <span className="yx-muted">
+                    {hit.session_title || hit.session_id} · {hit.role} {stamp(hit.ts_ms)}
+                  </span>
+                  <Snippet hit={hit} />
+                </button>
+              ))}
+            </div>
+          ) : null}
         </section>
 
         <section className="yx-panel yx-chat-main">
           <header>
             <h3>Transcript</h3>
-            <p className="yx-muted">Latest messages</p>
+            <p className="yx-muted">
+              {current ? [current.provider, current.model, stamp(current.updated_ms)].filter(Boolean).join(" · ") || current.id : "Latest messages"}
+            </p>
           </header>
+          <div className="yx-input-row">
+            <button type="button" disabled={!chat.selectedSession} onClick={() => exportAs("markdown")}>
+              Export Markdown
+            </button>
+            <button type="button" disabled={!chat.selectedSession} onClick={() => exportAs("json")}>
+              Export JSON
+            </button>
+          </div>
           <div className="yx-chat-transcript">
             {chat.messages.length === 0 ? (
               <div className="yx-muted">No messages yet.</div>
@@ -123,10 +217,17 @@ export function ChatView() {
                 <div key={`${m.role}-${idx}`} className="yx-chat-msg">
                   <b>{m.role}</b>
                   <span>{m.content}</span>
+                  {m.context?.length ? (
+                    <small className="yx-muted">
+                      context: {m.context.map((c) => `${c.label}${c.truncated ? " (truncated)" : ""}`).join(", ")}
+                    </small>
+                  ) : null}
+                  {messageMeta(m) ? <small className="yx-muted">{messageMeta(m)}</small> : null}
                 </div>
               ))
             )}
           </div>
+          <ChatContextPicker onChange={setContext} />
           <div className="yx-chat-compose">
             <input
               type="text"
@@ -137,10 +238,11 @@ export function ChatView() {
                 if (e.key === "Enter") send();
               }}
             />
-            <button type="button" onClick={send}>
+            <button type="button" disabled={!chat.selectedSession} onClick={send}>
               Send
             </button>
           </div>
+          {error ? <div className="yx-error">{error}</div> : null}
         </section>
       </div>
     </div>
@@ -20,7 +20,7 @@ export function LogsView() {
               ts_ms: evt.ts_ms,
               ok: true,
               request: { id: evt.trace_id || `log-${evt.ts_ms}`, ts_ms: evt.ts_ms, name: evt.topic, args: {} },
-              response: { id: evt.trace_id || `log-${evt.ts_ms}`, ts_ms: evt.ts_ms, name: evt.topic, ok: true, result: evt.payload },
+              response: { id: evt.trace_id || `log-${evt.ts_ms}`, ts_ms: evt.ts_ms, name: evt.topic, ok: true, result: evt.data },
             },
           })
         }
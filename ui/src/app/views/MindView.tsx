@@ -16,8 +16,8 @@ export function MindView() {
   const recentNodes = useMemo(() => {
     const seen = new Map<string, EventItem>();
     for (const evt of activations) {
-      const payload = evt.payload as any;
-      const id = String(payload?.id || payload?.node_id || payload?.node || payload?.entity || "unknown");
+      const data = evt.data as any;
+      const id = String(data?.id || data?.node_id || data?.node || data?.entity || "unknown");
       if (!seen.has(id)) {
         seen.set(id, evt);
       }
@@ -51,14 +51,14 @@ export function MindView() {
                         id: evt.trace_id || `mind-${evt.ts_ms}`,
                         ts_ms: evt.ts_ms,
                         name: evt.topic,
-                        args: (evt.payload as Record<string, unknown>) || {},
+                        args: (evt.data as Record<string, unknown>) || {},
                       },
                       response: {
                         id: evt.trace_id || `mind-${evt.ts_ms}`,
                         ts_ms: evt.ts_ms,
                         name: evt.topic,
                         ok: true,
-                        result: evt.payload,
+                        result: evt.data,
                       },
                     },
                   })
@@ -67,7 +67,7 @@ export function MindView() {
                 <div className="yx-feed__time">{new Date(evt.ts_ms).toLocaleTimeString()}</div>
                 <div>{evt.topic}</div>
                 <div className="yx-feed__summary">
-                  {typeof evt.payload === "string" ? evt.payload : JSON.stringify(evt.payload)}
+                  {evt.msg ?? JSON.stringify(evt.data)}
                 </div>
                 <div className="yx-feed__summary">{evt.trace_id}</div>
               </button>
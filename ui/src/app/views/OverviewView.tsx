@@ -1,10 +1,12 @@
 import React from "react";
-import React, { useMemo, useState } from "react";
+import React, { useEffect, useMemo, useState } from "react";
 import { Card } from "../components/Card";
+import { DownPanel } from "../components/DownPanel";
 import { FeedList } from "../components/FeedList";
 import { useAppDispatch, useAppState } from "../state/store.tsx";
 import { executeCommand } from "../core/commands";
 import { connectionBadge } from "../core/connection";
+import { getEventStats } from "../api/bridge";
 
 export function OverviewView() {
   const { connection, events, logs, commandHistory, providers } = useAppState();
@@ -12,6 +14,7 @@ export function OverviewView() {
   const badge = connectionBadge(connection);
   const [eventFilter, setEventFilter] = useState<string>("all");
   const [logFilter, setLogFilter] = useState<string>("all");
+  const [downOpen, setDownOpen] = useState(false);
 
   const filteredEvents = useMemo(() => {
     if (eventFilter === "all") return events;
@@ -22,6 +25,18 @@ export function OverviewView() {
     return logs.filter((evt) => evt.severity.toLowerCase() === logFilter);
   }, [logs, logFilter]);
 
+  const [stats, setStats] = useState<any>(null);
+  useEffect(() => {
+    let live = true;
+    const load = () => getEventStats().then((s) => live && setStats(s)).catch(() => undefined);
+    load();
+    const timer = window.setInterval(load, 5000);
+    return () => {
+      live = false;
+      window.clearInterval(timer);
+    };
+  }, [connection.selected_ws]);
+
   const lastActivation = useMemo(() => {
     const hit = events.find((evt) => evt.topic.toLowerCase().includes("mind"));
     return hit ? new Date(hit.ts_ms).toLocaleTimeString() : "-";
@@ -69,6 +84,13 @@ export function OverviewView() {
           <div className="yx-health-card__value">0</div>
           <div className="yx-health-card__meta">violations</div>
         </div>
+        <div className="yx-health-card">
+          <div className="yx-health-card__title">Events</div>
+          <div className="yx-health-card__value">{stats ? `${stats.total.rate_1m.toFixed(1)}/s` : "-"}</div>
+          <div className="yx-health-card__meta">
+            {stats?.silent_topics?.length ? `silent: ${stats.silent_topics.join(", ")}` : `lag ${stats?.lag?.mean_ms ?? 0}ms avg`}
+          </div>
+        </div>
         <div className="yx-health-card">
           <div className="yx-health-card__title">Mind</div>
           <div className="yx-health-card__value">{lastActivation}</div>
@@ -97,7 +119,7 @@ export function OverviewView() {
                   ts_ms: evt.ts_ms,
                   ok: true,
                   request: { id: evt.trace_id || `evt-${evt.ts_ms}`, ts_ms: evt.ts_ms, name: evt.topic, args: {} },
-                  response: { id: evt.trace_id || `evt-${evt.ts_ms}`, ts_ms: evt.ts_ms, name: evt.topic, ok: true, result: evt.payload },
+                  response: { id: evt.trace_id || `evt-${evt.ts_ms}`, ts_ms: evt.ts_ms, name: evt.topic, ok: true, result: evt.data },
                 },
               })
             }
@@ -123,7 +145,7 @@ export function OverviewView() {
                   ts_ms: evt.ts_ms,
                   ok: true,
                   request: { id: evt.trace_id || `log-${evt.ts_ms}`, ts_ms: evt.ts_ms, name: evt.topic, args: {} },
-                  response: { id: evt.trace_id || `log-${evt.ts_ms}`, ts_ms: evt.ts_ms, name: evt.topic, ok: true, result: evt.payload },
+                  response: { id: evt.trace_id || `log-${evt.ts_ms}`, ts_ms: evt.ts_ms, name: evt.topic, ok: true, result: evt.data },
                 },
               })
             }
@@ -153,12 +175,15 @@ export function OverviewView() {
         </div>
       </Card>
 
+      {downOpen ? <DownPanel onClose={() => setDownOpen(false)} /> : null}
+
       <div className="yx-overview-footer-strip">
         <button type="button" onClick={() => runQuick("status")}>Status</button>
         <button type="button" onClick={() => runQuick("law.snapshot")}>Law Snapshot</button>
         <button type="button" onClick={() => runQuick("providers.discover")}>Providers Discover</button>
         <button type="button" onClick={() => runQuick("providers.list")}>Providers List</button>
         <button type="button" onClick={() => runQuick("shell.exec", { cmd: "tail -n 50 ~/.yai/run/dev/daemon.log" })}>Tail Logs</button>
+        <button type="button" onClick={() => setDownOpen(true)}>Down…</button>
       </div>
     </div>
   );
@@ -1,38 +1,20 @@
-import React, { useMemo, useState } from "react";
+import React, { useEffect, useState } from "react";
 import { Card } from "../components/Card";
+import { PairingWizard } from "../components/PairingWizard";
 import { useAppDispatch, useAppState } from "../state/store.tsx";
-import { executeCommand } from "../core/commands";
-import type { ProviderItem } from "../state/types";
-
-function normalizeLastSeen(v: unknown): number | null {
-  const n = Number(v);
-  if (!Number.isFinite(n) || n <= 0) return null;
-  // server: epoch seconds (tipico). Date vuole ms.
-  return n < 10_000_000_000 ? n * 1000 : n;
-}
-
-function normalizeProviders(result: any): ProviderItem[] {
-  const items = result?.items ?? result?.providers ?? result;
-  if (!Array.isArray(items)) return [];
-  return items.map((p: any) => ({
-    id: String(p.id || p.provider_id || p.name || "unknown"),
-    endpoint: p.endpoint ?? null,
-    model: p.model ?? null,
-    state: p.trust_state || p.state || null,
-    last_seen: normalizeLastSeen(p.last_seen),
-    last_error: p.last_error ?? null,
-  }));
-}
-
-function requiresArming(name: string): boolean {
-  // allineato al daemon: pair/attach/revoke/detach richiedono operator+arming
-  return (
-    name === "providers.pair" ||
-    name === "providers.attach" ||
-    name === "providers.revoke" ||
-    name === "providers.detach"
-  );
-}
+import {
+  attachProvider,
+  detachProvider,
+  discoverProviders,
+  getProviderHealth,
+  listProviders,
+  pairProvider,
+  probeProviders,
+  providerStatus,
+  revokeProvider,
+  usageReport,
+} from "../api/bridge";
+import type { ProviderHealth, UsageGroupBy, UsageReport } from "../state/types";
 
 export function ProvidersView() {
   const { providers } = useAppState();
@@ -41,94 +23,64 @@ export function ProvidersView() {
   const [endpoint, setEndpoint] = useState("");
   const [model, setModel] = useState("");
   const [providerId, setProviderId] = useState("");
+  const [health, setHealth] = useState<ProviderHealth[]>([]);
+  const [usage, setUsage] = useState<UsageReport | null>(null);
+  const [usageBy, setUsageBy] = useState<UsageGroupBy>("provider");
+  const [usageAll, setUsageAll] = useState(false);
 
-  const discoverArgs = useMemo(() => {
-    // se vuoti: manda stringa vuota, così server riceve Some("") e decide lui
-    // (oppure cambia qui a null se vuoi “None”)
-    return { endpoint: endpoint ?? "", model: model ?? "" };
-  }, [endpoint, model]);
-
-  async function refreshListAndStatus() {
-    // best-effort: non far fallire la UI se status/list falliscono dopo un comando
-    const list = await executeCommand(dispatch, "providers.list", {}, { arming: false }).catch(() => null);
-    if (list?.ok) dispatch({ type: "providers/set", items: normalizeProviders(list.result) });
-
-    const st = await executeCommand(dispatch, "providers.status", {}, { arming: false }).catch(() => null);
-    if (st?.ok) {
-      const active = (st.result as any)?.active ?? null;
-      if (active) {
-        dispatch({
-          type: "providers/active",
-          active: {
-            id: String(active.id || active.provider_id || active.name || "unknown"),
-            endpoint: active.endpoint ?? null,
-            model: active.model ?? null,
-            state: active.trust_state || active.state || null,
-            last_seen: normalizeLastSeen(active.last_seen),
-            last_error: active.last_error ?? null,
-          },
-        });
-      } else {
-        dispatch({ type: "providers/active", active: null });
-      }
-    }
-  }
+  useEffect(() => {
+    let live = true;
+    const load = () => getProviderHealth().then((h) => live && setHealth(h)).catch(() => undefined);
+    load();
+    const timer = window.setInterval(load, 10000);
+    return () => {
+      live = false;
+      window.clearInterval(timer);
+    };
+  }, []);
 
-  async function run(name: string, args: Record<string, unknown> = {}) {
-    const res = await executeCommand(dispatch, name, args, { arming: requiresArming(name) });
+  useEffect(() => {
+    usageReport({ group_by: usageBy, all_workspaces: usageAll })
+      .then(setUsage)
+      .catch(() => undefined);
+  }, [usageBy, usageAll]);
 
-    if (!res.ok) {
-      dispatch({ type: "providers/error", message: res.error?.message || "command failed" });
-      return;
-    }
+  const cost = (value: number | null) => (value == null || !usage ? "-" : `${value.toFixed(4)} ${usage.currency}`);
 
-    if (name === "providers.list" || name === "providers.discover") {
-      dispatch({ type: "providers/set", items: normalizeProviders(res.result as any) });
-      await refreshListAndStatus();
-      return;
-    }
+  async function refreshList() {
+    const list = await listProviders();
+    dispatch({ type: "providers/set", items: list.items });
+  }
 
-    if (name === "providers.status") {
-      const active = (res.result as any)?.active ?? null;
-      if (active) {
-        dispatch({
-          type: "providers/active",
-          active: {
-            id: String(active.id || active.provider_id || active.name || "unknown"),
-            endpoint: active.endpoint ?? null,
-            model: active.model ?? null,
-            state: active.trust_state || active.state || null,
-            last_seen: normalizeLastSeen(active.last_seen),
-            last_error: active.last_error ?? null,
-          },
-        });
-      } else {
-        dispatch({ type: "providers/active", active: null });
-      }
-      return;
-    }
+  async function refreshStatus() {
+    const status = await providerStatus();
+    dispatch({ type: "providers/active", active: status.active });
+  }
 
-    // lifecycle mutations: dopo esecuzione aggiorna vista
-    if (
-      name === "providers.pair" ||
-      name === "providers.attach" ||
-      name === "providers.revoke" ||
-      name === "providers.detach"
-    ) {
-      await refreshListAndStatus();
+  async function run(action: () => Promise<unknown>) {
+    try {
+      await action();
+      dispatch({ type: "providers/error", message: "" });
+    } catch (e) {
+      // lifecycle errors come back as "code: message", e.g. invalid_transition
+      dispatch({ type: "providers/error", message: String(e) });
+      return;
     }
+    // best-effort: keep the view in sync after a lifecycle change
+    await refreshList().catch(() => {});
+    await refreshStatus().catch(() => {});
   }
 
   return (
     <div className="yx-providers">
       <Card title="Providers" subtitle="Lifecycle and status">
         <div className="yx-providers__actions">
-          <button type="button" onClick={() => run("providers.discover", discoverArgs)}>
+          <button type="button" onClick={() => run(() => discoverProviders(endpoint, model))}>
             Discover
           </button>
-          <button type="button" onClick={() => run("providers.list")}>List</button>
-          <button type="button" onClick={() => run("providers.status")}>Status</button>
-          <button type="button" onClick={() => run("providers.detach")}>Detach</button>
+          <button type="button" onClick={() => run(refreshList)}>List</button>
+          <button type="button" onClick={() => run(refreshStatus)}>Status</button>
+          <button type="button" onClick={() => run(detachProvider)}>Detach</button>
         </div>
 
         {providers.lastError ? <div className="yx-error">{providers.lastError}</div> : null}
@@ -167,10 +119,10 @@ export function ProvidersView() {
                 }
               >
                 <div>{p.id}</div>
-                <div>{p.state || "-"}</div>
+                <div>{p.state}</div>
                 <div>{p.endpoint || "-"}</div>
                 <div>{p.model || "-"}</div>
-                <div>{p.last_seen ? new Date(p.last_seen).toLocaleTimeString() : "-"}</div>
+                <div>{p.last_seen_ms ? new Date(p.last_seen_ms).toLocaleTimeString() : "-"}</div>
                 <div className="yx-muted">{p.last_error || "-"}</div>
               </button>
             ))
@@ -178,6 +130,13 @@ export function ProvidersView() {
         </div>
       </Card>
 
+      <PairingWizard
+        onChanged={() => {
+          refreshList().catch(() => undefined);
+          refreshStatus().catch(() => undefined);
+        }}
+      />
+
       <Card title="Lifecycle" subtitle="Pair / attach / revoke">
         <div className="yx-input-grid">
           <input type="text" placeholder="endpoint" value={endpoint} onChange={(e) => setEndpoint(e.target.value)} />
@@ -186,9 +145,9 @@ export function ProvidersView() {
         </div>
 
         <div className="yx-providers__actions" style={{ marginTop: 8 }}>
-          <button type="button" onClick={() => run("providers.pair", { id: providerId, endpoint, model })}>Pair</button>
-          <button type="button" onClick={() => run("providers.attach", { id: providerId, model: model || null })}>Attach</button>
-          <button type="button" onClick={() => run("providers.revoke", { id: providerId })}>Revoke</button>
+          <button type="button" onClick={() => run(() => pairProvider(providerId, endpoint, model))}>Pair</button>
+          <button type="button" onClick={() => run(() => attachProvider(providerId, model))}>Attach</button>
+          <button type="button" onClick={() => run(() => revokeProvider(providerId))}>Revoke</button>
         </div>
       </Card>
 
@@ -196,7 +155,7 @@ export function ProvidersView() {
         {providers.active ? (
           <div className="yx-table__row-static">
             <div>{providers.active.id}</div>
-            <div>{providers.active.state || "-"}</div>
+            <div>{providers.active.state}</div>
             <div>{providers.active.endpoint || "-"}</div>
             <div>{providers.active.model || "-"}</div>
           </div>
@@ -204,6 +163,98 @@ export function ProvidersView() {
           <div className="yx-muted">No active provider.</div>
         )}
       </Card>
+
+      <Card title="Health" subtitle="Probes of attached and paired providers">
+        <div className="yx-providers__actions">
+          <button type="button" onClick={() => probeProviders().then(setHealth).catch(() => undefined)}>
+            Probe now
+          </button>
+        </div>
+
+        <div className="yx-table">
+          <div className="yx-table__head yx-table__head--health">
+            <div>ID</div>
+            <div>Health</div>
+            <div>Uptime</div>
+            <div>Latency</div>
+            <div>Failures</div>
+            <div>Since</div>
+          </div>
+
+          {health.length === 0 ? (
+            <div className="yx-muted">No probes yet.</div>
+          ) : (
+            health.map((h) => (
+              <div key={h.id} className="yx-table__row-static yx-table__row--health">
+                <div>{h.id}</div>
+                <div>{h.status}</div>
+                <div>{h.uptime_pct.toFixed(1)}%</div>
+                <div>{h.latency_ms != null ? `${h.latency_ms} ms` : "-"}</div>
+                <div>
+                  {h.failures}/{h.checks}
+                </div>
+                <div>{new Date(h.since_ms).toLocaleTimeString()}</div>
+              </div>
+            ))
+          )}
+        </div>
+
+        {health.some((h) => h.recent_failures.length > 0) ? (
+          <div style={{ marginTop: 8 }}>
+            {health.flatMap((h) =>
+              h.recent_failures.map((f) => (
+                <div key={`${f.id}-${f.ts_ms}`} className="yx-muted">
+                  {new Date(f.ts_ms).toLocaleTimeString()} {f.id} ({f.method}): {f.error || "failed"}
+                </div>
+              ))
+            )}
+          </div>
+        ) : null}
+      </Card>
+
+      <Card title="Usage" subtitle="Chat tokens, latency and cost; prices from [usage] in yx.toml">
+        <div className="yx-providers__actions">
+          <select value={usageBy} onChange={(e) => setUsageBy(e.target.value as UsageGroupBy)}>
+            <option value="provider">by provider</option>
+            <option value="model">by model</option>
+            <option value="session">by session</option>
+            <option value="workspace">by workspace</option>
+            <option value="day">by day</option>
+          </select>
+          <label>
+            <input type="checkbox" checked={usageAll} onChange={(e) => setUsageAll(e.target.checked)} /> all workspaces
+          </label>
+        </div>
+
+        <div className="yx-table">
+          <div className="yx-table__head yx-table__head--usage">
+            <div>Key</div>
+            <div>Requests</div>
+            <div>Prompt</div>
+            <div>Completion</div>
+            <div>Latency</div>
+            <div>Cost</div>
+          </div>
+
+          {!usage || usage.rows.length === 0 ? (
+            <div className="yx-muted">No usage recorded.</div>
+          ) : (
+            [...usage.rows, usage.totals].map((row) => (
+              <div key={row.key} className="yx-table__row-static yx-table__row--usage">
+                <div>{row.key}</div>
+                <div>{row.requests}</div>
+                <div>{row.prompt_tokens}</div>
+                <div>{row.completion_tokens}</div>
+                <div>{row.avg_latency_ms} ms</div>
+                <div title={row.unpriced ? `${row.unpriced} unpriced` : undefined}>
+                  {cost(row.cost)}
+                  {row.unpriced ? " *" : ""}
+                </div>
+              </div>
+            ))
+          )}
+        </div>
+      </Card>
     </div>
   );
 }
@@ -1,33 +1,82 @@
 import React, { useRef, useState } from "react";
-import { executeCommand } from "../core/commands";
+import { execShell } from "../api/bridge";
+import { RecordingsPanel } from "../components/RecordingsPanel";
 import { useAppDispatch, useAppState } from "../state/store.tsx";
-import type { ShellEntry } from "../state/types";
+import type { ShellChunk, ShellEntry, ShellExit } from "../state/types";
 
-function normalizeShellEntry(result: any, command: string): ShellEntry {
+function entryFrom(command: string, chunks: ShellChunk[], exit: ShellExit): ShellEntry {
+  const text = (stream: ShellChunk["stream"]) =>
+    chunks
+      .filter((c) => c.stream === stream)
+      .map((c) => c.data)
+      .join("");
   return {
     id: `shell-${Date.now()}`,
     ts_ms: Date.now(),
     command,
-    stdout: result?.stdout ?? "",
-    stderr: result?.stderr ?? "",
-    exit_code: typeof result?.exit_code === "number" ? result.exit_code : 0,
+    stdout: text("stdout"),
+    stderr: text("stderr"),
+    exit_code: exit.exit_code ?? -1,
+    chunks,
+    signal: exit.signal,
+    timed_out: exit.timed_out,
+    truncated: exit.truncated,
+    trace_id: exit.trace_id,
   };
 }
 
+function exitLine(entry: ShellEntry) {
+  const parts = [entry.signal ? `signal ${entry.signal}` : `exit ${entry.exit_code}`];
+  if (entry.timed_out) parts.push("timed out");
+  if (entry.truncated) parts.push("output truncated");
+  if (entry.trace_id) parts.push(entry.trace_id);
+  return parts.join(" · ");
+}
+
+function Output({ entry }: { entry: Pick<ShellEntry, "stdout" | "stderr" | "chunks"> }) {
+  if (!entry.chunks) {
+    return (
+      <>
+        {entry.stdout ? <pre>{entry.stdout}</pre> : null}
+        {entry.stderr ? <pre>{entry.stderr}</pre> : null}
+      </>
+    );
+  }
+  return (
+    <pre>
+      {entry.chunks.map((c) => (
+        <span key={c.seq} className={c.stream === "stderr" ? "yx-terminal__stderr" : undefined}>
+          {c.data}
+        </span>
+      ))}
+    </pre>
+  );
+}
+
 export function ShellView() {
   const { shell } = useAppState();
   const dispatch = useAppDispatch();
   const [input, setInput] = useState("");
   const [historyIndex, setHistoryIndex] = useState(-1);
+  const [running, setRunning] = useState<{ command: string; chunks: ShellChunk[] } | null>(null);
   const inputRef = useRef<HTMLInputElement | null>(null);
 
   const run = async () => {
     const cmd = input.trim();
-    if (!cmd) return;
-    const res = await executeCommand(dispatch, "shell.exec", { cmd }, { arming: true });
-    if (res.ok) {
-      dispatch({ type: "shell/add", entry: normalizeShellEntry(res.result as any, cmd) });
-    } else {
+    if (!cmd || running) return;
+    setInput("");
+    setHistoryIndex(-1);
+    setRunning({ command: cmd, chunks: [] });
+    const chunks: ShellChunk[] = [];
+    try {
+      const exit = await execShell({ cmd }, (output) => {
+        if (output.type !== "chunk") return;
+        chunks.push(output);
+        setRunning({ command: cmd, chunks: [...chunks] });
+      });
+      dispatch({ type: "shell/add", entry: entryFrom(cmd, chunks, exit) });
+    } catch (e) {
+      // denials come back as "code: message", e.g. arming_required
       dispatch({
         type: "shell/add",
         entry: {
@@ -35,13 +84,13 @@ export function ShellView() {
           ts_ms: Date.now(),
           command: cmd,
           stdout: "",
-          stderr: res.error?.message || "command failed",
+          stderr: String(e) || "command failed",
           exit_code: 1,
         },
       });
+    } finally {
+      setRunning(null);
     }
-    setInput("");
-    setHistoryIndex(-1);
   };
 
   const handleKeyDown = (e: React.KeyboardEvent<HTMLInputElement>) => {
@@ -97,18 +146,24 @@ export function ShellView() {
         </div>
       </div>
       <div className="yx-terminal__body">
-        {shell.entries.length === 0 ? (
+        {shell.entries.length === 0 && !running ? (
           <div className="yx-muted">No commands yet.</div>
         ) : (
           shell.entries.map((entry) => (
             <div key={entry.id} className="yx-terminal__entry">
               <div className="yx-terminal__prompt">$ {entry.command}</div>
-              {entry.stdout ? <pre>{entry.stdout}</pre> : null}
-              {entry.stderr ? <pre>{entry.stderr}</pre> : null}
-              <div className="yx-muted">exit {entry.exit_code}</div>
+              <Output entry={entry} />
+              <div className="yx-muted">{exitLine(entry)}</div>
             </div>
           ))
         )}
+        {running ? (
+          <div className="yx-terminal__entry">
+            <div className="yx-terminal__prompt">$ {running.command}</div>
+            <Output entry={{ stdout: "", stderr: "", chunks: running.chunks }} />
+            <div className="yx-muted">running…</div>
+          </div>
+        ) : null}
       </div>
       <div className="yx-terminal__input">
         <span className="yx-terminal__prompt-symbol">$</span>
@@ -120,10 +175,11 @@ export function ShellView() {
           onChange={(e) => setInput(e.target.value)}
           onKeyDown={handleKeyDown}
         />
-        <button type="button" onClick={run}>
+        <button type="button" disabled={Boolean(running)} onClick={run}>
           Run
         </button>
       </div>
+      <RecordingsPanel />
     </div>
   );
 }